@@ -6,6 +6,7 @@
 //! configuration.
 
 extern crate libc;
+extern crate regex;
 extern crate xcb;
 
 extern crate env_logger;
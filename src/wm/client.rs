@@ -1,11 +1,15 @@
-use std::cell::{RefCell,RefMut};
-use std::collections::HashMap;
+use std::cell::{Cell,RefCell,RefMut};
+use std::cmp;
+use std::collections::{hash_map, HashMap, HashSet};
+use std::mem;
 use std::rc::{Rc,Weak};
 
+use regex::Regex;
+
 use xcb::xproto;
 
 use wm::config::Tag;
-use wm::layout::Layout;
+use wm::layout::{Geometry, Layout, LayoutMessage, ScreenSize, Split};
 use wm::window_system::WmCommand;
 
 /// Client properties, as obtained from the X server.
@@ -17,6 +21,105 @@ pub struct ClientProps {
     pub name: String,
     /// the client's class(es)
     pub class: Vec<String>,
+    /// the protocols the client supports, as listed in `WM_PROTOCOLS`
+    pub protocols: Vec<xproto::Atom>,
+    /// whether the client asked to start iconified in `WM_HINTS`
+    pub start_iconic: bool,
+    /// whether the client wants to be given the input focus, as set in
+    /// `WM_HINTS`
+    pub accepts_input: bool,
+    /// the id of the process owning the client, as set in `_NET_WM_PID`
+    pub pid: Option<u32>,
+    /// the time of the last user activity in the client, as set in
+    /// `_NET_WM_USER_TIME`
+    pub user_time: Option<u32>,
+}
+
+/// Size constraints of a client, as set in `WM_NORMAL_HINTS`.
+///
+/// Sizes are given as `(width, height)` pairs, aspect ratios as
+/// `(numerator, denominator)` pairs of width and height.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeHints {
+    /// the minimal size of the client, if any
+    pub min_size: Option<(u16, u16)>,
+    /// the maximal size of the client, if any
+    pub max_size: Option<(u16, u16)>,
+    /// the steps in which the client prefers to be resized, if any
+    pub resize_inc: Option<(u16, u16)>,
+    /// the size resize increments are counted from
+    pub base_size: (u16, u16),
+    /// the minimal aspect ratio of the client, if any
+    pub min_aspect: Option<(u32, u32)>,
+    /// the maximal aspect ratio of the client, if any
+    pub max_aspect: Option<(u32, u32)>,
+}
+
+impl SizeHints {
+    /// Adjust the size of a geometry to the hints, keeping its position.
+    ///
+    /// Computations are done in 64 bits, as the aspect ratios can use the
+    /// whole 32 bit range.
+    pub fn constrain(&self, geometry: &Geometry) -> Geometry {
+        let (mut width, mut height) =
+            (geometry.width as u64, geometry.height as u64);
+        if let Some((num, den)) = self.max_aspect {
+            let (num, den) = (num as u64, den as u64);
+            if num > 0 && den > 0 && width * den > height * num {
+                width = height * num / den;
+            }
+        }
+        if let Some((num, den)) = self.min_aspect {
+            let (num, den) = (num as u64, den as u64);
+            if num > 0 && den > 0 && width * den < height * num {
+                height = width * den / num;
+            }
+        }
+        if let Some((inc_w, inc_h)) = self.resize_inc {
+            let (base_w, base_h) =
+                (self.base_size.0 as u64, self.base_size.1 as u64);
+            if inc_w > 0 && width > base_w {
+                width -= (width - base_w) % inc_w as u64;
+            }
+            if inc_h > 0 && height > base_h {
+                height -= (height - base_h) % inc_h as u64;
+            }
+        }
+        if let Some((max_w, max_h)) = self.max_size {
+            if max_w > 0 {
+                width = cmp::min(width, max_w as u64);
+            }
+            if max_h > 0 {
+                height = cmp::min(height, max_h as u64);
+            }
+        }
+        if let Some((min_w, min_h)) = self.min_size {
+            width = cmp::max(width, min_w as u64);
+            height = cmp::max(height, min_h as u64);
+        }
+        Geometry {
+            x: geometry.x,
+            y: geometry.y,
+            width: cmp::max(1, width) as u16,
+            height: cmp::max(1, height) as u16,
+        }
+    }
+
+    /// Fit a geometry adjusted to the hints into a tile, centered on it.
+    ///
+    /// The geometry never gets larger than the tile, even if the client
+    /// asked for a larger minimal size.
+    pub fn fit(&self, tile: &Geometry) -> Geometry {
+        let geometry = self.constrain(tile);
+        let width = cmp::min(geometry.width, tile.width);
+        let height = cmp::min(geometry.height, tile.height);
+        Geometry {
+            x: tile.x + (tile.width - width) / 2,
+            y: tile.y + (tile.height - height) / 2,
+            width: width,
+            height: height,
+        }
+    }
 }
 
 /// A client wrapping a window.
@@ -39,18 +142,54 @@ pub struct Client {
     urgent: bool,
     /// all tags this client is visible on, in no particular order
     tags: Vec<Tag>,
+    /// indicates whether the client is floating above the tiled windows
+    floating: bool,
+    /// indicates whether the client is minimized and thus not shown
+    minimized: bool,
+    /// indicates whether the client covers it's whole output, without
+    /// a border
+    fullscreen: bool,
+    /// indicates whether the client is shown on all tags
+    sticky: bool,
+    /// the client's geometry while floating, if already determined,
+    /// remembered while the client is tiled
+    float_geometry: Option<Geometry>,
+    /// the size constraints the client asked for
+    size_hints: SizeHints,
+    /// whether the floating client is maximized vertically and horizontally
+    maximized: (bool, bool),
+    /// the client's floating geometry before it got maximized
+    unmaximized_geometry: Option<Geometry>,
+    /// the orientation of the split from the client's predecessor, if any
+    split: Option<Split>,
+    /// position of the client in the sequence of managed clients,
+    /// assigned when added to a `ClientSet`
+    sequence: u64,
 }
 
 impl Client {
     /// Setup a new client for a specific window, on a set of tags
     /// and with given properties.
+    ///
+    /// Clients asking to start iconic are minimized right away.
     pub fn new(window: xproto::Window, tags: Vec<Tag>, props: ClientProps)
         -> Client {
+        let minimized = props.start_iconic;
         Client {
             window: window,
             props: props,
             urgent: false,
             tags: tags,
+            floating: false,
+            minimized: minimized,
+            fullscreen: false,
+            sticky: false,
+            float_geometry: None,
+            size_hints: SizeHints::default(),
+            maximized: (false, false),
+            unmaximized_geometry: None,
+            split: None,
+            sequence: 0,
         }
     }
 
@@ -77,14 +216,250 @@ impl Client {
         }
     }
 
+    /// Exchange two tags on a window, returning whether changes have been
+    /// made.
+    ///
+    /// If the window has both or none of the tags attached, it stays as it
+    /// is.
+    pub fn swap_tags(&mut self, a: &Tag, b: &Tag) -> bool {
+        let has_a = self.tags.contains(a);
+        let has_b = self.tags.contains(b);
+        if has_a == has_b {
+            return false;
+        }
+        for tag in self.tags.iter_mut() {
+            if *tag == *a {
+                *tag = b.clone();
+            } else if *tag == *b {
+                *tag = a.clone();
+            }
+        }
+        true
+    }
+
+    /// Get the tags the client is visible on.
+    pub fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    /// Get the client's properties.
+    pub fn props(&self) -> &ClientProps {
+        &self.props
+    }
+
+    /// Get the client's current title.
+    pub fn name(&self) -> &str {
+        &self.props.name
+    }
+
+    /// Update the client's title.
+    pub fn set_name(&mut self, name: String) {
+        self.props.name = name;
+    }
+
+    /// Check whether the client has the urgency flag set.
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+
+    /// Set or reset the urgency flag of the client.
+    pub fn set_urgent(&mut self, urgent: bool) {
+        self.urgent = urgent;
+    }
+
+    /// Check whether the client is floating.
+    pub fn is_floating(&self) -> bool {
+        self.floating
+    }
+
+    /// Check whether the client is minimized.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Minimize or restore the client.
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+    }
+
+    /// Check whether the client is shown fullscreen.
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen
+    }
+
+    /// Show the client fullscreen or restore it.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    /// Check whether the client is shown on all tags.
+    pub fn is_sticky(&self) -> bool {
+        self.sticky
+    }
+
+    /// Toggle whether the client is shown on all tags.
+    ///
+    /// The client's tags are kept, so that it returns to them when it stops
+    /// being sticky.
+    pub fn toggle_sticky(&mut self) {
+        self.sticky = !self.sticky;
+    }
+
+    /// Toggle the floating state of the client.
+    ///
+    /// The floating geometry is kept when the client gets tiled, so that it
+    /// is restored when the client floats again.
+    pub fn toggle_floating(&mut self) {
+        self.floating = !self.floating;
+    }
+
+    /// Get the client's floating geometry, centering it on the screen if it
+    /// hasn't been determined yet.
+    ///
+    /// The geometry is adjusted to the client's size hints.
+    pub fn float_geometry(&mut self, screen: &ScreenSize, border_width: u16)
+        -> Geometry {
+        if self.float_geometry.is_none() {
+            self.float_geometry = Some(Geometry::centered(
+                screen, screen.width / 2, screen.height / 2, border_width));
+        }
+        self.size_hints.constrain(self.float_geometry.as_ref().unwrap())
+    }
+
+    /// Get the size constraints of the client.
+    pub fn size_hints(&self) -> &SizeHints {
+        &self.size_hints
+    }
+
+    /// Set the size constraints of the client.
+    pub fn set_size_hints(&mut self, size_hints: SizeHints) {
+        self.size_hints = size_hints;
+    }
+
+    /// Set the client's floating geometry.
+    pub fn set_float_geometry(&mut self, geometry: Geometry) {
+        self.float_geometry = Some(geometry);
+    }
+
+    /// Move the client to another output, showing it on a set of tags
+    /// viewed there.
+    ///
+    /// A floating client keeps it's position relative to the output, as far
+    /// as it fits onto the new one.
+    pub fn move_to_output(&mut self, tags: &[Tag], src: &ScreenSize,
+                          dest: &ScreenSize, border_width: u16) {
+        self.set_tags(tags);
+        if self.floating {
+            let geometry = self.float_geometry(src, border_width);
+            self.float_geometry =
+                Some(geometry.moved_to(src, dest, border_width));
+        }
+    }
+
+    /// Check whether the client is maximized vertically and horizontally.
+    pub fn maximized(&self) -> (bool, bool) {
+        self.maximized
+    }
+
+    /// Maximize the client's floating geometry vertically, horizontally or
+    /// both.
+    ///
+    /// The geometry the client had before being maximized is restored as
+    /// soon as it isn't maximized in any direction anymore.
+    pub fn set_maximized(&mut self, vert: bool, horz: bool,
+                         screen: &ScreenSize, border_width: u16) {
+        let base = match self.unmaximized_geometry.take() {
+            Some(geometry) => geometry,
+            None => self.float_geometry(screen, border_width),
+        };
+        if vert || horz {
+            self.float_geometry =
+                Some(base.maximize(screen, vert, horz, border_width));
+            self.unmaximized_geometry = Some(base);
+        } else {
+            self.float_geometry = Some(base);
+        }
+        self.maximized = (vert, horz);
+    }
+
+    /// Get the split orientation requested by the client, if any.
+    pub fn split(&self) -> Option<Split> {
+        self.split
+    }
+
+    /// Set the split orientation requested by the client.
+    pub fn set_split(&mut self, split: Option<Split>) {
+        self.split = split;
+    }
+
     /// Check whether a client is visible on a set of tags.
+    ///
+    /// Sticky clients are visible on any set of tags.
     pub fn match_tags(&self, tags: &[Tag]) -> bool {
-        self.tags
+        self.sticky || self.tags
             .iter()
             .any(|t| tags.iter().find(|t2| t == *t2).is_some())
     }
 }
 
+/// A pattern matched against a string property of clients.
+pub enum Pattern {
+    /// the property has to be equal to the string
+    Exact(String),
+    /// the property has to match the regular expression
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// Check whether a string matches the pattern.
+    pub fn matches(&self, string: &str) -> bool {
+        match *self {
+            Pattern::Exact(ref s) => s == string,
+            Pattern::Regex(ref r) => r.is_match(string),
+        }
+    }
+}
+
+/// A declarative rule deciding upon placement of clients.
+///
+/// A rule applies to clients matching all the patterns it has, so that a
+/// rule without any patterns applies to all clients.
+#[derive(Default)]
+pub struct ClientRule {
+    /// pattern one of the client's classes has to match, if any
+    pub class: Option<Pattern>,
+    /// pattern the client's title has to match, if any
+    pub name: Option<Pattern>,
+    /// tags the client is placed on, if any
+    pub tags: Option<Vec<Tag>>,
+    /// make the client floating?
+    pub floating: bool,
+    /// name of the layout the tagset showing the client is switched to, if
+    /// any
+    pub layout: Option<&'static str>,
+}
+
+impl ClientRule {
+    /// Check whether the rule applies to a client with the given
+    /// properties.
+    pub fn matches(&self, props: &ClientProps) -> bool {
+        self.class
+            .as_ref()
+            .map_or(true, |p| props.class.iter().any(|c| p.matches(c))) &&
+            self.name.as_ref().map_or(true, |p| p.matches(&props.name))
+    }
+
+    /// Get the tags of the first rule specifying tags that applies to a
+    /// client with the given properties, if any.
+    pub fn tags_from(rules: &[ClientRule], props: &ClientProps)
+        -> Option<Vec<Tag>> {
+        rules
+            .iter()
+            .find(|r| r.tags.is_some() && r.matches(props))
+            .and_then(|r| r.tags.clone())
+    }
+}
+
 /// Weak reference to a client.
 ///
 /// Used to store references to clients that are placed in secondary
@@ -105,6 +480,25 @@ pub type ClientRef = Rc<RefCell<Client>>;
 /// the tagset given.
 pub type OrderEntry = (Option<WeakClientRef>, Vec<WeakClientRef>);
 
+/// Placement of a new client in the orderings of the tagsets it is shown on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertPolicy {
+    /// the client becomes the master window
+    AsMaster,
+    /// the client is appended to the end of the stack
+    AsSlave,
+    /// the client is placed after the focused window
+    AfterFocused,
+}
+
+/// Add a signed offset to an index, wrapping around in both directions.
+///
+/// `len` has to be nonzero.
+fn wrap_index(index: usize, offset: isize, len: usize) -> usize {
+    let len = len as isize;
+    (((index as isize + offset) % len + len) % len) as usize
+}
+
 /// A client set.
 ///
 /// Managing all direct children of the root window, as well as
@@ -117,6 +511,17 @@ pub struct ClientSet {
     clients: HashMap<xproto::Window, ClientRef>,
     /// ordered subsets of clients associated with tagsets
     order: HashMap<Vec<Tag>, OrderEntry>,
+    /// clients focused on tagsets, most recent last
+    focus_history: HashMap<Vec<Tag>, Vec<WeakClientRef>>,
+    /// clients that have been marked urgent, most recent last
+    urgent: Vec<WeakClientRef>,
+    /// sequence number of the next client added
+    next_sequence: u64,
+    /// windows whose references are to be fixed at the end of the running
+    /// transaction, if any
+    deferred: Option<Vec<xproto::Window>>,
+    /// windows the layout didn't assign a geometry when last arranged
+    hidden: HashSet<xproto::Window>,
 }
 
 impl ClientSet {
@@ -125,6 +530,11 @@ impl ClientSet {
         ClientSet {
             clients: HashMap::new(),
             order: HashMap::new(),
+            focus_history: HashMap::new(),
+            urgent: Vec::new(),
+            next_sequence: 0,
+            deferred: None,
+            hidden: HashSet::new(),
         }
     }
 
@@ -134,15 +544,104 @@ impl ClientSet {
         self.clients.get(&window)
     }
 
+    /// Get the windows of all clients, in the order they have been added.
+    pub fn windows(&self) -> Vec<xproto::Window> {
+        let mut clients: Vec<&ClientRef> = self.clients.values().collect();
+        clients.sort_by_key(|cl| cl.borrow().sequence);
+        clients.iter().map(|cl| cl.borrow().window).collect()
+    }
+
+    /// Get the tags of the client corresponding to a window.
+    pub fn tags_of(&self, window: xproto::Window) -> Option<Vec<Tag>> {
+        self.clients
+            .get(&window)
+            .map(|c| c.borrow().tags().to_vec())
+    }
+
+    /// Check whether no client is visible on a tag.
+    pub fn is_tag_empty(&self, tag: &Tag) -> bool {
+        !self.clients
+            .values()
+            .any(|c| c.borrow().tags().contains(tag))
+    }
+
+    /// Get the windows of all clients belonging to a process, ordered by
+    /// window.
+    pub fn windows_for_pid(&self, pid: u32) -> Vec<xproto::Window> {
+        let mut res: Vec<xproto::Window> = self.clients
+            .values()
+            .filter(|c| c.borrow().props().pid == Some(pid))
+            .map(|c| c.borrow().window)
+            .collect();
+        res.sort();
+        res
+    }
+
+    /// Forget which windows have been hidden by the layout.
+    pub fn clear_hidden(&mut self) {
+        self.hidden.clear();
+    }
+
+    /// Remember that the layout didn't assign a geometry to a window.
+    pub fn mark_hidden(&mut self, window: xproto::Window) {
+        self.hidden.insert(window);
+    }
+
+    /// Mark the client corresponding to a window as urgent.
+    ///
+    /// The client is remembered as the most recently urgent one.
+    pub fn mark_urgent(&mut self, window: xproto::Window) {
+        if let Some(client) = self.clients.get(&window) {
+            client.borrow_mut().set_urgent(true);
+            self.urgent.retain(|r| !Self::is_ref_to_client(r, client));
+            self.urgent.push(Rc::downgrade(client));
+        }
+    }
+
+    /// Reset the urgency flag of the client corresponding to a window,
+    /// returning whether there is such a client.
+    pub fn clear_urgent(&mut self, window: xproto::Window) -> bool {
+        self.clients
+            .get(&window)
+            .map(|c| c.borrow_mut().set_urgent(false))
+            .is_some()
+    }
+
+    /// Get the tags and window of the most recently urgent client that
+    /// still has the urgency flag set, if any.
+    pub fn find_urgent(&self) -> Option<(Vec<Tag>, xproto::Window)> {
+        self.urgent
+            .iter()
+            .rev()
+            .filter_map(|r| r.upgrade())
+            .find(|c| c.borrow().is_urgent())
+            .map(|c| (c.borrow().tags().to_vec(), c.borrow().window))
+    }
+
+    /// Get all clients that have the urgency flag set, ordered by window.
+    pub fn urgent_clients(&self) -> Vec<ClientRef> {
+        let mut res: Vec<ClientRef> = self.clients
+            .values()
+            .filter(|c| c.borrow().is_urgent())
+            .cloned()
+            .collect();
+        res.sort_by_key(|c| c.borrow().window);
+        res
+    }
 
     /// Get the order entry for a set of tags.
     ///
-    /// If not present, create it.
+    /// If not present, create it, ordering the clients by the time they have
+    /// been added.
     pub fn get_order_or_insert(&mut self, tags: &[Tag]) -> &mut OrderEntry {
-        let clients: Vec<WeakClientRef> = self
+        let mut matching: Vec<&ClientRef> = self
             .clients
             .values()
             .filter(|cl| cl.borrow().match_tags(tags))
+            .collect();
+        matching.sort_by_key(|cl| cl.borrow().sequence);
+        let clients: Vec<WeakClientRef> = matching
+            .iter()
             .map(|r| Rc::downgrade(r))
             .collect();
         let focused = clients.first().map(|r| r.clone());
@@ -154,19 +653,93 @@ impl ClientSet {
     /// This need arises from the fact that we store weak references to
     /// clients. When these objects get deallocated, we clean up.
     fn clean(&mut self) {
-        for entry in self.order.values_mut() {
+        for history in self.focus_history.values_mut() {
+            history.retain(|r| r.upgrade().is_some());
+        }
+        let focus_history = &self.focus_history;
+        for (tags, entry) in self.order.iter_mut() {
             entry.1 = entry.1
                 .iter()
                 .filter_map(|c| c.upgrade().map(|_| c.clone()))
                 .collect();
             if entry.0.clone().and_then(|r| r.upgrade()).is_none() {
-                entry.0 = entry.1.first().map(|r| r.clone());
+                entry.0 =
+                    Self::last_focused(focus_history.get(tags), &entry.1);
             }
         }
+        self.urgent.retain(|r| r.upgrade().is_some());
+    }
+
+    /// Get the most recently focused client of a tagset still present on
+    /// it, or the first one if none of them has been focused yet.
+    fn last_focused(history: Option<&Vec<WeakClientRef>>,
+                    clients: &[WeakClientRef]) -> Option<WeakClientRef> {
+        history
+            .and_then(|h| h
+                .iter()
+                .rev()
+                .find(|r| r.upgrade().map_or(false, |c| clients
+                    .iter()
+                    .any(|r2| Self::is_ref_to_client(r2, &c))))
+            )
+            .or(clients.first())
+            .cloned()
+    }
+
+    /// Remember that a window has been focused on a set of tags, so that
+    /// focus returns to it when the client focused after it is gone.
+    pub fn record_focus(&mut self, tags: &[Tag], window: xproto::Window) {
+        let client = match self.clients.get(&window) {
+            Some(client) => client.clone(),
+            None => return,
+        };
+        let history = self.focus_history
+            .entry(tags.to_vec())
+            .or_insert_with(Vec::new);
+        history.retain(|r| !Self::is_ref_to_client(r, &client));
+        history.push(Rc::downgrade(&client));
     }
 
     /// Update all order entries to account for changes in a given client.
+    ///
+    /// During a transaction, the update is deferred until it ends.
     fn fix_references(&mut self, target_client: ClientRef) {
+        if let Some(ref mut deferred) = self.deferred {
+            let window = target_client.borrow().window;
+            if !deferred.contains(&window) {
+                deferred.push(window);
+            }
+            return;
+        }
+        self.apply_references(target_client);
+    }
+
+    /// Perform several operations on the client set, updating the order
+    /// entries only once at the end.
+    ///
+    /// Each client changed during the transaction has its references fixed
+    /// exactly once. Nested transactions are merged into the outermost one.
+    pub fn transaction<F, T>(&mut self, func: F) -> T
+        where F: FnOnce(&mut ClientSet) -> T {
+        let outermost = self.deferred.is_none();
+        if outermost {
+            self.deferred = Some(Vec::new());
+        }
+        let res = func(self);
+        if outermost {
+            for window in self.deferred.take().unwrap_or_default() {
+                if let Some(client) = self.clients.get(&window).cloned() {
+                    self.apply_references(client);
+                }
+            }
+        }
+        res
+    }
+
+    /// Update all order entries to account for changes in a given client,
+    /// right away.
+    fn apply_references(&mut self, target_client: ClientRef) {
+        let focus_history = &self.focus_history;
         for (tags, entry) in self.order.iter_mut() {
             if !target_client.borrow().match_tags(&tags) {
                 // filter tagset's client references
@@ -181,7 +754,7 @@ impl ClientSet {
                     )
                     .collect();
                 // if left pointing to a moved client, set focus reference
-                // to current master client
+                // to the client focused before it
                 entry.0 = entry.0
                     .iter()
                     .filter_map(|r|
@@ -192,7 +765,8 @@ impl ClientSet {
                         }
                     )
                     .next()
-                    .or(entry.1.first().map(|c| c.clone()));
+                    .or_else(|| Self::last_focused(focus_history.get(tags),
+                                                   &entry.1));
             } else if entry.1
                 .iter()
                 .find(|r| Self::is_ref_to_client(*r, &target_client))
@@ -217,9 +791,12 @@ impl ClientSet {
     /// Add a new client to the client store.
     ///
     /// Adds client object to master `HashMap` and creates references to
-    /// on the tagsets the client is visible on.
-    // TODO: add as_master/as_slave distinction
-    pub fn add(&mut self, client: Client) {
+    /// on the tagsets the client is visible on, placed according to the
+    /// insertion policy. The client gets focused on these tagsets, unless it
+    /// is minimized.
+    pub fn add(&mut self, mut client: Client, policy: InsertPolicy) {
+        client.sequence = self.next_sequence;
+        self.next_sequence += 1;
         let window = client.window;
         let dummy_client = client.clone();
         let wrapped_client = Rc::new(RefCell::new(client));
@@ -228,12 +805,105 @@ impl ClientSet {
         for (tags, &mut (ref mut current, ref mut clients))
             in self.order.iter_mut() {
             if dummy_client.match_tags(tags) {
-                clients.push(weak.clone());
-                *current = Some(weak.clone());
+                let index = match policy {
+                    InsertPolicy::AsMaster => 0,
+                    InsertPolicy::AsSlave => clients.len(),
+                    InsertPolicy::AfterFocused => current
+                        .as_ref()
+                        .and_then(|c| c.upgrade())
+                        .and_then(|c| clients
+                            .iter()
+                            .position(|r| Self::is_ref_to_client(r, &c))
+                        )
+                        .map_or(clients.len(), |i| i + 1),
+                };
+                clients.insert(index, weak.clone());
+                if !dummy_client.is_minimized() {
+                    *current = Some(weak.clone());
+                }
             }
         }
     }
 
+    /// Exchange two tags on all clients, returning whether changes have been
+    /// made.
+    ///
+    /// Clients having both tags attached stay where they are.
+    pub fn swap_tags(&mut self, a: &Tag, b: &Tag) -> bool {
+        let changed: Vec<ClientRef> = self.clients
+            .values()
+            .filter(|c| c.borrow_mut().swap_tags(a, b))
+            .cloned()
+            .collect();
+        for client in changed.iter() {
+            self.fix_references(client.clone());
+        }
+        !changed.is_empty()
+    }
+
+    /// Add a client like `add`, notifying the layouts of all tagsets showing
+    /// it tiled of where it got inserted.
+    ///
+    /// Tagsets without other tiled clients aren't notified, as layouts
+    /// start keeping track of windows from the second one on.
+    pub fn add_to_tagsets<'a, I>(&mut self, client: Client,
+                                 policy: InsertPolicy, tagsets: I)
+        where I: Iterator<Item=&'a mut TagSet> {
+        let window = client.window;
+        let mut notified = Vec::new();
+        for tagset in tagsets.filter(|t| client.match_tags(&t.tags)) {
+            let tiled = self.tiled_windows(&tagset.tags);
+            if !tiled.is_empty() {
+                let focused = self
+                    .get_focused_window(&tagset.tags)
+                    .and_then(|f| tiled.iter().position(|w| *w == f))
+                    .unwrap_or(tiled.len() - 1);
+                notified.push((tagset, focused));
+            }
+        }
+        self.add(client, policy);
+        for (tagset, focused) in notified {
+            if let Some(index) = self
+                .tiled_windows(&tagset.tags)
+                .iter()
+                .position(|w| *w == window) {
+                tagset.client_added(index, focused);
+            }
+        }
+    }
+
+    /// Notify the layouts of all tagsets showing a client tiled that it is
+    /// going to be removed.
+    pub fn notify_removal<'a, I>(&mut self, window: xproto::Window,
+                                 tagsets: I)
+        where I: Iterator<Item=&'a mut TagSet> {
+        for tagset in tagsets {
+            if let Some(index) = self
+                .tiled_windows(&tagset.tags)
+                .iter()
+                .position(|w| *w == window) {
+                tagset.client_removed(index);
+            }
+        }
+    }
+
+    /// Get the windows of the tiled clients shown on a set of tags, in the
+    /// order they are passed to layouts.
+    ///
+    /// Floating, fullscreen and minimized clients are left out.
+    pub fn tiled_windows(&mut self, tags: &[Tag]) -> Vec<xproto::Window> {
+        self.get_order_or_insert(tags)
+            .1
+            .iter()
+            .filter_map(|c| c.upgrade())
+            .filter(|c| {
+                let c = c.borrow();
+                !c.is_floating() && !c.is_minimized() && !c.is_fullscreen()
+            })
+            .map(|c| c.borrow().window)
+            .collect()
+    }
+
     /// Remove the client corresponding to a window.
     ///
     /// Removes the client objects and cleans all weak references to it.
@@ -261,6 +931,85 @@ impl ClientSet {
         res
     }
 
+    /// Recompute the tags of all clients from their properties.
+    ///
+    /// Clients for which `tags_of` returns `None` keep their tags.
+    pub fn retag<F>(&mut self, tags_of: F)
+        where F: Fn(&ClientProps) -> Option<Vec<Tag>> {
+        for window in self.windows() {
+            let tags = self
+                .clients
+                .get(&window)
+                .and_then(|c| tags_of(c.borrow().props()));
+            if let Some(tags) = tags {
+                self.update_client(window, |mut cl| {
+                    cl.set_tags(&tags);
+                    WmCommand::NoCommand
+                });
+            }
+        }
+    }
+
+    /// Replace the tags of the client focused on a set of tags.
+    ///
+    /// Returns `WmCommand::Redraw` if there is such a client, and
+    /// `WmCommand::NoCommand` otherwise.
+    pub fn move_focused_to_tags(&mut self, current: &[Tag], new: &[Tag])
+        -> WmCommand {
+        self.get_focused_window(current)
+            .and_then(|w| self.update_client(w, |mut cl| {
+                cl.set_tags(new);
+                WmCommand::Redraw
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+    }
+
+    /// Move the client focused on a set of tags to a new set of tags, making
+    /// it the master on exactly these tags.
+    ///
+    /// Returns `WmCommand::Redraw` if there is such a client, and
+    /// `WmCommand::NoCommand` otherwise.
+    pub fn move_focused_to_tags_as_master(&mut self, current: &[Tag],
+                                          new: &[Tag]) -> WmCommand {
+        let focused = self.get_focused_window(current);
+        let cmd = self.move_focused_to_tags(current, new);
+        if let Some(window) = focused {
+            self.make_master(new, window);
+        }
+        cmd
+    }
+
+    /// Toggle a tag on the client focused on a set of tags.
+    ///
+    /// Returns `WmCommand::Redraw` if there is such a client and the tag is
+    /// part of the set, as the client's visibility can only change then, and
+    /// `WmCommand::NoCommand` otherwise.
+    pub fn toggle_tag_on_focused(&mut self, current: &[Tag], tag: Tag)
+        -> WmCommand {
+        let shown = current.contains(&tag);
+        self.get_focused_window(current)
+            .and_then(|w| self.update_client(w, |mut cl| {
+                cl.toggle_tag(tag.clone());
+                if shown {
+                    WmCommand::Redraw
+                } else {
+                    WmCommand::NoCommand
+                }
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+    }
+
+    /// Kill the client focused on the current tagset of a tag stack.
+    ///
+    /// Returns `WmCommand::Kill` for the client's window if there is such a
+    /// client, and `WmCommand::NoCommand` otherwise.
+    pub fn kill_focused(&self, tag_stack: &TagStack) -> WmCommand {
+        tag_stack
+            .current()
+            .and_then(|t| self.get_focused_window(&t.tags))
+            .map_or(WmCommand::NoCommand, WmCommand::Kill)
+    }
+
     /// Get the currently focused window on a set of tags.
     pub fn get_focused_window(&self, tags: &[Tag]) -> Option<xproto::Window> {
         self.order
@@ -270,11 +1019,33 @@ impl ClientSet {
             .map(|r| r.borrow().window)
     }
 
+    /// Focus a specific window on a set of tags, returning whether changes
+    /// have been made.
+    pub fn focus_window(&mut self, tags: &[Tag], window: xproto::Window)
+        -> bool {
+        let &mut (ref mut current, ref clients) =
+            self.get_order_or_insert(tags);
+        if let Some(new_client) = clients
+            .iter()
+            .find(|c| c
+                .upgrade()
+                .map_or(false, |r| r.borrow().window == window)
+            ) {
+            *current = Some(new_client.clone());
+            true
+        } else {
+            false
+        }
+    }
+
     /// Focus a window on a set of tags relative to the current
     /// by index difference, returning whether changes have been made.
     fn focus_offset(&mut self, tags: &[Tag], offset: isize) -> bool {
         let &mut (ref mut current, ref clients) =
             self.get_order_or_insert(&tags);
+        if clients.is_empty() {
+            return false;
+        }
         if let Some(current_window) = current
             .clone()
             .and_then(|c| c.upgrade())
@@ -288,7 +1059,7 @@ impl ClientSet {
                 )
                 .unwrap();
             let new_index =
-                (current_index as isize + offset) as usize % clients.len();
+                wrap_index(current_index, offset, clients.len());
             if let Some(new_client) = clients.get(new_index) {
                 *current = Some(new_client.clone());
                 return true;
@@ -297,11 +1068,59 @@ impl ClientSet {
         false
     }
 
+    /// Focus a window on a set of tags relative to the current by index
+    /// difference, only considering windows matching a predicate, and return
+    /// whether changes have been made.
+    ///
+    /// If the currently focused window isn't considered, focus the first or
+    /// last window in question, depending on the direction.
+    fn focus_offset_filtered<F>(&mut self, tags: &[Tag], offset: isize,
+                                pred: F) -> bool
+        where F: Fn(&Client) -> bool {
+        let &mut (ref mut current, ref clients) =
+            self.get_order_or_insert(&tags);
+        let candidates: Vec<&WeakClientRef> = clients
+            .iter()
+            .filter(|client| client
+                .upgrade()
+                .map_or(false, |r| pred(&r.borrow()))
+            )
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+        let current_index = current
+            .clone()
+            .and_then(|c| c.upgrade())
+            .map(|r| r.borrow().window)
+            .and_then(|current_window| candidates
+                .iter()
+                .position(|client| client
+                    .upgrade()
+                    .map_or(false, |r| r.borrow().window == current_window)
+                )
+            );
+        let len = candidates.len();
+        let new_index = match current_index {
+            Some(index) => wrap_index(index, offset, len),
+            None if offset < 0 => len - 1,
+            None => 0,
+        };
+        if current_index == Some(new_index) {
+            return false;
+        }
+        *current = Some(candidates[new_index].clone());
+        true
+    }
+
     /// Swap with current window on a set of tags relative to the current
     /// by index difference, returning whether changes have been made.
     fn swap_offset(&mut self, tags: &[Tag], offset: isize) -> bool {
         let &mut (ref current, ref mut clients) =
             self.get_order_or_insert(&tags);
+        if clients.is_empty() {
+            return false;
+        }
         if let Some(current_window) = current
             .clone()
             .and_then(|c| c.upgrade())
@@ -315,7 +1134,7 @@ impl ClientSet {
                 )
                 .unwrap();
             let new_index =
-                (current_index as isize + offset) as usize % clients.len();
+                wrap_index(current_index, offset, clients.len());
             clients.swap(current_index, new_index);
             true
         } else {
@@ -323,9 +1142,23 @@ impl ClientSet {
         }
     }
 
+    /// Focus a window on a tagset relative to the current by index
+    /// difference, skipping windows hidden by the layout if the tagset
+    /// requests it.
+    fn focus_offset_visible(&mut self, tagset: &TagSet, offset: isize)
+        -> bool {
+        if tagset.skip_hidden {
+            let hidden = self.hidden.clone();
+            self.focus_offset_filtered(
+                &tagset.tags, offset, |c| !hidden.contains(&c.window))
+        } else {
+            self.focus_offset(&tagset.tags, offset)
+        }
+    }
+
     /// Focus next window, returning whether changes have been made.
     pub fn focus_next(&mut self, tagset: &TagSet) -> bool {
-        self.focus_offset(&tagset.tags, 1)
+        self.focus_offset_visible(tagset, 1)
     }
 
     /// Swap with next window, returning whether changes have been made.
@@ -333,9 +1166,30 @@ impl ClientSet {
         self.swap_offset(&tagset.tags, 1)
     }
 
+    /// Focus next floating window, returning whether changes have been made.
+    pub fn focus_next_floating(&mut self, tagset: &TagSet) -> bool {
+        self.focus_offset_filtered(&tagset.tags, 1, |c| c.is_floating())
+    }
+
+    /// Focus previous floating window, returning whether changes have been
+    /// made.
+    pub fn focus_prev_floating(&mut self, tagset: &TagSet) -> bool {
+        self.focus_offset_filtered(&tagset.tags, -1, |c| c.is_floating())
+    }
+
+    /// Focus next tiled window, returning whether changes have been made.
+    pub fn focus_next_tiled(&mut self, tagset: &TagSet) -> bool {
+        self.focus_offset_filtered(&tagset.tags, 1, |c| !c.is_floating())
+    }
+
+    /// Focus previous tiled window, returning whether changes have been made.
+    pub fn focus_prev_tiled(&mut self, tagset: &TagSet) -> bool {
+        self.focus_offset_filtered(&tagset.tags, -1, |c| !c.is_floating())
+    }
+
     /// Focus previous window, returning whether changes have been made.
     pub fn focus_prev(&mut self, tagset: &TagSet) -> bool {
-        self.focus_offset(&tagset.tags, -1)
+        self.focus_offset_visible(tagset, -1)
     }
 
     /// Swap with previous window, returning whether changes have been made.
@@ -349,6 +1203,9 @@ impl ClientSet {
         where F: Fn(usize, usize) -> Option<usize> {
         let &mut (ref mut current, ref mut clients) =
             self.get_order_or_insert(&tags);
+        if clients.is_empty() {
+            return false;
+        }
         if let Some(current_window) = current
             .clone()
             .and_then(|c| c.upgrade())
@@ -378,6 +1235,9 @@ impl ClientSet {
         where F: Fn(usize, usize) -> Option<usize> {
         let &mut (ref current, ref mut clients) =
             self.get_order_or_insert(&tags);
+        if clients.is_empty() {
+            return false;
+        }
         if let Some(current_window) = current
             .clone()
             .and_then(|c| c.upgrade())
@@ -392,7 +1252,7 @@ impl ClientSet {
                 .unwrap();
             if let Some(new_index) =
                 focus_func(current_index, clients.len() - 1) {
-                if new_index < clients.len() {
+                if new_index < clients.len() && new_index != current_index {
                     clients.swap(current_index, new_index);
                     return true;
                 }
@@ -404,55 +1264,128 @@ impl ClientSet {
     /// Focus the window to the right, returning whether changes have been
     /// made.
     pub fn focus_right(&mut self, tagset: &TagSet) -> bool {
-        self.focus_direction(&tagset.tags,
-                             |i, m| tagset.layout.right_window(i, m))
+        self.focus_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .right_window(i, m))
     }
 
     /// Swap with the window to the right, returning whether changes have been
     /// made.
     pub fn swap_right(&mut self, tagset: &TagSet) -> bool {
-        self.swap_direction(&tagset.tags,
-                            |i, m| tagset.layout.right_window(i, m))
+        self.swap_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .right_window(i, m))
     }
 
     /// Focus the window to the left, returning whether changes have been made.
     pub fn focus_left(&mut self, tagset: &TagSet) -> bool {
-        self.focus_direction(&tagset.tags,
-                             |i, m| tagset.layout.left_window(i, m))
+        self.focus_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .left_window(i, m))
     }
 
     /// Swap with the window to the left, returning whether changes have been
     /// made.
     pub fn swap_left(&mut self, tagset: &TagSet) -> bool {
-        self.swap_direction(&tagset.tags,
-                            |i, m| tagset.layout.left_window(i, m))
+        self.swap_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .left_window(i, m))
     }
 
     /// Focus the window to the top, returning whether changes have been made.
     pub fn focus_top(&mut self, tagset: &TagSet) -> bool {
-        self.focus_direction(&tagset.tags,
-                             |i, m| tagset.layout.top_window(i, m))
+        self.focus_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .top_window(i, m))
     }
 
     /// Swap with the window to the left, returning whether changes have been
     /// made.
     pub fn swap_top(&mut self, tagset: &TagSet) -> bool {
-        self.swap_direction(&tagset.tags,
-                            |i, m| tagset.layout.top_window(i, m))
+        self.swap_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .top_window(i, m))
     }
 
     /// Focus the window to the bottom, returning whether changes have been
     /// made.
     pub fn focus_bottom(&mut self, tagset: &TagSet) -> bool {
-        self.focus_direction(&tagset.tags,
-                             |i, m| tagset.layout.bottom_window(i, m))
+        self.focus_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .bottom_window(i, m))
     }
 
     /// Swap with the window to the left, returning whether changes have been
     /// made.
     pub fn swap_bottom(&mut self, tagset: &TagSet) -> bool {
-        self.swap_direction(&tagset.tags,
-                            |i, m| tagset.layout.bottom_window(i, m))
+        self.swap_direction(&tagset.tags, |i, m| tagset
+            .layout_for(m + 1)
+            .bottom_window(i, m))
+    }
+
+    /// Make a window the master on a set of tags, returning whether changes
+    /// have been made.
+    ///
+    /// The order entry for the tags is created if not present. The remaining
+    /// clients keep their relative order.
+    pub fn make_master(&mut self, tags: &[Tag], window: xproto::Window)
+        -> bool {
+        let &mut (_, ref mut clients) = self.get_order_or_insert(tags);
+        if let Some(index) = clients
+            .iter()
+            .position(|c| c
+                .upgrade()
+                .map_or(false, |r| r.borrow().window == window)
+            ) {
+            if index > 0 {
+                let client = clients.remove(index);
+                clients.insert(0, client);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Rotate the windows on a tagset until the focused window is the
+    /// master, returning whether changes have been made.
+    ///
+    /// In contrast to `swap_master`, the cyclic order of the windows is
+    /// kept, so that focusing the next window repeatedly visits all of them.
+    pub fn rotate_to_master(&mut self, tagset: &TagSet) -> bool {
+        let &mut (ref current, ref mut clients) =
+            self.get_order_or_insert(&tagset.tags);
+        let current_window = match current
+            .clone()
+            .and_then(|c| c.upgrade())
+            .map(|r| r.borrow().window) {
+            Some(window) => window,
+            None => return false,
+        };
+        match clients
+            .iter()
+            .position(|c| c
+                .upgrade()
+                .map_or(false, |r| r.borrow().window == current_window)
+            ) {
+            Some(index) if index > 0 => {
+                let head: Vec<_> = clients.drain(..index).collect();
+                clients.extend(head);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Rotate the focused window on a tagset to the master position if the
+    /// layout in effect only shows the master, returning whether changes
+    /// have been made.
+    ///
+    /// The focused client stays the same, so that it remains visible when
+    /// switching to such a layout.
+    pub fn surface_focused(&mut self, tagset: &TagSet) -> bool {
+        let num_windows = self.tiled_windows(&tagset.tags).len();
+        tagset.layout_for(num_windows).new_window_as_master() &&
+            self.rotate_to_master(tagset)
     }
 
     /// Swap with the master window, returning whether changes have been made.
@@ -474,6 +1407,17 @@ pub struct TagSet {
     pub tags: Vec<Tag>,
     /// the layout used to display clients on the tagset
     pub layout: Box<Layout>,
+    /// the layout used before the current one, keeping it's state, if any
+    previous_layout: Option<Box<Layout>>,
+    /// layouts overriding the default, each used from a minimal number of
+    /// windows on, sorted by that number
+    count_layouts: Vec<(usize, Box<Layout>)>,
+    /// skip windows hidden by the layout when cycling focus?
+    pub skip_hidden: bool,
+    /// prevent the layout from being changed or edited?
+    layout_locked: bool,
+    /// number of tiled clients the tagset was last arranged with
+    tiled_count: Cell<usize>,
 }
 
 impl TagSet {
@@ -482,9 +1426,155 @@ impl TagSet {
         TagSet {
             tags: tags,
             layout: Box::new(layout),
+            previous_layout: None,
+            count_layouts: Vec::new(),
+            skip_hidden: false,
+            layout_locked: false,
+            tiled_count: Cell::new(0),
         }
     }
 
+    /// Use a different layout as soon as a number of windows is displayed.
+    ///
+    /// The layout applies up to the next threshold set this way, below the
+    /// lowest threshold, the default layout is used. For instance, a tagset
+    /// created with `Monocle` and adding `VStack` from 2 windows and `Grid`
+    /// from 5 windows on switches between all three as windows come and go.
+    pub fn with_count_layout<L: Layout + 'static>(mut self,
+                                                  min_windows: usize,
+                                                  layout: L) -> TagSet {
+        self.count_layouts.retain(|&(min, _)| min != min_windows);
+        self.count_layouts.push((min_windows, Box::new(layout)));
+        self.count_layouts.sort_by_key(|&(min, _)| min);
+        self
+    }
+
+    /// Set a layout to toggle to from the default one.
+    pub fn with_alternate_layout<L: Layout + 'static>(mut self, layout: L)
+        -> TagSet {
+        self.previous_layout = Some(Box::new(layout));
+        self
+    }
+
+    /// Set whether to skip windows hidden by the layout when cycling focus.
+    pub fn with_skip_hidden(mut self, skip_hidden: bool) -> TagSet {
+        self.skip_hidden = skip_hidden;
+        self
+    }
+
+    /// Get the labels of the tagset's tags, separated by slashes.
+    pub fn label(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| tag.label())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Get the layout in effect when displaying a number of windows.
+    pub fn layout_for(&self, num_windows: usize) -> &Layout {
+        match self.count_layout_index(num_windows) {
+            Some(index) => &*self.count_layouts[index].1,
+            None => &*self.layout,
+        }
+    }
+
+    /// Get a mutable reference to the layout in effect when displaying a
+    /// number of windows.
+    pub fn layout_for_mut(&mut self, num_windows: usize) -> &mut Box<Layout> {
+        match self.count_layout_index(num_windows) {
+            Some(index) => &mut self.count_layouts[index].1,
+            None => &mut self.layout,
+        }
+    }
+
+    /// Get the index of the count layout in effect when displaying a number
+    /// of windows, if any.
+    fn count_layout_index(&self, num_windows: usize) -> Option<usize> {
+        self.count_layouts
+            .iter()
+            .rposition(|&(min, _)| min <= num_windows)
+    }
+
+    /// Get the layout in effect when the tagset was last arranged.
+    pub fn current_layout(&self) -> &Layout {
+        self.layout_for(self.tiled_count.get())
+    }
+
+    /// Get a mutable reference to the layout in effect when the tagset was
+    /// last arranged.
+    pub fn current_layout_mut(&mut self) -> &mut Box<Layout> {
+        let num_windows = self.tiled_count.get();
+        self.layout_for_mut(num_windows)
+    }
+
+    /// Arrange clients shown on the tagset, in order, on a screen.
+    ///
+    /// Only the state of the clients and the layout are considered, so that
+    /// no requests to the X server are needed. Minimized clients are left
+    /// out. The number of tiled clients is kept to determine the layout
+    /// edited later on.
+    pub fn arrange(&self, clients: Vec<ClientRef>, screen: &ScreenSize,
+                   border_width: u16) -> Arrangement {
+        // fullscreen and floating clients are not subject to the layout
+        let (fullscreen, others): (Vec<_>, Vec<_>) = clients
+            .into_iter()
+            .filter(|c| !c.borrow().is_minimized())
+            .partition(|c| c.borrow().is_fullscreen());
+        let (mut floating, mut tiled): (Vec<_>, Vec<_>) = others
+            .into_iter()
+            .partition(|c| c.borrow().is_floating());
+        // some layouts leave all windows floating, keeping those floating
+        // anyway above the others
+        self.tiled_count.set(tiled.len());
+        let layout = self.layout_for(tiled.len());
+        let layout_floats = layout.floats_windows();
+        if layout_floats {
+            tiled.extend(floating.drain(..));
+            floating = mem::replace(&mut tiled, Vec::new());
+        }
+        let splits: Vec<_> = tiled
+            .iter()
+            .map(|c| c.borrow().split())
+            .collect();
+        let geometries = layout.arrange_split(&splits, screen, border_width);
+        Arrangement {
+            tiled: tiled.into_iter().zip(geometries.into_iter()).collect(),
+            floating: floating,
+            fullscreen: fullscreen,
+            layout_floats: layout_floats,
+        }
+    }
+
+    /// Predict the geometry of a new tiled client shown on the tagset,
+    /// given the clients shown already, in order.
+    ///
+    /// The client is assumed to be appended, or inserted as master if the
+    /// layout asks for it. Returns `None` if the layout would hide it.
+    pub fn next_geometry(&self, clients: Vec<ClientRef>, screen: &ScreenSize,
+                         border_width: u16) -> Option<Geometry> {
+        let mut splits: Vec<_> = clients
+            .iter()
+            .filter(|c| {
+                let c = c.borrow();
+                !c.is_floating() && !c.is_minimized() && !c.is_fullscreen()
+            })
+            .map(|c| c.borrow().split())
+            .collect();
+        let layout = self.layout_for(splits.len() + 1);
+        let index = if layout.new_window_as_master() {
+            0
+        } else {
+            splits.len()
+        };
+        splits.insert(index, None);
+        layout
+            .arrange_split(&splits, screen, border_width)
+            .into_iter()
+            .nth(index)
+            .and_then(|g| g)
+    }
+
     /// Toggle a tag on the tagset and return whether changes have been made.
     pub fn toggle_tag(&mut self, tag: Tag) -> bool {
         if let Some(index) = self.tags.iter().position(|t| *t == tag) {
@@ -496,10 +1586,113 @@ impl TagSet {
         }
     }
 
-    /// Set a layout on the tagset.
+    /// Set a layout on the tagset, returning whether changes have been
+    /// made.
+    ///
+    /// Nothing happens if the layout is locked.
     #[allow(dead_code)]
-    pub fn set_layout<L: Layout + 'static>(&mut self, layout: L) {
-        self.layout = Box::new(layout);
+    pub fn set_layout<L: Layout + 'static>(&mut self, layout: L) -> bool {
+        self.set_boxed_layout(Box::new(layout))
+    }
+
+    /// Set a boxed layout on the tagset, returning whether changes have been
+    /// made.
+    ///
+    /// The layout in effect is replaced, which is the default one unless a
+    /// count layout applies. Nothing happens if the layout is locked.
+    pub fn set_boxed_layout(&mut self, layout: Box<Layout>) -> bool {
+        if self.layout_locked {
+            false
+        } else {
+            let old = mem::replace(self.current_layout_mut(), layout);
+            self.previous_layout = Some(old);
+            true
+        }
+    }
+
+    /// Swap the layout with the one used before it, returning whether
+    /// changes have been made.
+    ///
+    /// Both layouts keep their state, like the master factor. Nothing
+    /// happens if the layout is locked.
+    pub fn toggle_layout(&mut self) -> bool {
+        if self.layout_locked {
+            return false;
+        }
+        if let Some(previous) = self.previous_layout.take() {
+            let old = mem::replace(self.current_layout_mut(), previous);
+            self.previous_layout = Some(old);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Send the first applicable of a set of messages to the tagset's
+    /// layout in effect, returning whether changes have been made.
+    ///
+    /// Nothing happens if the layout is locked.
+    pub fn edit_layout(&mut self, msgs: Vec<LayoutMessage>) -> bool {
+        !self.layout_locked &&
+            self.current_layout_mut().edit_layout_retry(msgs)
+    }
+
+    /// Notify all layouts of the tagset of a tiled client inserted at the
+    /// given index while the client with the index `focused` had the focus.
+    pub fn client_added(&mut self, index: usize, focused: usize) {
+        self.layout.on_client_added(index, focused);
+        if let Some(ref mut layout) = self.previous_layout {
+            layout.on_client_added(index, focused);
+        }
+        for &mut (_, ref mut layout) in self.count_layouts.iter_mut() {
+            layout.on_client_added(index, focused);
+        }
+    }
+
+    /// Notify all layouts of the tagset of the removal of the tiled client
+    /// with the given index.
+    pub fn client_removed(&mut self, index: usize) {
+        self.layout.on_client_removed(index);
+        if let Some(ref mut layout) = self.previous_layout {
+            layout.on_client_removed(index);
+        }
+        for &mut (_, ref mut layout) in self.count_layouts.iter_mut() {
+            layout.on_client_removed(index);
+        }
+    }
+
+    /// Check whether the layout of the tagset is locked.
+    pub fn is_layout_locked(&self) -> bool {
+        self.layout_locked
+    }
+
+    /// Lock or unlock the layout of the tagset, returning whether changes
+    /// have been made.
+    pub fn set_layout_locked(&mut self, locked: bool) -> bool {
+        let changed = self.layout_locked != locked;
+        self.layout_locked = locked;
+        changed
+    }
+}
+
+/// The arrangement of the clients shown on a tagset.
+pub struct Arrangement {
+    /// tiled clients, each with the geometry assigned by the layout, if
+    /// shown
+    pub tiled: Vec<(ClientRef, Option<Geometry>)>,
+    /// floating clients, placed above the tiled ones
+    pub floating: Vec<ClientRef>,
+    /// fullscreen clients, covering the whole output
+    pub fullscreen: Vec<ClientRef>,
+    /// whether the layout leaves all clients floating
+    pub layout_floats: bool,
+}
+
+impl Arrangement {
+    /// Get the number of clients shown.
+    pub fn num_shown(&self) -> usize {
+        self.tiled.iter().filter(|&&(_, ref g)| g.is_some()).count() +
+            self.floating.len() + self.fullscreen.len()
     }
 }
 
@@ -545,6 +1738,12 @@ impl TagStack {
         }
     }
 
+    /// Get all tag sets by mutable reference, in no particular order.
+    pub fn tagsets_mut<'a>(&'a mut self)
+        -> hash_map::ValuesMut<'a, u8, TagSet> {
+        self.tagsets.values_mut()
+    }
+
     /// Get the current tag set's index
     ///
     /// Returns `None` if the history stack is empty
@@ -573,6 +1772,51 @@ impl TagStack {
         }
     }
 
+    /// Send a message to the layout of the current tagset, keeping the
+    /// state of the layout otherwise.
+    ///
+    /// Returns `WmCommand::Redraw` if the layout changed, for use in key
+    /// bindings.
+    pub fn send_layout_message(&mut self, msg: LayoutMessage) -> WmCommand {
+        let changed = self
+            .current_mut()
+            .map_or(false, |t| t.edit_layout(vec![msg]));
+        if changed {
+            WmCommand::Redraw
+        } else {
+            WmCommand::NoCommand
+        }
+    }
+
+    /// Swap the layout of the current tagset with the one used before it.
+    ///
+    /// Returns `WmCommand::Redraw` if the layout changed, for use in key
+    /// bindings.
+    pub fn toggle_layout(&mut self) -> WmCommand {
+        if self.current_mut().map_or(false, |t| t.toggle_layout()) {
+            WmCommand::Redraw
+        } else {
+            WmCommand::NoCommand
+        }
+    }
+
+    /// Get the index of a tagset showing clients with a set of tags,
+    /// preferring one with exactly these tags.
+    ///
+    /// If there are multiple candidates, the lowest index is used.
+    pub fn index_for_tags(&self, tags: &[Tag]) -> Option<u8> {
+        let mut indices: Vec<_> = self.tagsets.keys().cloned().collect();
+        indices.sort();
+        let tagsets = &self.tagsets;
+        indices
+            .iter()
+            .find(|i| tagsets[*i].tags.as_slice() == tags)
+            .or_else(|| indices
+                .iter()
+                .find(|i| tagsets[*i].tags.iter().any(|t| tags.contains(t))))
+            .cloned()
+    }
+
     /// Set the currently viewed tagset by index.
     pub fn push(&mut self, new_index: u8) {
         if self.tagsets.contains_key(&new_index) {
@@ -584,6 +1828,33 @@ impl TagStack {
         }
     }
 
+    /// Rotate through the tagsets in the order of their indices, viewing the
+    /// tagset `offset` steps away from the current one, and return whether
+    /// changes have been made.
+    ///
+    /// Cycling past the last tagset wraps around to the first and vice
+    /// versa. The new tagset is moved to the top of the history stack
+    /// instead of being added again.
+    pub fn cycle(&mut self, offset: isize) -> bool {
+        let mut indices: Vec<u8> = self.tagsets.keys().cloned().collect();
+        if indices.is_empty() {
+            return false;
+        }
+        indices.sort();
+        let new_index = match self
+            .current_index()
+            .and_then(|i| indices.iter().position(|j| j == i)) {
+            Some(pos) => indices[wrap_index(pos, offset, indices.len())],
+            None => indices[0],
+        };
+        if self.current_index() == Some(&new_index) {
+            return false;
+        }
+        self.history.retain(|i| *i != new_index);
+        self.push(new_index);
+        true
+    }
+
     /// Add a new tagset to the set.
     #[allow(dead_code)]
     pub fn add(&mut self, index: u8, value: TagSet) -> bool {
@@ -616,3 +1887,802 @@ impl TagStack {
         self.history.pop().is_some()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wm::layout::bsp::Bsp;
+    use wm::layout::floating::Floating;
+    use wm::layout::monocle::Monocle;
+    use wm::layout::stack::VStack;
+
+    fn props() -> ClientProps {
+        ClientProps {
+            window_type: 0,
+            name: String::new(),
+            class: Vec::new(),
+            protocols: Vec::new(),
+            start_iconic: false,
+            accepts_input: true,
+            pid: None,
+            user_time: None,
+        }
+    }
+
+    fn tagset() -> TagSet {
+        TagSet::new(vec![Tag::Web], VStack::default())
+    }
+
+    /// Setup a client set with windows `1` to `n` on the web tag, in order.
+    fn client_set(n: u32) -> ClientSet {
+        let mut clients = ClientSet::new();
+        clients.get_order_or_insert(&[Tag::Web]);
+        for window in 1..n + 1 {
+            clients.add(Client::new(window, vec![Tag::Web], props()),
+                        InsertPolicy::AsSlave);
+        }
+        clients
+    }
+
+    /// Get the windows ordered on a set of tags.
+    fn order(clients: &mut ClientSet, tags: &[Tag]) -> Vec<xproto::Window> {
+        clients
+            .get_order_or_insert(tags)
+            .1
+            .iter()
+            .filter_map(|r| r.upgrade())
+            .map(|r| r.borrow().window)
+            .collect()
+    }
+
+    /// Get the windows of a list of clients.
+    fn windows(clients: &[ClientRef]) -> Vec<xproto::Window> {
+        clients.iter().map(|c| c.borrow().window).collect()
+    }
+
+    /// Setup a client set with windows `1` to `5` on the web tag: two tiled
+    /// ones, a floating, a fullscreen and a minimized one.
+    fn mixed_client_set() -> ClientSet {
+        let mut clients = client_set(5);
+        clients.update_client(3, |mut cl| {
+            cl.toggle_floating();
+            cl.set_float_geometry(
+                Geometry { x: 10, y: 10, width: 30, height: 20 });
+            WmCommand::NoCommand
+        });
+        clients.update_client(4, |mut cl| {
+            cl.set_fullscreen(true);
+            WmCommand::NoCommand
+        });
+        clients.update_client(5, |mut cl| {
+            cl.set_minimized(true);
+            WmCommand::NoCommand
+        });
+        clients
+    }
+
+    /// Get the clients shown on a tagset, in order.
+    fn shown(clients: &mut ClientSet, tagset: &TagSet) -> Vec<ClientRef> {
+        clients
+            .get_order_or_insert(&tagset.tags)
+            .1
+            .iter()
+            .filter_map(|r| r.upgrade())
+            .collect()
+    }
+
+    fn redraws(cmd: WmCommand) -> bool {
+        match cmd {
+            WmCommand::Redraw => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn wrap_index_forward() {
+        assert_eq!(wrap_index(0, 1, 1), 0);
+        assert_eq!(wrap_index(1, 1, 2), 0);
+        assert_eq!(wrap_index(2, 1, 3), 0);
+        assert_eq!(wrap_index(1, 1, 3), 2);
+        assert_eq!(wrap_index(2, 4, 3), 0);
+    }
+
+    #[test]
+    fn wrap_index_backward() {
+        assert_eq!(wrap_index(0, -1, 1), 0);
+        assert_eq!(wrap_index(0, -1, 2), 1);
+        assert_eq!(wrap_index(0, -1, 3), 2);
+        assert_eq!(wrap_index(2, -1, 3), 1);
+        assert_eq!(wrap_index(0, -4, 3), 2);
+    }
+
+    #[test]
+    fn focus_wraps_around() {
+        for n in 1..4 {
+            let mut clients = client_set(n);
+            let tagset = tagset();
+            // the last client added is focused
+            assert_eq!(clients.get_focused_window(&tagset.tags), Some(n));
+            clients.focus_next(&tagset);
+            assert_eq!(clients.get_focused_window(&tagset.tags), Some(1));
+            clients.focus_prev(&tagset);
+            assert_eq!(clients.get_focused_window(&tagset.tags), Some(n));
+        }
+    }
+
+    #[test]
+    fn cycle_empty_set() {
+        let mut clients = ClientSet::new();
+        let tagset = tagset();
+        assert!(!clients.focus_next(&tagset));
+        assert!(!clients.focus_prev(&tagset));
+        assert!(!clients.swap_next(&tagset));
+        assert!(!clients.swap_prev(&tagset));
+        assert!(!clients.focus_next_floating(&tagset));
+        assert!(!clients.focus_prev_tiled(&tagset));
+        assert_eq!(clients.get_focused_window(&tagset.tags), None);
+    }
+
+    #[test]
+    fn transaction_fixes_references_once() {
+        let mut clients = client_set(2);
+        clients.get_order_or_insert(&[Tag::Media]);
+        clients.transaction(|clients| {
+            for &window in &[1, 2, 1] {
+                clients.update_client(window, |mut cl| {
+                    cl.toggle_tag(Tag::Media);
+                    WmCommand::NoCommand
+                });
+            }
+            // each window is queued once, nothing is fixed yet
+            assert_eq!(clients.deferred, Some(vec![1, 2]));
+            assert_eq!(order(clients, &[Tag::Media]), Vec::<u32>::new());
+        });
+        assert_eq!(clients.deferred, None);
+        assert_eq!(order(&mut clients, &[Tag::Media]), vec![2]);
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![1, 2]);
+    }
+
+    #[test]
+    fn cycle_wraps_around() {
+        let mut tag_stack = TagStack::from_presets(vec![
+            TagSet::new(vec![Tag::Web], VStack::default()),
+            TagSet::new(vec![Tag::Work2], VStack::default()),
+            TagSet::new(vec![Tag::Work3], VStack::default()),
+        ], 2);
+        assert!(tag_stack.cycle(1));
+        assert_eq!(tag_stack.current_index(), Some(&0));
+        assert!(tag_stack.cycle(-1));
+        assert_eq!(tag_stack.current_index(), Some(&2));
+    }
+
+    #[test]
+    fn new_tagset_keeps_creation_order() {
+        let mut clients = ClientSet::new();
+        for &window in &[30, 10, 20] {
+            clients.add(Client::new(window, vec![Tag::Web], props()),
+                        InsertPolicy::AsSlave);
+        }
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![30, 10, 20]);
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(30));
+    }
+
+    #[test]
+    fn constrain_large_aspect_ratios() {
+        let hints = SizeHints {
+            min_aspect: Some((1000000000, 2000000000)),
+            max_aspect: Some((2000000000, 1000000000)),
+            ..SizeHints::default()
+        };
+        let wide = Geometry { x: 5, y: 5, width: 1000, height: 100 };
+        assert_eq!(hints.constrain(&wide),
+                   Geometry { x: 5, y: 5, width: 200, height: 100 });
+        let tall = Geometry { x: 5, y: 5, width: 100, height: 1000 };
+        assert_eq!(hints.constrain(&tall),
+                   Geometry { x: 5, y: 5, width: 100, height: 200 });
+    }
+
+    #[test]
+    fn insert_as_master() {
+        let mut clients = client_set(2);
+        clients.add(Client::new(3, vec![Tag::Web], props()),
+                    InsertPolicy::AsMaster);
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![3, 1, 2]);
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(3));
+    }
+
+    #[test]
+    fn insert_as_slave() {
+        let mut clients = client_set(2);
+        clients.focus_window(&[Tag::Web], 1);
+        clients.add(Client::new(3, vec![Tag::Web], props()),
+                    InsertPolicy::AsSlave);
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![1, 2, 3]);
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(3));
+    }
+
+    #[test]
+    fn insert_after_focused() {
+        let mut clients = client_set(3);
+        clients.focus_window(&[Tag::Web], 1);
+        clients.add(Client::new(4, vec![Tag::Web], props()),
+                    InsertPolicy::AfterFocused);
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![1, 4, 2, 3]);
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(4));
+        // without a focused client, the new one is appended
+        clients.get_order_or_insert(&[Tag::Media]);
+        clients.add(Client::new(5, vec![Tag::Media], props()),
+                    InsertPolicy::AfterFocused);
+        assert_eq!(order(&mut clients, &[Tag::Media]), vec![5]);
+    }
+
+    #[test]
+    fn move_and_toggle_tags_on_focused() {
+        let mut clients = client_set(2);
+        let web = vec![Tag::Web];
+        assert!(!redraws(clients.toggle_tag_on_focused(&web, Tag::Media)));
+        assert_eq!(clients.tags_of(2), Some(vec![Tag::Web, Tag::Media]));
+        assert!(redraws(clients.toggle_tag_on_focused(&web, Tag::Web)));
+        assert_eq!(order(&mut clients, &web), vec![1]);
+        assert_eq!(clients.get_focused_window(&web), Some(1));
+        assert!(redraws(clients.move_focused_to_tags(&web, &[Tag::Chat])));
+        assert_eq!(clients.tags_of(1), Some(vec![Tag::Chat]));
+        assert!(!redraws(clients.move_focused_to_tags(&web, &[Tag::Chat])));
+    }
+
+    #[test]
+    fn move_client_between_outputs() {
+        let left = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 1920,
+            height: 1080,
+        };
+        let right = ScreenSize {
+            offset_x: 1920,
+            offset_y: 0,
+            width: 1280,
+            height: 1024,
+        };
+        let mut clients = client_set(2);
+        let views = (vec![Tag::Web], vec![Tag::Work2]);
+        clients.get_order_or_insert(&views.1);
+        clients.update_client(2, |mut cl| {
+            cl.toggle_floating();
+            cl.set_float_geometry(
+                Geometry { x: 1700, y: 1000, width: 200, height: 50 });
+            cl.move_to_output(&views.1, &left, &right, 1);
+            WmCommand::Redraw
+        });
+        assert_eq!(order(&mut clients, &views.0), vec![1]);
+        assert_eq!(order(&mut clients, &views.1), vec![2]);
+        let client = clients.get_client_by_window(2).unwrap().clone();
+        assert_eq!(client.borrow_mut().float_geometry(&right, 1),
+                   Geometry { x: 2998, y: 972, width: 200, height: 50 });
+    }
+
+    #[test]
+    fn arrange_mixed_clients() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let mut clients = mixed_client_set();
+        let tagset = tagset();
+        let arrangement =
+            tagset.arrange(shown(&mut clients, &tagset), &screen, 1);
+        let tiled: Vec<_> = arrangement.tiled
+            .iter()
+            .map(|&(ref c, ref g)| (c.borrow().window, g.clone()))
+            .collect();
+        assert_eq!(tiled, vec![
+            (1, Some(Geometry { x: 0, y: 0, width: 48, height: 58 })),
+            (2, Some(Geometry { x: 50, y: 0, width: 48, height: 58 })),
+        ]);
+        assert_eq!(windows(&arrangement.floating), vec![3]);
+        assert_eq!(windows(&arrangement.fullscreen), vec![4]);
+        assert!(!arrangement.layout_floats);
+        assert_eq!(arrangement.num_shown(), 4);
+    }
+
+    #[test]
+    fn arrange_with_floating_layout() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let mut clients = mixed_client_set();
+        let tagset = TagSet::new(vec![Tag::Web], Floating::default());
+        let arrangement =
+            tagset.arrange(shown(&mut clients, &tagset), &screen, 1);
+        assert!(arrangement.tiled.is_empty());
+        assert_eq!(windows(&arrangement.floating), vec![1, 2, 3]);
+        assert_eq!(windows(&arrangement.fullscreen), vec![4]);
+        assert!(arrangement.layout_floats);
+        assert_eq!(arrangement.num_shown(), 4);
+    }
+
+    #[test]
+    fn edit_count_layout_in_effect() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let mut tagset = TagSet::new(vec![Tag::Web], Monocle::default())
+            .with_count_layout(2, VStack::default());
+
+        let mut single = client_set(1);
+        tagset.arrange(shown(&mut single, &tagset), &screen, 1);
+        assert_eq!(tagset.current_layout().name(), "monocle");
+        assert!(!tagset.edit_layout(vec![LayoutMessage::MasterFactorAbs(70)]));
+
+        let mut pair = client_set(2);
+        let before = tagset.arrange(shown(&mut pair, &tagset), &screen, 1);
+        assert_eq!(tagset.current_layout().name(), "vstack");
+        assert!(tagset.edit_layout(vec![LayoutMessage::MasterFactorAbs(70)]));
+        assert_eq!(tagset.current_layout().master_factor(), Some(70));
+        assert_eq!(tagset.layout_for(1).master_factor(), None);
+        let after = tagset.arrange(shown(&mut pair, &tagset), &screen, 1);
+        assert!(after.tiled[0].1 != before.tiled[0].1);
+
+        // replacing the layout only affects the one in effect
+        assert!(tagset.set_layout(Monocle::default()));
+        assert_eq!(tagset.layout_for(2).name(), "monocle");
+        assert!(tagset.toggle_layout());
+        assert_eq!(tagset.layout_for(2).name(), "vstack");
+        assert_eq!(tagset.layout_for(1).name(), "monocle");
+
+        tagset.set_layout_locked(true);
+        assert!(!tagset.edit_layout(vec![LayoutMessage::MasterFactorAbs(50)]));
+        assert_eq!(tagset.current_layout().master_factor(), Some(70));
+    }
+
+    #[test]
+    fn notify_layouts_of_clients_added_after_focused() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let mut tagsets = vec![
+            TagSet::new(vec![Tag::Web], Bsp::default()),
+            TagSet::new(vec![Tag::Web, Tag::Media], Bsp::default()),
+        ];
+        let mut clients = ClientSet::new();
+        for window in 1..4 {
+            let client = Client::new(window, vec![Tag::Web], props());
+            clients.add_to_tagsets(client, InsertPolicy::AsSlave,
+                                   tagsets.iter_mut());
+        }
+        let geometries = |clients: &mut ClientSet, tagset: &TagSet| {
+            tagset
+                .arrange(shown(clients, tagset), &screen, 0)
+                .tiled
+                .iter()
+                .map(|&(ref c, ref g)| (c.borrow().window, g.clone()))
+                .collect::<Vec<_>>()
+        };
+        let before = geometries(&mut clients, &tagsets[0]);
+        assert_eq!(before, vec![
+            (1, Some(Geometry { x: 0, y: 0, width: 50, height: 60 })),
+            (2, Some(Geometry { x: 50, y: 0, width: 50, height: 30 })),
+            (3, Some(Geometry { x: 50, y: 30, width: 50, height: 30 })),
+        ]);
+
+        // the new window splits the focused one, the others keep theirs
+        clients.focus_window(&[Tag::Web], 1);
+        clients.focus_window(&[Tag::Web, Tag::Media], 1);
+        clients.add_to_tagsets(Client::new(4, vec![Tag::Web], props()),
+                               InsertPolicy::AfterFocused,
+                               tagsets.iter_mut());
+        for tagset in tagsets.iter() {
+            assert_eq!(geometries(&mut clients, tagset), vec![
+                (1, Some(Geometry { x: 0, y: 0, width: 50, height: 30 })),
+                (4, Some(Geometry { x: 50, y: 0, width: 50, height: 60 })),
+                (2, Some(Geometry { x: 0, y: 30, width: 25, height: 30 })),
+                (3, Some(Geometry { x: 25, y: 30, width: 25, height: 30 })),
+            ]);
+        }
+
+        clients.notify_removal(4, tagsets.iter_mut());
+        clients.remove(4);
+        for tagset in tagsets.iter() {
+            assert_eq!(geometries(&mut clients, tagset), before);
+        }
+    }
+
+    #[test]
+    fn match_patterns() {
+        assert!(Pattern::Exact("xterm".to_owned()).matches("xterm"));
+        assert!(!Pattern::Exact("xterm".to_owned()).matches("uxterm"));
+        let regex = Pattern::Regex(Regex::new("^(u|x)term$").unwrap());
+        assert!(!regex.matches("uxterm"));
+        assert!(regex.matches("xterm"));
+        assert!(regex.matches("uterm"));
+    }
+
+    #[test]
+    fn match_client_rules() {
+        let mut firefox = props();
+        firefox.class = vec!["Navigator".to_owned(), "Firefox".to_owned()];
+        firefox.name = "Mozilla Firefox".to_owned();
+        // a rule without patterns applies to all clients
+        assert!(ClientRule::default().matches(&props()));
+        let class = ClientRule {
+            class: Some(Pattern::Exact("Firefox".to_owned())),
+            ..ClientRule::default()
+        };
+        assert!(class.matches(&firefox));
+        assert!(!class.matches(&props()));
+        // all patterns have to match
+        let both = ClientRule {
+            class: Some(Pattern::Exact("Firefox".to_owned())),
+            name: Some(Pattern::Regex(Regex::new("Private").unwrap())),
+            ..ClientRule::default()
+        };
+        assert!(!both.matches(&firefox));
+        firefox.name = "Mozilla Firefox (Private Browsing)".to_owned();
+        assert!(both.matches(&firefox));
+    }
+
+    #[test]
+    fn take_tags_from_first_rule_specifying_them() {
+        let mut firefox = props();
+        firefox.class = vec!["Firefox".to_owned()];
+        let rules = vec![
+            ClientRule {
+                class: Some(Pattern::Exact("Firefox".to_owned())),
+                floating: true,
+                ..ClientRule::default()
+            },
+            ClientRule {
+                class: Some(Pattern::Exact("Chromium".to_owned())),
+                tags: Some(vec![Tag::Chat]),
+                ..ClientRule::default()
+            },
+            ClientRule {
+                tags: Some(vec![Tag::Web]),
+                ..ClientRule::default()
+            },
+            ClientRule {
+                tags: Some(vec![Tag::Media]),
+                ..ClientRule::default()
+            },
+        ];
+        assert_eq!(ClientRule::tags_from(&rules, &firefox),
+                   Some(vec![Tag::Web]));
+        assert_eq!(ClientRule::tags_from(&rules[..2], &firefox), None);
+    }
+
+    #[test]
+    fn swap_tags_of_clients() {
+        let mut clients = ClientSet::new();
+        clients.get_order_or_insert(&[Tag::Web]);
+        clients.get_order_or_insert(&[Tag::Media]);
+        clients.add(Client::new(1, vec![Tag::Web], props()),
+                    InsertPolicy::AsSlave);
+        clients.add(Client::new(2, vec![Tag::Media], props()),
+                    InsertPolicy::AsSlave);
+        clients.add(Client::new(3, vec![Tag::Web, Tag::Media], props()),
+                    InsertPolicy::AsSlave);
+        clients.add(Client::new(4, vec![Tag::Chat], props()),
+                    InsertPolicy::AsSlave);
+        assert!(clients.swap_tags(&Tag::Web, &Tag::Media));
+        assert_eq!(clients.tags_of(1), Some(vec![Tag::Media]));
+        assert_eq!(clients.tags_of(2), Some(vec![Tag::Web]));
+        assert_eq!(clients.tags_of(3), Some(vec![Tag::Web, Tag::Media]));
+        assert_eq!(clients.tags_of(4), Some(vec![Tag::Chat]));
+        // clients getting a tag are appended to it's order
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![3, 2]);
+        assert_eq!(order(&mut clients, &[Tag::Media]), vec![3, 1]);
+        // clients having none or both of the tags don't count as changes
+        assert!(!clients.swap_tags(&Tag::Logs, &Tag::Mon));
+        let mut both = ClientSet::new();
+        both.add(Client::new(1, vec![Tag::Web, Tag::Media], props()),
+                 InsertPolicy::AsSlave);
+        assert!(!both.swap_tags(&Tag::Media, &Tag::Web));
+    }
+
+    #[test]
+    fn cycle_floating_and_tiled_only() {
+        let mut clients = client_set(4);
+        for window in &[2, 4] {
+            clients.update_client(*window, |mut cl| {
+                cl.toggle_floating();
+                WmCommand::NoCommand
+            });
+        }
+        let tagset = tagset();
+        let focused = |clients: &ClientSet| {
+            clients.get_focused_window(&[Tag::Web]).unwrap()
+        };
+        assert_eq!(focused(&clients), 4);
+        assert!(clients.focus_next_floating(&tagset));
+        assert_eq!(focused(&clients), 2);
+        assert!(clients.focus_prev_floating(&tagset));
+        assert_eq!(focused(&clients), 4);
+        assert!(clients.focus_prev_floating(&tagset));
+        assert_eq!(focused(&clients), 2);
+        // coming from a floating window, the first tiled one is focused
+        assert!(clients.focus_next_tiled(&tagset));
+        assert_eq!(focused(&clients), 1);
+        assert!(clients.focus_next_tiled(&tagset));
+        assert_eq!(focused(&clients), 3);
+        assert!(clients.focus_next_tiled(&tagset));
+        assert_eq!(focused(&clients), 1);
+        assert!(clients.focus_prev_tiled(&tagset));
+        assert_eq!(focused(&clients), 3);
+        // coming from a tiled window backwards, the last floating one
+        assert!(clients.focus_prev_floating(&tagset));
+        assert_eq!(focused(&clients), 4);
+
+        let mut tiled = client_set(2);
+        assert!(!tiled.focus_next_floating(&tagset));
+        assert!(!tiled.focus_prev_floating(&tagset));
+        assert_eq!(focused(&tiled), 2);
+    }
+
+    #[test]
+    fn restore_float_geometry() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let geometry = Geometry { x: 10, y: 10, width: 30, height: 20 };
+        let mut client = Client::new(1, vec![Tag::Web], props());
+        client.toggle_floating();
+        client.set_float_geometry(geometry.clone());
+        client.toggle_floating();
+        assert!(!client.is_floating());
+        client.toggle_floating();
+        assert!(client.is_floating());
+        assert_eq!(client.float_geometry(&screen, 1), geometry);
+        // without a geometry, the client is centered
+        let mut client = Client::new(2, vec![Tag::Web], props());
+        client.toggle_floating();
+        assert_eq!(client.float_geometry(&screen, 1),
+                   Geometry { x: 24, y: 14, width: 50, height: 30 });
+    }
+
+    #[test]
+    fn move_focused_to_tags_as_master() {
+        let mut clients = client_set(1);
+        clients.get_order_or_insert(&[Tag::Chat]);
+        clients.add(Client::new(2, vec![Tag::Chat], props()),
+                    InsertPolicy::AsSlave);
+        clients.add(Client::new(3, vec![Tag::Chat], props()),
+                    InsertPolicy::AsSlave);
+        let web = vec![Tag::Web];
+        let chat = vec![Tag::Chat];
+        assert!(redraws(clients.move_focused_to_tags_as_master(&web, &chat)));
+        assert_eq!(order(&mut clients, &chat), vec![1, 2, 3]);
+        assert_eq!(clients.tags_of(1), Some(chat.clone()));
+        assert!(order(&mut clients, &web).is_empty());
+        // the order entry is created if needed
+        assert!(redraws(
+            clients.move_focused_to_tags_as_master(&chat, &[Tag::Logs])));
+        assert_eq!(order(&mut clients, &[Tag::Logs]), vec![3]);
+        assert!(!redraws(clients.move_focused_to_tags_as_master(&web, &chat)));
+    }
+
+    #[test]
+    fn query_tags_of_clients() {
+        let mut clients = client_set(1);
+        clients.add(Client::new(2, vec![Tag::Web, Tag::Media], props()),
+                    InsertPolicy::AsSlave);
+        assert_eq!(clients.tags_of(1), Some(vec![Tag::Web]));
+        assert_eq!(clients.tags_of(2), Some(vec![Tag::Web, Tag::Media]));
+        assert_eq!(clients.tags_of(3), None);
+        // the client is shown on both tags
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![1, 2]);
+        assert_eq!(order(&mut clients, &[Tag::Media]), vec![2]);
+    }
+
+    #[test]
+    fn locked_layout_stays_as_is() {
+        let mut tagset = tagset().with_alternate_layout(Monocle::default());
+        let factor = tagset.current_layout().master_factor();
+        assert!(tagset.set_layout_locked(true));
+        assert!(!tagset.set_layout_locked(true));
+        assert!(tagset.is_layout_locked());
+        assert!(!tagset.edit_layout(vec![LayoutMessage::MasterFactorAbs(70)]));
+        assert!(!tagset.set_layout(Monocle::default()));
+        assert!(!tagset.toggle_layout());
+        assert_eq!(tagset.current_layout().name(), "vstack");
+        assert_eq!(tagset.current_layout().master_factor(), factor);
+
+        let mut stack = TagStack::from_presets(vec![tagset], 0);
+        let msg = LayoutMessage::MasterFactorAbs(70);
+        assert!(!redraws(stack.send_layout_message(msg)));
+        assert!(!redraws(stack.toggle_layout()));
+        stack.current_mut().unwrap().set_layout_locked(false);
+        let msg = LayoutMessage::MasterFactorAbs(70);
+        assert!(redraws(stack.send_layout_message(msg)));
+        assert_eq!(stack.current().unwrap().current_layout().master_factor(),
+                   Some(70));
+    }
+
+    #[test]
+    fn maximize_and_restore() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let geometry = Geometry { x: 10, y: 10, width: 30, height: 20 };
+        let mut client = Client::new(1, vec![Tag::Web], props());
+        client.toggle_floating();
+        client.set_float_geometry(geometry.clone());
+        client.set_maximized(true, false, &screen, 1);
+        assert_eq!(client.maximized(), (true, false));
+        assert_eq!(client.float_geometry(&screen, 1),
+                   Geometry { x: 10, y: 0, width: 30, height: 58 });
+        // switching directions starts from the unmaximized geometry
+        client.set_maximized(false, true, &screen, 1);
+        assert_eq!(client.maximized(), (false, true));
+        assert_eq!(client.float_geometry(&screen, 1),
+                   Geometry { x: 0, y: 10, width: 98, height: 20 });
+        client.set_maximized(true, true, &screen, 1);
+        assert_eq!(client.float_geometry(&screen, 1),
+                   Geometry { x: 0, y: 0, width: 98, height: 58 });
+        client.set_maximized(false, false, &screen, 1);
+        assert_eq!(client.maximized(), (false, false));
+        assert_eq!(client.float_geometry(&screen, 1), geometry);
+    }
+
+    #[test]
+    fn find_windows_of_process() {
+        let mut clients = ClientSet::new();
+        for &(window, pid) in &[(7, Some(42)), (3, Some(42)), (5, Some(9)),
+                                (4, None)] {
+            let mut props = props();
+            props.pid = pid;
+            clients.add(Client::new(window, vec![Tag::Web], props),
+                        InsertPolicy::AsSlave);
+        }
+        assert_eq!(clients.windows_for_pid(42), vec![3, 7]);
+        assert_eq!(clients.windows_for_pid(9), vec![5]);
+        assert!(clients.windows_for_pid(1).is_empty());
+    }
+
+    #[test]
+    fn predict_next_geometry() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let actual = |clients: &mut ClientSet, tagset: &TagSet, window| {
+            tagset
+                .arrange(shown(clients, tagset), &screen, 1)
+                .tiled
+                .into_iter()
+                .find(|&(ref c, _)| c.borrow().window == window)
+                .and_then(|(_, g)| g)
+        };
+        for &(ref tagset, policy) in &[
+            (tagset(), InsertPolicy::AsSlave),
+            (TagSet::new(vec![Tag::Web], Monocle::default()),
+             InsertPolicy::AsMaster),
+        ] {
+            let mut clients = mixed_client_set();
+            let predicted = tagset.next_geometry(
+                shown(&mut clients, tagset), &screen, 1);
+            assert!(predicted.is_some());
+            clients.add(Client::new(6, vec![Tag::Web], props()), policy);
+            assert_eq!(predicted, actual(&mut clients, tagset, 6));
+        }
+    }
+
+    #[test]
+    fn reset_layout_on_union_view() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let tags = vec![Tag::Web, Tag::Media];
+        let mut clients = client_set(2);
+        clients.get_order_or_insert(&tags);
+        clients.add(Client::new(3, vec![Tag::Media], props()),
+                    InsertPolicy::AsSlave);
+        let geometries = |clients: &mut ClientSet, tagset: &TagSet| {
+            tagset
+                .arrange(shown(clients, tagset), &screen, 1)
+                .tiled
+                .into_iter()
+                .map(|(c, g)| (c.borrow().window, g))
+                .collect::<Vec<_>>()
+        };
+        let fresh = TagSet::new(tags.clone(), VStack::default());
+        let expected = geometries(&mut clients, &fresh);
+        assert_eq!(expected.len(), 3);
+
+        let tagset = TagSet::new(tags.clone(), VStack::default())
+            .with_count_layout(3, VStack::default());
+        let mut stack = TagStack::from_presets(vec![tagset], 0);
+        geometries(&mut clients, stack.current().unwrap());
+        let msg = LayoutMessage::MasterFactorAbs(70);
+        assert!(redraws(stack.send_layout_message(msg)));
+        assert!(geometries(&mut clients, stack.current().unwrap()) !=
+                expected);
+        // the count layout in effect for all clients of both tags is reset
+        assert!(redraws(stack.send_layout_message(LayoutMessage::Reset)));
+        assert_eq!(geometries(&mut clients, stack.current().unwrap()),
+                   expected);
+    }
+
+    #[test]
+    fn keep_focus_when_toggling_layouts() {
+        let screen = ScreenSize {
+            offset_x: 0,
+            offset_y: 0,
+            width: 100,
+            height: 60,
+        };
+        let mut clients = client_set(3);
+        clients.focus_window(&[Tag::Web], 2);
+        let mut tagset = tagset().with_alternate_layout(Monocle::default());
+        assert!(!clients.surface_focused(&tagset));
+        assert!(tagset.toggle_layout());
+        // the focused client is the one shown by the monocle layout
+        assert!(clients.surface_focused(&tagset));
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(2));
+        let arrangement =
+            tagset.arrange(shown(&mut clients, &tagset), &screen, 1);
+        let visible: Vec<_> = arrangement.tiled
+            .iter()
+            .filter(|&&(_, ref g)| g.is_some())
+            .map(|&(ref c, _)| c.borrow().window)
+            .collect();
+        assert_eq!(visible, vec![2]);
+        assert!(tagset.toggle_layout());
+        assert!(!clients.surface_focused(&tagset));
+        assert_eq!(clients.get_focused_window(&[Tag::Web]), Some(2));
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn retag_moves_matching_clients() {
+        let mut clients = client_set(3);
+        clients.update_client(2, |mut cl| {
+            cl.props.class = vec![String::from("mail")];
+            WmCommand::NoCommand
+        });
+        clients.retag(|props| if props.class.iter().any(|c| c == "mail") {
+            Some(vec![Tag::Work2])
+        } else {
+            None
+        });
+        assert_eq!(clients.tags_of(1), Some(vec![Tag::Web]));
+        assert_eq!(clients.tags_of(2), Some(vec![Tag::Work2]));
+        assert_eq!(clients.tags_of(3), Some(vec![Tag::Web]));
+        assert_eq!(order(&mut clients, &[Tag::Web]), vec![1, 3]);
+        assert_eq!(order(&mut clients, &[Tag::Work2]), vec![2]);
+    }
+
+    #[test]
+    fn iconic_start_is_added_minimized() {
+        let mut clients = client_set(2);
+        let mut iconic = props();
+        iconic.start_iconic = true;
+        clients.add(Client::new(3, vec![Tag::Web], iconic),
+                    InsertPolicy::AsSlave);
+        assert!(clients
+            .get_client_by_window(3)
+            .map_or(false, |c| c.borrow().is_minimized()));
+        assert_eq!(clients.tiled_windows(&[Tag::Web]), vec![1, 2]);
+    }
+}
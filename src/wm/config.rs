@@ -11,18 +11,23 @@
 //!   a more involved and complex feature.
 //!
 //! But feel free to do otherwise if you wish.
+use std::fmt;
 use std::process::Command;
 
-use wm::client::{TagSet, TagStack, ClientSet};
+use regex::Regex;
+
+use wm::client::{ClientRule, ClientSet, Pattern, TagSet, TagStack};
 use wm::kbd::*;
 
-use wm::layout::{ScreenSize,LayoutMessage};
+use wm::layout::{Direction,ScreenSize,LayoutMessage,Split};
+use wm::layout::dwindle::Dwindle;
 use wm::layout::grid::Grid;
 use wm::layout::monocle::Monocle;
 use wm::layout::spiral::Spiral;
 use wm::layout::stack::{DStack,HStack,VStack};
+use wm::layout::transform::Reflected;
 
-use wm::window_system::{Wm, WmConfig, WmCommand};
+use wm::window_system::{FocusOnMap, Matching, Wm, WmConfig, WmCommand};
 
 /// All tags used by `gabelstaplerwm`
 ///
@@ -58,6 +63,29 @@ impl Default for Tag {
     }
 }
 
+impl Tag {
+    /// Get the human-readable label of the tag, as shown in status output.
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Tag::Web => "web",
+            Tag::Work2 => "work2",
+            Tag::Work3 => "work3",
+            Tag::Work4 => "work4",
+            Tag::Work5 => "work5",
+            Tag::Media => "media",
+            Tag::Chat => "chat",
+            Tag::Logs => "logs",
+            Tag::Mon => "mon",
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
 /// All keyboard modes used by `gabelstaplerwm`-
 ///
 /// A mode represents the active set of keybindings and/or their functionality.
@@ -83,6 +111,16 @@ impl Default for Mode {
     }
 }
 
+impl Mode {
+    /// Get the human-readable name of the mode, as shown in status output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mode::Normal => "normal",
+            Mode::Setup => "setup",
+        }
+    }
+}
+
 /// Generate a window manager config - colors, border width...
 ///
 /// Here you can specify (or compute) the settings you want to have.
@@ -92,12 +130,31 @@ pub fn generate_config() -> WmConfig {
         f_color: (0x5353, 0x5d5d, 0x6c6c),
         u_color: (0x0000, 0x0000, 0x0000),
         border_width: 1,
+        border_on_focus_only: false,
+        smart_borders: false,
+        batch_configure: false,
+        respect_size_hints: false,
         screen: ScreenSize {
             offset_x: 0,
             offset_y: 20,
             width: 800,
             height: 600,
         },
+        click_to_focus: true,
+        focus_on_map: FocusOnMap::Always,
+        new_window_after_focused: false,
+        focus_follows_mouse: false,
+        focus_dwell: 150,
+        kill_grace: 5,
+        follow_sent_client: true,
+        animation_steps: 0,
+        animation_interval: 15,
+        idle_timeout: 0,
+        drag_modifier: ALTGR,
+        autostart_on_empty: Vec::new(),
+        tag_order: vec![Tag::Web, Tag::Work2, Tag::Work3, Tag::Work4,
+                        Tag::Work5, Tag::Media, Tag::Chat, Tag::Logs,
+                        Tag::Mon],
     }
 }
 
@@ -118,6 +175,9 @@ pub fn setup_wm(wm: &mut Wm) {
         bind!(16, modkey, Mode::Normal, push_tagset!(6)),
         bind!(17, modkey, Mode::Normal, push_tagset!(7)),
         bind!(18, modkey, Mode::Normal, push_tagset!(8)),
+        // cycle through tagsets
+        bind!(113, modkey, Mode::Normal, |_, _| WmCommand::CycleTagset(-1)),
+        bind!(114, modkey, Mode::Normal, |_, _| WmCommand::CycleTagset(1)),
         // toggle tags on current client
         bind!(10, modkey+CTRL+SHIFT, Mode::Normal, toggle_tag!(Tag::Web)),
         bind!(11, modkey+CTRL+SHIFT, Mode::Normal, toggle_tag!(Tag::Work2)),
@@ -138,6 +198,25 @@ pub fn setup_wm(wm: &mut Wm) {
         bind!(16, modkey+SHIFT, Mode::Normal, move_to_tag!(Tag::Chat)),
         bind!(17, modkey+SHIFT, Mode::Normal, move_to_tag!(Tag::Logs)),
         bind!(18, modkey+SHIFT, Mode::Normal, move_to_tag!(Tag::Mon)),
+        // move client to tags as master
+        bind!(10, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Web)),
+        bind!(11, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Work2)),
+        bind!(12, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Work3)),
+        bind!(13, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Work4)),
+        bind!(14, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Work5)),
+        bind!(15, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Media)),
+        bind!(16, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Chat)),
+        bind!(17, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Logs)),
+        bind!(18, modkey+CTRL, Mode::Normal,
+              move_to_tag_master!(Tag::Mon)),
         // focus windows
         bind!(43, modkey, Mode::Normal, focus!(ClientSet::focus_left)),
         bind!(44, modkey, Mode::Normal, focus!(ClientSet::focus_bottom)),
@@ -145,6 +224,14 @@ pub fn setup_wm(wm: &mut Wm) {
         bind!(46, modkey, Mode::Normal, focus!(ClientSet::focus_right)),
         bind!(35, modkey, Mode::Normal, focus!(ClientSet::focus_next)),
         bind!(61, modkey, Mode::Normal, focus!(ClientSet::focus_prev)),
+        bind!(35, modkey+CTRL, Mode::Normal,
+              focus!(ClientSet::focus_next_floating)),
+        bind!(61, modkey+CTRL, Mode::Normal,
+              focus!(ClientSet::focus_prev_floating)),
+        bind!(35, modkey+CTRL+SHIFT, Mode::Normal,
+              focus!(ClientSet::focus_next_tiled)),
+        bind!(61, modkey+CTRL+SHIFT, Mode::Normal,
+              focus!(ClientSet::focus_prev_tiled)),
         // swap windows
         bind!(43, modkey+SHIFT, Mode::Normal, swap!(ClientSet::swap_left)),
         bind!(44, modkey+SHIFT, Mode::Normal, swap!(ClientSet::swap_bottom)),
@@ -159,8 +246,91 @@ pub fn setup_wm(wm: &mut Wm) {
         bind!(45, modkey+CTRL, Mode::Normal, edit_layout!(
                 LayoutMessage::MasterFactorRel(5),
                 LayoutMessage::ColumnRel(1))),
+        bind!(57, modkey+CTRL, Mode::Normal, edit_layout!(
+                LayoutMessage::Reset)),
+        bind!(20, modkey+CTRL, Mode::Normal, |_, s|
+              s.send_layout_message(LayoutMessage::GapRel(-2))),
+        bind!(21, modkey+CTRL, Mode::Normal, |_, s|
+              s.send_layout_message(LayoutMessage::GapRel(2))),
+        // switch between the current and the previous layout
+        bind!(65, modkey, Mode::Normal, |_, s| s.toggle_layout()),
+        // lock or unlock the layout of the current tagset
+        bind!(46, modkey+CTRL, Mode::Normal, |_, s| {
+            if let Some(t) = s.current_mut() {
+                let locked = t.is_layout_locked();
+                t.set_layout_locked(!locked);
+            }
+            WmCommand::NoCommand
+        }),
+        // toggle floating state of current client
+        bind!(41, modkey+SHIFT, Mode::Normal, |c, s| s
+            .current()
+            .and_then(|t| c.get_focused_window(&t.tags))
+            .and_then(|w| c.update_client(w, |mut cl| {
+                cl.toggle_floating();
+                WmCommand::Redraw
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+        ),
+        // toggle whether the current client is shown on all tags
+        bind!(39, modkey+CTRL, Mode::Normal, |c, s| s
+            .current()
+            .and_then(|t| c.get_focused_window(&t.tags))
+            .and_then(|w| c.update_client(w, |mut cl| {
+                cl.toggle_sticky();
+                WmCommand::Redraw
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+        ),
+        // resize current client if floating
+        bind!(43, modkey+CTRL+SHIFT, Mode::Normal, |_, _|
+              WmCommand::ResizeFloating(Direction::Right, -10)),
+        bind!(44, modkey+CTRL+SHIFT, Mode::Normal, |_, _|
+              WmCommand::ResizeFloating(Direction::Bottom, 10)),
+        bind!(45, modkey+CTRL+SHIFT, Mode::Normal, |_, _|
+              WmCommand::ResizeFloating(Direction::Bottom, -10)),
+        bind!(46, modkey+CTRL+SHIFT, Mode::Normal, |_, _|
+              WmCommand::ResizeFloating(Direction::Right, 10)),
+        // set split orientation of current client
+        bind!(55, modkey, Mode::Normal, |c, s| s
+            .current()
+            .and_then(|t| c.get_focused_window(&t.tags))
+            .and_then(|w| c.update_client(w, |mut cl| {
+                cl.set_split(Some(Split::Vertical));
+                WmCommand::Redraw
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+        ),
+        bind!(55, modkey+SHIFT, Mode::Normal, |c, s| s
+            .current()
+            .and_then(|t| c.get_focused_window(&t.tags))
+            .and_then(|w| c.update_client(w, |mut cl| {
+                cl.set_split(Some(Split::Horizontal));
+                WmCommand::Redraw
+            }))
+            .unwrap_or(WmCommand::NoCommand)
+        ),
+        // zoom current client
+        bind!(52, modkey, Mode::Normal, |_, _| WmCommand::ToggleZoom),
+        // mark the current client as the scratchpad, and toggle it
+        bind!(49, modkey+SHIFT, Mode::Normal,
+              |_, _| WmCommand::MarkScratchpad),
+        bind!(49, modkey, Mode::Normal, |_, _| WmCommand::ToggleScratchpad),
+        // toggle space reserved for bars
+        bind!(56, modkey, Mode::Normal, |_, _| WmCommand::ToggleStruts),
+        // view and focus the most recently urgent client
+        bind!(30, modkey, Mode::Normal, |_, _| WmCommand::FocusUrgent),
+        // switch between outputs
+        bind!(60, modkey, Mode::Normal, |_, _| WmCommand::FocusNextOutput),
+        bind!(60, modkey+SHIFT, Mode::Normal,
+              |_, _| WmCommand::SendToNextOutput),
+        // launch a terminal
+        bind!(36, modkey+SHIFT, Mode::Normal,
+              |_, _| WmCommand::Spawn(vec!["xterm".to_owned()])),
         // quit the window manager
         bind!(24, modkey+CTRL, Mode::Normal, |_, _| WmCommand::Quit),
+        // rebuild the client set if something went wrong
+        bind!(27, modkey+CTRL+SHIFT, Mode::Normal, |_, _| WmCommand::Rebuild),
         // go back in tagset history
         bind!(42, modkey, Mode::Normal, |_, s| {
             if s.view_prev() {
@@ -175,12 +345,7 @@ pub fn setup_wm(wm: &mut Wm) {
             WmCommand::NoCommand
         }),
         // kill current client
-        bind!(54, modkey, Mode::Normal, |c, s| s
-            .current()
-            .and_then(|t| c.get_focused_window(&t.tags))
-            .map(|w| WmCommand::Kill(w))
-            .unwrap_or(WmCommand::NoCommand)
-        ),
+        bind!(54, modkey, Mode::Normal, |c, s| c.kill_focused(s)),
         // switch to setup mode
         bind!(36, modkey, Mode::Normal, |_, _|
               WmCommand::ModeSwitch(Mode::Setup)),
@@ -198,28 +363,63 @@ pub fn setup_wm(wm: &mut Wm) {
         bind!(17, modkey, Mode::Setup, toggle_show_tag!(Tag::Logs)),
         bind!(18, modkey, Mode::Setup, toggle_show_tag!(Tag::Mon)),
     ]);
-    // default tag stack
-    wm.setup_tags(
+    // default tag stack of each output, viewing different tagsets
+    wm.setup_tags(|output|
         TagStack::from_presets(
             vec![
                 TagSet::new(vec![Tag::Web], DStack::default()),
-                TagSet::new(vec![Tag::Work2], VStack::default()),
-                TagSet::new(vec![Tag::Work3], VStack::default()),
+                TagSet::new(vec![Tag::Work2], VStack::default())
+                    .with_alternate_layout(Monocle::default()),
+                TagSet::new(vec![Tag::Work3], Dwindle::default()),
                 TagSet::new(vec![Tag::Work4], Spiral::default()),
                 TagSet::new(vec![Tag::Work5], Grid::default()),
                 TagSet::new(vec![Tag::Media], Monocle::default()),
                 TagSet::new(vec![Tag::Chat], HStack::default()),
                 TagSet::new(vec![Tag::Logs], HStack::default()),
-                TagSet::new(vec![Tag::Mon], HStack::default()),
-            ], 1
+                TagSet::new(vec![Tag::Mon],
+                            Reflected::new(HStack::default(), false, true)),
+            ], output as u8 + 1
         )
     );
-    // matching function deciding upon client placement
-    wm.setup_matching(Box::new(
+    // rules and matching function deciding upon client placement
+    wm.setup_rules(generate_rules());
+    wm.setup_matching(generate_matching());
+}
+
+/// Generate the rules deciding upon client placement.
+///
+/// The first rule applying to a client is used, the matching function is
+/// consulted for tags if no rule specifies them. This is called again if the
+/// matching is reloaded at runtime.
+pub fn generate_rules() -> Vec<ClientRule> {
+    vec![
+        ClientRule {
+            class: Some(Pattern::Exact("Gimp".to_owned())),
+            layout: Some("floating"),
+            ..ClientRule::default()
+        },
+        ClientRule {
+            class: Some(Pattern::Exact("Pinentry".to_owned())),
+            floating: true,
+            ..ClientRule::default()
+        },
+        ClientRule {
+            name: Some(Pattern::Regex(Regex::new("^(mutt|weechat)").unwrap())),
+            tags: Some(vec![Tag::Work2]),
+            ..ClientRule::default()
+        },
+    ]
+}
+
+/// Generate the matching function deciding upon client placement.
+///
+/// This is called again if the matching is reloaded at runtime.
+pub fn generate_matching() -> Matching {
+    Box::new(
         |props| if props.name == "firefox" {
             Some(vec![Tag::Web])
         } else {
             None
         }
-    ));
+    )
 }
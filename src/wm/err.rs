@@ -1,7 +1,10 @@
+use std::fmt;
 use std::process::exit;
 
 use xcb::base;
 
+use wm::kbd::KeyPress;
+
 /// An error encountered by the WM.
 pub enum WmError {
     CouldNotConnect(base::ConnError),
@@ -35,6 +38,30 @@ impl WmError {
     }
 }
 
+/// A problem found in a keybinding configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BindingError {
+    /// the key is bound more than once, only the last binding is used
+    Duplicate(KeyPress),
+    /// the keycode is outside the range the X server supports
+    InvalidKeycode(KeyPress),
+    /// the key is already grabbed by another client
+    AlreadyGrabbed(KeyPress),
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (msg, key) = match *self {
+            BindingError::Duplicate(ref key) => ("duplicate binding", key),
+            BindingError::InvalidKeycode(ref key) => ("invalid keycode", key),
+            BindingError::AlreadyGrabbed(ref key) =>
+                ("key already grabbed", key),
+        };
+        write!(f, "{}: code={} mods={} mode={:?}",
+               msg, key.code, key.mods, key.mode)
+    }
+}
+
 pub fn handle_logger_error() {
     println!("ERROR:main: could not initialize logger");
     exit(1);
@@ -0,0 +1,333 @@
+//! # IPC socket for gabelstaplerwm
+//! The window manager listens on a Unix domain socket located at
+//! `$XDG_RUNTIME_DIR/gabelstaplerwm$DISPLAY.sock` (or in `/tmp` if the
+//! variable is unset) and accepts simple text commands from scripts. Thus,
+//! instances running on different displays don't interfere. The socket is
+//! only replaced if no other instance is listening on it anymore.
+//!
+//! # Wire format
+//! A client connects, writes a single command terminated by a newline and
+//! reads the response until the window manager closes the connection. The
+//! response consists of zero or more lines of data, followed by a single
+//! line reading either `ok` or `error: <description>`. For instance, using
+//! `socat`:
+//!
+//! ```plaintext
+//! $ SOCK=$XDG_RUNTIME_DIR/gabelstaplerwm$DISPLAY.sock
+//! $ echo "query urgent" | socat - UNIX-CONNECT:$SOCK
+//! 0x1a00003 termite weechat
+//! ok
+//! ```
+//!
+//! Window ids are accepted in decimal or in hexadecimal notation (prefixed
+//! with `0x`), and printed in the latter.
+//!
+//! # Commands
+//! * `query urgent` - list all urgent clients, one per line, consisting of
+//!   the window id, the client's first class and it's title.
+//! * `clear-urgent <window>` - reset the urgency flag of a client without
+//!   focusing it.
+//! * `query next-geometry` - print the geometry the next mapped window would
+//!   be assigned as `<x> <y> <width> <height>`, or nothing if it would be
+//!   hidden.
+//! * `query tags` - print the tags shown on each output as
+//!   `<output> <tag>...`, in the configured display order.
+//! * `query screens` - print the area tiled windows are placed in for each
+//!   output, after accounting for space reserved by docks, as
+//!   `<output> <x> <y> <width> <height>`.
+//! * `query tabs` - print the tabs the layout of the current tagset leaves
+//!   room for, one per tiled window, starting with the shown one, as
+//!   `<window> <x> <y> <width> <height>`.
+//! * `query status` - print a single line describing the current tagset, its
+//!   layout, the keyboard mode and the focused client, suitable for a status
+//!   bar.
+//! * `reload-matching [retag]` - reinstall the client rules and matching
+//!   function, optionally recomputing the tags of all existing clients.
+//! * `focus <next|prev|left|right|top|bottom>` - focus another window on the
+//!   current tagset, like the corresponding key bindings.
+//! * `tag <index>` - view the tagset with the given index on the current
+//!   output.
+//! * `layout <name>` - replace the layout of the current tagset by one with
+//!   default parameters. Known names are `bsp`, `columns`, `dstack`,
+//!   `dwindle`, `floating`, `grid`, `hstack`, `monocle`, `spiral`,
+//!   `tabbed` and `vstack`.
+//! * `list-clients` - list all clients in the order they have been mapped,
+//!   one per line, consisting of the window id, the client's first class
+//!   and it's title.
+use std::env;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use xcb::xproto;
+
+use wm::client::Client;
+use wm::layout::{Direction, ScreenSize};
+
+/// Name of the socket file, to be completed by the display and extension.
+const SOCKET_NAME: &'static str = "gabelstaplerwm";
+
+/// A request received over the IPC socket.
+#[derive(Debug, PartialEq, Eq)]
+pub enum IpcRequest {
+    /// list all urgent clients
+    QueryUrgent,
+    /// reset the urgency flag of a client
+    ClearUrgent(xproto::Window),
+    /// get the geometry of the next window to be mapped
+    QueryNextGeometry,
+    /// get the tags shown on each output
+    QueryTags,
+    /// get the area used for tiling on each output
+    QueryScreens,
+    /// get the tabs of the current tagset's layout
+    QueryTabs,
+    /// get a line of status information
+    QueryStatus,
+    /// reinstall the client rules and matching function, optionally
+    /// retagging all clients
+    ReloadMatching(bool),
+    /// focus the next window on the current tagset
+    FocusNext,
+    /// focus the previous window on the current tagset
+    FocusPrev,
+    /// focus the window in a direction on the current tagset
+    FocusDirection(Direction),
+    /// view a tagset by index
+    ViewTagset(u8),
+    /// set the layout of the current tagset by name
+    SetLayout(String),
+    /// list all clients
+    ListClients,
+}
+
+impl IpcRequest {
+    /// Parse a request from a line of text.
+    pub fn parse(line: &str) -> Result<IpcRequest, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            &["query", "urgent"] => Ok(IpcRequest::QueryUrgent),
+            &["query", "next-geometry"] => Ok(IpcRequest::QueryNextGeometry),
+            &["query", "tags"] => Ok(IpcRequest::QueryTags),
+            &["query", "screens"] => Ok(IpcRequest::QueryScreens),
+            &["query", "tabs"] => Ok(IpcRequest::QueryTabs),
+            &["query", "status"] => Ok(IpcRequest::QueryStatus),
+            &["reload-matching"] => Ok(IpcRequest::ReloadMatching(false)),
+            &["reload-matching", "retag"] =>
+                Ok(IpcRequest::ReloadMatching(true)),
+            &["clear-urgent", window] =>
+                parse_window(window).map(IpcRequest::ClearUrgent),
+            &["focus", "next"] => Ok(IpcRequest::FocusNext),
+            &["focus", "prev"] => Ok(IpcRequest::FocusPrev),
+            &["focus", "left"] =>
+                Ok(IpcRequest::FocusDirection(Direction::Left)),
+            &["focus", "right"] =>
+                Ok(IpcRequest::FocusDirection(Direction::Right)),
+            &["focus", "top"] =>
+                Ok(IpcRequest::FocusDirection(Direction::Top)),
+            &["focus", "bottom"] =>
+                Ok(IpcRequest::FocusDirection(Direction::Bottom)),
+            &["tag", index] => index
+                .parse()
+                .map(IpcRequest::ViewTagset)
+                .map_err(|_| format!("invalid tagset index: {}", index)),
+            &["layout", name] => Ok(IpcRequest::SetLayout(name.to_owned())),
+            &["list-clients"] => Ok(IpcRequest::ListClients),
+            _ => Err(format!("unknown command: {}", line.trim())),
+        }
+    }
+}
+
+/// Parse a window id in decimal or hexadecimal notation.
+fn parse_window(s: &str) -> Result<xproto::Window, String> {
+    let res = if s.starts_with("0x") {
+        u32::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse()
+    };
+    res.map_err(|_| format!("invalid window id: {}", s))
+}
+
+/// Format a window id the way it is accepted by `parse_window`.
+pub fn format_window(window: xproto::Window) -> String {
+    format!("0x{:x}", window)
+}
+
+/// Format a client as a line of a client listing, consisting of the window
+/// id, the client's first class and it's title.
+pub fn format_client(client: &Client) -> String {
+    format!("{} {} {}\n", format_window(client.window),
+            client.props().class.first().map_or("", |c| c), client.name())
+}
+
+/// Format the area tiled windows are placed in on an output as a line of
+/// the screen listing, consisting of the output's name, the offset and the
+/// size of the area.
+pub fn format_screen(name: &str, area: &ScreenSize) -> String {
+    format!("{} {} {} {} {}\n",
+            name, area.offset_x, area.offset_y, area.width, area.height)
+}
+
+/// Get the name of the socket file for a display.
+///
+/// Slashes, as contained in displays given by a path, are replaced.
+fn socket_name(display: &str) -> String {
+    format!("{}{}.sock", SOCKET_NAME, display.replace('/', "_"))
+}
+
+/// The listening end of the IPC mechanism.
+///
+/// The socket file is removed when the object is dropped.
+pub struct IpcSocket {
+    /// the listening socket
+    listener: UnixListener,
+    /// path of the socket file
+    path: PathBuf,
+}
+
+impl IpcSocket {
+    /// Create the socket for the display in use, replacing any stale socket
+    /// file.
+    ///
+    /// Fails if another instance is listening on the socket.
+    pub fn bind() -> io::Result<IpcSocket> {
+        let mut path = env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or(PathBuf::from("/tmp"));
+        path.push(socket_name(&env::var("DISPLAY").unwrap_or_default()));
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse,
+                                      "socket in use by another instance"));
+        }
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(IpcSocket {
+            listener: listener,
+            path: path,
+        })
+    }
+
+    /// Get the file descriptor of the socket, to wait for connections.
+    pub fn fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+
+    /// Accept a pending connection, if any.
+    pub fn accept(&self) -> Option<IpcConnection> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                // don't let a misbehaving client block the window manager
+                let timeout = Some(Duration::from_millis(100));
+                if stream.set_nonblocking(false).is_err() ||
+                   stream.set_read_timeout(timeout).is_err() ||
+                   stream.set_write_timeout(timeout).is_err() {
+                    error!("could not set up ipc connection");
+                    None
+                } else {
+                    Some(IpcConnection { stream: stream })
+                }
+            },
+            Err(_) => None,
+        }
+    }
+}
+
+impl Drop for IpcSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A connection to an IPC client.
+pub struct IpcConnection {
+    /// the connected stream
+    stream: UnixStream,
+}
+
+impl IpcConnection {
+    /// Read a single line containing a request.
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match BufReader::new(&self.stream).read_line(&mut line) {
+            Ok(n) if n > 0 => Some(line),
+            _ => None,
+        }
+    }
+
+    /// Send a response and close the connection.
+    pub fn respond(mut self, response: &str) {
+        if self.stream.write_all(response.as_bytes()).is_err() {
+            error!("could not send ipc response");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wm::client::{ClientProps, ClientSet, InsertPolicy};
+    use wm::config::Tag;
+
+    fn client(window: xproto::Window, class: &str, name: &str) -> Client {
+        let props = ClientProps {
+            window_type: 0,
+            name: name.to_owned(),
+            class: vec![class.to_owned()],
+            protocols: Vec::new(),
+            start_iconic: false,
+            accepts_input: true,
+            pid: None,
+            user_time: None,
+        };
+        Client::new(window, vec![Tag::Web], props)
+    }
+
+    fn urgent_list(clients: &ClientSet) -> String {
+        clients
+            .urgent_clients()
+            .iter()
+            .map(|c| format_client(&c.borrow()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_urgent_requests() {
+        assert_eq!(IpcRequest::parse("query urgent\n"),
+                   Ok(IpcRequest::QueryUrgent));
+        assert_eq!(IpcRequest::parse("clear-urgent 0x1a"),
+                   Ok(IpcRequest::ClearUrgent(26)));
+        assert_eq!(IpcRequest::parse("clear-urgent 26"),
+                   Ok(IpcRequest::ClearUrgent(26)));
+        assert!(IpcRequest::parse("clear-urgent 0xzz").is_err());
+    }
+
+    #[test]
+    fn urgent_list_and_clear() {
+        let mut clients = ClientSet::new();
+        clients.add(client(0x30, "termite", "weechat"),
+                    InsertPolicy::AsSlave);
+        clients.add(client(0x10, "firefox", "mail"), InsertPolicy::AsSlave);
+        clients.add(client(0x20, "xterm", "htop"), InsertPolicy::AsSlave);
+        assert_eq!(urgent_list(&clients), "");
+        clients.mark_urgent(0x30);
+        clients.mark_urgent(0x10);
+        assert_eq!(urgent_list(&clients),
+                   "0x10 firefox mail\n0x30 termite weechat\n");
+        assert!(clients.clear_urgent(0x10));
+        assert_eq!(urgent_list(&clients), "0x30 termite weechat\n");
+        assert_eq!(clients.find_urgent(), Some((vec![Tag::Web], 0x30)));
+        assert!(!clients.clear_urgent(0x40));
+    }
+
+    #[test]
+    fn socket_name_contains_display() {
+        assert_eq!(socket_name(":0"), "gabelstaplerwm:0.sock");
+        assert_eq!(socket_name("/tmp/launch/org:0"),
+                   "gabelstaplerwm_tmp_launch_org:0.sock");
+    }
+}
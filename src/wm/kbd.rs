@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use xcb::base::Connection;
 use xcb::xkb;
 
 use wm::client::{ClientSet, TagStack};
 use wm::config::Mode;
+use wm::err::BindingError;
 use wm::window_system::WmCommand;
 
 // constants for easier modifier handling
@@ -35,6 +36,10 @@ pub type KeyCallback = Box<Fn(&mut ClientSet, &mut TagStack) -> WmCommand>;
 /// Keybinding map.
 pub type Keybindings = HashMap<KeyPress, KeyCallback>;
 
+/// Closure type of a callback function running on unbound key presses.
+pub type FallbackCallback =
+    Box<Fn(KeyPress, &mut ClientSet, &mut TagStack) -> WmCommand>;
+
 /// Closure type of a callback function providing plugin functionality.
 pub type PluginCallback = Box<Fn(&Connection) -> ()>;
 /// Plugin keybinding map.
@@ -59,3 +64,119 @@ pub fn from_key(event: &xkb::StateNotifyEvent, mode: Mode) -> KeyPress {
         mode: mode,
     }
 }
+
+/// The callback a key press is dispatched to.
+pub enum KeyHandler<'a> {
+    /// a keybinding
+    Binding(&'a KeyCallback),
+    /// a plugin keybinding
+    Plugin(&'a PluginCallback),
+    /// the fallback for unbound keys
+    Fallback(&'a FallbackCallback),
+}
+
+/// Get the callback to run on a key press, if any.
+///
+/// Keybindings take precedence over plugins, the fallback being used only
+/// if the key is bound to neither.
+pub fn handler_for<'a>(key: &KeyPress,
+                       bindings: &'a Keybindings,
+                       plugins: &'a PluginBindings,
+                       fallback: Option<&'a FallbackCallback>)
+    -> Option<KeyHandler<'a>> {
+    bindings
+        .get(key)
+        .map(KeyHandler::Binding)
+        .or_else(|| plugins.get(key).map(KeyHandler::Plugin))
+        .or_else(|| fallback.map(KeyHandler::Fallback))
+}
+
+/// Check keys to be bound for duplicates and keycodes outside of the range
+/// `(min, max)` supported by the X server.
+///
+/// Returns a result for each key, in order, keys passing the checks being
+/// returned as is.
+pub fn check_bindings(keys: &[KeyPress], keycodes: (u8, u8))
+    -> Vec<Result<KeyPress, BindingError>> {
+    let (min, max) = keycodes;
+    let mut seen = HashSet::with_capacity(keys.len());
+    keys
+        .iter()
+        .map(|&key| if !seen.insert(key) {
+            Err(BindingError::Duplicate(key))
+        } else if key.code < min || key.code > max {
+            Err(BindingError::InvalidKeycode(key))
+        } else {
+            Ok(key)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    fn key(code: u8, mods: u8) -> KeyPress {
+        KeyPress { code: code, mods: mods, mode: Mode::default() }
+    }
+
+    #[test]
+    fn check_duplicate_and_invalid_keys() {
+        let keys = [key(10, ALTGR), key(10, SHIFT), key(10, ALTGR),
+                    key(4, ALTGR), key(255, NO_MODIFIER)];
+        assert_eq!(check_bindings(&keys, (8, 254)), vec![
+            Ok(key(10, ALTGR)),
+            Ok(key(10, SHIFT)),
+            Err(BindingError::Duplicate(key(10, ALTGR))),
+            Err(BindingError::InvalidKeycode(key(4, ALTGR))),
+            Err(BindingError::InvalidKeycode(key(255, NO_MODIFIER))),
+        ]);
+    }
+
+    #[test]
+    fn check_valid_keys() {
+        let keys = [key(8, CTRL), key(255, CTRL)];
+        assert!(check_bindings(&keys, (8, 255)).iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn fallback_receives_unbound_keys() {
+        let mut bindings = Keybindings::new();
+        bindings.insert(key(10, CTRL),
+                        Box::new(|_, _| WmCommand::Redraw));
+        let plugins = PluginBindings::new();
+        let seen = Rc::new(Cell::new(None));
+        let seen_by_fallback = seen.clone();
+        let fallback: FallbackCallback = Box::new(move |key, _, _| {
+            seen_by_fallback.set(Some(key));
+            WmCommand::NoCommand
+        });
+        let mut clients = ClientSet::new();
+        let mut stack = TagStack::new();
+
+        match handler_for(&key(10, CTRL), &bindings, &plugins,
+                          Some(&fallback)) {
+            Some(KeyHandler::Binding(func)) =>
+                match func(&mut clients, &mut stack) {
+                    WmCommand::Redraw => (),
+                    _ => panic!("wrong binding run"),
+                },
+            _ => panic!("bound key not dispatched to it's binding"),
+        }
+        assert_eq!(seen.get(), None);
+
+        match handler_for(&key(11, CTRL), &bindings, &plugins,
+                          Some(&fallback)) {
+            Some(KeyHandler::Fallback(func)) => {
+                func(key(11, CTRL), &mut clients, &mut stack);
+            },
+            _ => panic!("unbound key not dispatched to the fallback"),
+        }
+        assert_eq!(seen.get(), Some(key(11, CTRL)));
+        assert!(handler_for(&key(11, CTRL), &bindings, &plugins, None)
+            .is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use std::cmp;
+
+use wm::layout::*;
+
+/// Ratio of a split that hasn't been adjusted, in percent.
+const DEFAULT_RATIO: u8 = 50;
+
+/// A split of an area, creating a window.
+#[derive(Clone, Copy, Debug)]
+struct BspSplit {
+    /// index of the window whose area got split
+    parent: usize,
+    /// share of the area kept by the parent window, in percent
+    ratio: u8,
+}
+
+/// Binary space partitioning layout.
+///
+/// ```plaintext
+/// +---+---+
+/// |   | B |
+/// | A +-+-+ A: first window
+/// |   |C|D| B, C, D: windows splitting the area of a focused window
+/// +-+-+-+-+
+/// |E|     | E: window splitting A's area
+/// +-+-----+
+/// ```
+/// Every new window splits the area of the window focused when it was added
+/// along the area's longer axis. The resulting tree of splits is kept by the
+/// layout, so it needs to be notified of added and removed windows. Windows
+/// it doesn't know about split the area of their predecessor.
+pub struct Bsp {
+    /// the splits creating the second, third, ... window
+    splits: Vec<BspSplit>,
+}
+
+impl Default for Bsp {
+    fn default() -> Bsp {
+        Bsp { splits: Vec::new() }
+    }
+}
+
+impl Bsp {
+    /// Get the split creating a window, clamped to valid parents.
+    fn split(&self, index: usize) -> BspSplit {
+        self.splits
+            .get(index - 1)
+            .map_or(BspSplit { parent: index - 1, ratio: DEFAULT_RATIO },
+                    |s| BspSplit { parent: cmp::min(s.parent, index - 1),
+                                   ratio: s.ratio })
+    }
+
+    /// Set the ratio of the split creating a window, returning whether
+    /// changes have been made.
+    fn set_ratio(&mut self, index: usize, ratio: u8) -> bool {
+        let ratio = cmp::min(100, ratio);
+        if let Some(split) = index
+            .checked_sub(1)
+            .and_then(|i| self.splits.get_mut(i)) {
+            if ratio != split.ratio {
+                split.ratio = ratio;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Add an offset to the ratio of the split creating a window, returning
+    /// whether changes have been made.
+    fn resize_split(&mut self, index: usize, delta: i8) -> bool {
+        let ratio = self.split(cmp::max(1, index)).ratio as i16;
+        self.set_ratio(index, cmp::max(0, ratio + delta as i16) as u8)
+    }
+}
+
+impl Layout for Bsp {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        if num_windows == 0 {
+            return Vec::new();
+        }
+        let mut areas = Vec::with_capacity(num_windows);
+        areas.push(screen.clone());
+        for i in 1..num_windows {
+            let split = self.split(i);
+            let parent = areas[split.parent].clone();
+            // the longer axis of the parent's area is split
+            let (kept, new) = if parent.width >= parent.height {
+                let w = master_size(parent.width, split.ratio, true);
+                (ScreenSize { width: w, ..parent.clone() },
+                 ScreenSize {
+                     offset_x: parent.offset_x + w,
+                     width: parent.width - w,
+                     ..parent
+                 })
+            } else {
+                let h = master_size(parent.height, split.ratio, true);
+                (ScreenSize { height: h, ..parent.clone() },
+                 ScreenSize {
+                     offset_y: parent.offset_y + h,
+                     height: parent.height - h,
+                     ..parent
+                 })
+            };
+            areas[split.parent] = kept;
+            areas.push(new);
+        }
+        areas
+            .into_iter()
+            .map(|a| Some(Geometry {
+                x: a.offset_x,
+                y: a.offset_y,
+                width: a.width.saturating_sub(2 * border_width),
+                height: a.height.saturating_sub(2 * border_width),
+            }))
+            .collect()
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if index < max {
+            Some(index + 1)
+        } else {
+            None
+        }
+    }
+
+    fn left_window(&self, index: usize, _: usize) -> Option<usize> {
+        if index != 0 {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    fn top_window(&self, index: usize, _: usize) -> Option<usize> {
+        self.left_window(index, 0)
+    }
+
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.right_window(index, max)
+    }
+
+    fn name(&self) -> &'static str { "bsp" }
+
+    fn new_window_as_master(&self) -> bool { false }
+
+    fn on_client_added(&mut self, index: usize, focused: usize) {
+        // windows from the index on move back by one
+        for split in self.splits.iter_mut() {
+            if split.parent >= index {
+                split.parent += 1;
+            }
+        }
+        let focused = if focused >= index { focused + 1 } else { focused };
+        // the new window splits the focused window's area if it comes
+        // first, a new first window is split by the former one
+        let (position, parent) = match index.checked_sub(1) {
+            Some(position) => (position, cmp::min(focused, position)),
+            None => (0, 0),
+        };
+        let split = BspSplit { parent: parent, ratio: DEFAULT_RATIO };
+        let position = cmp::min(position, self.splits.len());
+        self.splits.insert(position, split);
+    }
+
+    fn on_client_removed(&mut self, index: usize) {
+        // the removed window's area is given to the window it split, or
+        // the one splitting it first if it is the first window
+        let parent = if index > 0 {
+            self.split(index).parent
+        } else {
+            0
+        };
+        if index > 0 && index <= self.splits.len() {
+            self.splits.remove(index - 1);
+        } else if index == 0 && !self.splits.is_empty() {
+            self.splits.remove(0);
+        }
+        for split in self.splits.iter_mut() {
+            if split.parent == index {
+                split.parent = parent;
+            } else if split.parent > index {
+                split.parent -= 1;
+            }
+        }
+    }
+
+    fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
+        match msg {
+            LayoutMessage::MasterFactorAbs(mf) => self.set_ratio(1, mf),
+            LayoutMessage::MasterFactorRel(delta) =>
+                self.resize_split(1, delta),
+            LayoutMessage::ResizeSplit { index, delta } =>
+                self.resize_split(index, delta),
+            LayoutMessage::Reset => {
+                for split in self.splits.iter_mut() {
+                    split.ratio = DEFAULT_RATIO;
+                }
+                true
+            },
+            _ => false,
+        }
+    }
+}
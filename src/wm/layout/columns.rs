@@ -0,0 +1,113 @@
+use std::cmp;
+
+use wm::layout::*;
+
+/// Weight of a column whose width hasn't been adjusted.
+const DEFAULT_WEIGHT: u16 = 100;
+
+/// Minimal weight of a column.
+const MIN_WEIGHT: u16 = 10;
+
+/// Columns layout.
+///
+/// ```plaintext
+/// +--+----+--+
+/// |  |    |  |
+/// |A | B  |C | A, B, C: windows in columns of adjustable width
+/// |  |    |  |
+/// +--+----+--+
+/// ```
+/// Every window gets a column spanning the screen's height. The screen's
+/// width is distributed among the columns proportionally to their weights,
+/// which are equal unless adjusted.
+pub struct Columns {
+    /// weights of the columns from the left, missing ones being the default
+    pub weights: Vec<u16>,
+}
+
+impl Default for Columns {
+    fn default() -> Columns {
+        Columns { weights: Vec::new() }
+    }
+}
+
+impl Columns {
+    /// Get the weight of a column.
+    fn weight(&self, index: usize) -> u16 {
+        self.weights.get(index).cloned().unwrap_or(DEFAULT_WEIGHT)
+    }
+}
+
+impl Layout for Columns {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        let mut res = Vec::with_capacity(num_windows);
+        let total: u32 = (0..num_windows)
+            .map(|i| self.weight(i) as u32)
+            .sum();
+        let mut x = 0;
+        for i in 0..num_windows {
+            // the last column takes the space left by rounding
+            let width = if i + 1 == num_windows {
+                screen.width - x
+            } else {
+                (screen.width as u32 * self.weight(i) as u32 / total) as u16
+            };
+            res.push(Some(Geometry {
+                x: x + screen.offset_x,
+                y: screen.offset_y,
+                width: cmp::max(1, width.saturating_sub(2 * border_width)),
+                height: screen.height.saturating_sub(2 * border_width),
+            }));
+            x += width;
+        }
+        res
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if index < max {
+            Some(index + 1)
+        } else {
+            None
+        }
+    }
+
+    fn left_window(&self, index: usize, _: usize) -> Option<usize> {
+        if index != 0 {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    fn top_window(&self, _: usize, _: usize) -> Option<usize> {
+        None
+    }
+
+    fn bottom_window(&self, _: usize, _: usize) -> Option<usize> {
+        None
+    }
+
+    fn name(&self) -> &'static str { "columns" }
+
+    fn new_window_as_master(&self) -> bool { false }
+
+    fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
+        match msg {
+            LayoutMessage::ResizeColumn { index, delta } => {
+                while self.weights.len() <= index {
+                    self.weights.push(DEFAULT_WEIGHT);
+                }
+                let weight = if delta < 0 {
+                    self.weights[index].saturating_sub(delta.abs() as u16)
+                } else {
+                    self.weights[index].saturating_add(delta.abs() as u16)
+                };
+                self.weights[index] = cmp::max(MIN_WEIGHT, weight);
+            },
+            LayoutMessage::Reset => *self = Columns::default(),
+            _ => return false,
+        };
+        true
+    }
+}
@@ -0,0 +1,150 @@
+use wm::layout::*;
+
+/// Dwindle layout.
+///
+/// ```plaintext
+/// +---+---+
+/// |   | B |
+/// | A +-+-+ A: master window
+/// |   |C|D| B, C, D: windows split off their predecessor's area
+/// +---+-+-+
+/// ```
+/// Each window takes half of the area left by it's predecessor, the split
+/// orientation being chosen by the window itself. Windows without a
+/// preference alternate between vertical and horizontal splits. The last
+/// window fills the remaining area.
+pub struct Dwindle;
+
+impl Default for Dwindle {
+    fn default() -> Dwindle {
+        Dwindle
+    }
+}
+
+impl Layout for Dwindle {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        self.arrange_split(&vec![None; num_windows], screen, border_width)
+    }
+
+    fn arrange_split(&self, splits: &[Option<Split>], screen: &ScreenSize,
+                     border_width: u16) -> Vec<Option<Geometry>> {
+        let num_windows = splits.len();
+        let mut res = Vec::with_capacity(num_windows);
+        let mut x = screen.offset_x;
+        let mut y = screen.offset_y;
+        let mut width = screen.width;
+        let mut height = screen.height;
+        for i in 0..num_windows {
+            if i + 1 == num_windows {
+                // the last window fills the remaining area
+                res.push(Some(Geometry {
+                    x: x,
+                    y: y,
+                    width: width.saturating_sub(2 * border_width),
+                    height: height.saturating_sub(2 * border_width),
+                }));
+                break;
+            }
+            // the next window decides how the remaining area is split
+            let split = splits[i + 1].unwrap_or(if i % 2 == 0 {
+                Split::Vertical
+            } else {
+                Split::Horizontal
+            });
+            match split {
+                Split::Vertical => {
+                    let w = width / 2;
+                    res.push(Some(Geometry {
+                        x: x,
+                        y: y,
+                        width: w.saturating_sub(2 * border_width),
+                        height: height.saturating_sub(2 * border_width),
+                    }));
+                    x += w;
+                    width -= w;
+                },
+                Split::Horizontal => {
+                    let h = height / 2;
+                    res.push(Some(Geometry {
+                        x: x,
+                        y: y,
+                        width: width.saturating_sub(2 * border_width),
+                        height: h.saturating_sub(2 * border_width),
+                    }));
+                    y += h;
+                    height -= h;
+                },
+            }
+        }
+        res
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if index < max {
+            Some(index + 1)
+        } else {
+            None
+        }
+    }
+
+    fn left_window(&self, index: usize, _: usize) -> Option<usize> {
+        if index != 0 {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    fn top_window(&self, index: usize, _: usize) -> Option<usize> {
+        if index != 0 {
+            Some(index - 1)
+        } else {
+            None
+        }
+    }
+
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        if index < max {
+            Some(index + 1)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> &'static str { "dwindle" }
+
+    fn new_window_as_master(&self) -> bool { false }
+
+    fn edit_layout(&mut self, _: LayoutMessage) -> bool { false }
+}
+
+#[cfg(test)]
+mod tests {
+    use wm::layout::*;
+    use super::Dwindle;
+
+    fn screen() -> ScreenSize {
+        ScreenSize { offset_x: 0, offset_y: 0, width: 100, height: 100 }
+    }
+
+    fn geometry(x: u16, y: u16, width: u16, height: u16) -> Option<Geometry> {
+        Some(Geometry { x: x, y: y, width: width, height: height })
+    }
+
+    #[test]
+    fn mixed_orientations() {
+        let splits = [None, Some(Split::Horizontal), Some(Split::Vertical)];
+        assert_eq!(Dwindle.arrange_split(&splits, &screen(), 1),
+                   vec![geometry(0, 0, 98, 48),
+                        geometry(0, 50, 48, 48),
+                        geometry(50, 50, 48, 48)]);
+    }
+
+    #[test]
+    fn large_border_saturates() {
+        let res = Dwindle.arrange(4, &screen(), 40);
+        assert_eq!(res[0], geometry(0, 0, 0, 20));
+        assert_eq!(res[3], geometry(75, 50, 0, 0));
+    }
+}
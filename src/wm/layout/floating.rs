@@ -0,0 +1,54 @@
+use wm::layout::*;
+
+/// Floating layout.
+///
+/// Doesn't arrange windows at all, all of them are shown at their floating
+/// geometries instead, as if they were floating clients. New windows keep
+/// the size they asked for and are centered on the screen. Directional
+/// navigation cycles through all windows.
+pub struct Floating;
+
+impl Default for Floating {
+    fn default() -> Floating {
+        Floating
+    }
+}
+
+impl Layout for Floating {
+    fn arrange(&self, num_windows: usize, _: &ScreenSize, _: u16)
+        -> Vec<Option<Geometry>> {
+        vec![None; num_windows]
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + 1) % (max + 1))
+        } else {
+            None
+        }
+    }
+
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + max) % (max + 1))
+        } else {
+            None
+        }
+    }
+
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.left_window(index, max)
+    }
+
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.right_window(index, max)
+    }
+
+    fn name(&self) -> &'static str { "floating" }
+
+    fn new_window_as_master(&self) -> bool { false }
+
+    fn floats_windows(&self) -> bool { true }
+
+    fn edit_layout(&mut self, _: LayoutMessage) -> bool { false }
+}
@@ -1,3 +1,5 @@
+use std::cmp;
+
 use wm::layout::*;
 
 /// Grid Layout.
@@ -10,82 +12,98 @@ use wm::layout::*;
 /// +-+---+-+
 /// ```
 /// Places windows in a grid with a fixed number of columns, adding new lines
-/// as necessary. Number of columns is configurable.
+/// as necessary. Number of columns is configurable. If the grid is balanced,
+/// the number of columns is the square root of the number of windows,
+/// rounded up, instead.
 /// If the amount of windows present isn't evenly divisible by the number of
 /// columns, the leftover slots are left empty.
 pub struct Grid {
     /// Maximum number of columns.
     pub max_col: u8,
+    /// Compute the number of columns from the number of windows?
+    pub balanced: bool,
 }
 
 impl Default for Grid {
     fn default() -> Grid {
         Grid {
             max_col: 3,
+            balanced: false,
         }
     }
 }
 
-impl Layout for Grid {
-    fn arrange(&self, num_windows: usize, screen: &ScreenSize)
-        -> Vec<Option<Geometry>> {
-        if num_windows > 0 {
-            let max_col = if self.max_col > 0 {
-                self.max_col
-            } else { self.max_col + 1 } as usize;
-            let width = screen.width / max_col as u16;
-            let height =
-                screen.height / (1 + ((num_windows - 1) / max_col)) as u16;
-            (0..num_windows)
-                .map(|i| {
-                    let x = (width + 2) * (i % max_col) as u16;
-                    let y = (height + 2) * (i / max_col) as u16;
-                    Some(Geometry { x: x, y: y, width: width, height: height })
-                })
-                .collect()
+impl Grid {
+    /// Get the number of columns used to display a number of windows.
+    fn columns(&self, num_windows: usize) -> usize {
+        if self.balanced {
+            let mut cols = 1;
+            while cols * cols < num_windows {
+                cols += 1;
+            }
+            cols
         } else {
-            (0..num_windows).map(|_| None).collect()
+            cmp::max(self.max_col, 1) as usize
+        }
+    }
+}
+
+impl Layout for Grid {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        if num_windows == 0 {
+            return Vec::new();
         }
+        let cols = self.columns(num_windows);
+        let rows = (num_windows + cols - 1) / cols;
+        let width = screen.width / cols as u16;
+        let height = screen.height / rows as u16;
+        (0..num_windows)
+            .map(|i| Some(Geometry {
+                x: (i % cols) as u16 * width + screen.offset_x,
+                y: (i / cols) as u16 * height + screen.offset_y,
+                width: width.saturating_sub(2 * border_width),
+                height: height.saturating_sub(2 * border_width),
+            }))
+            .collect()
     }
 
     fn right_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index != max {
+        if index < max && (index + 1) % self.columns(max + 1) != 0 {
             Some(index + 1)
         } else {
             None
         }
     }
 
-    fn left_window(&self, index: usize, _: usize) -> Option<usize> {
-        if index != 0 {
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        if index % self.columns(max + 1) != 0 {
             Some(index - 1)
         } else {
             None
         }
     }
 
-    fn top_window(&self, index: usize, _: usize) -> Option<usize> {
-        let max_col = if self.max_col > 0 {
-            self.max_col
-        } else { self.max_col + 1 } as usize;
-        if index >= max_col {
-            Some(index - max_col)
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        let cols = self.columns(max + 1);
+        if index >= cols {
+            Some(index - cols)
         } else {
             None
         }
     }
 
     fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
-        let max_col = if self.max_col > 0 {
-            self.max_col
-        } else { self.max_col + 1 } as usize;
-        if index < max - max_col {
-            Some(index + max_col)
+        let cols = self.columns(max + 1);
+        if index + cols <= max {
+            Some(index + cols)
         } else {
             None
         }
     }
 
+    fn name(&self) -> &'static str { "grid" }
+
     fn new_window_as_master(&self) -> bool { false }
 
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
@@ -97,6 +115,7 @@ impl Layout for Grid {
                 } else {
                     self.max_col.saturating_add(ncol.abs() as u8)
                 },
+            LayoutMessage::Reset => self.max_col = Grid::default().max_col,
             _ => return false,
         };
         true
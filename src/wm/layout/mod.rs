@@ -1,13 +1,21 @@
+use std::cmp;
+
+pub mod bsp;
+pub mod columns;
+pub mod dwindle;
+pub mod floating;
 pub mod grid;
 pub mod monocle;
 pub mod spiral;
 pub mod stack;
+pub mod tabbed;
+pub mod transform;
 
 /// A screen size to be accounted for when arranging windows.
 ///
 /// Describes the area used for tiling windows. This allows to leave an open
 /// spot for desktop windows, bars and whatever else you might want.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ScreenSize {
     /// x offset of tiling area
     pub offset_x: u16,
@@ -42,10 +50,26 @@ impl ScreenSize {
             height: new_height,
         }
     }
+
+    /// Carve a smaller area out of a `ScreenSize` object.
+    ///
+    /// The given amounts of pixels are removed from the respective edges.
+    /// Insets larger than the area leave an empty area at its far edge.
+    pub fn inset(&self, top: u16, bottom: u16, left: u16, right: u16)
+        -> ScreenSize {
+        let left = cmp::min(left, self.width);
+        let top = cmp::min(top, self.height);
+        ScreenSize {
+            offset_x: self.offset_x + left,
+            offset_y: self.offset_y + top,
+            width: (self.width - left).saturating_sub(right),
+            height: (self.height - top).saturating_sub(bottom),
+        }
+    }
 }
 
 /// A window's geometry.
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Geometry {
     /// x coordinate of window
     pub x: u16,
@@ -57,16 +81,283 @@ pub struct Geometry {
     pub height: u16,
 }
 
+/// Minimal width and height of a floating window.
+const MIN_FLOAT_SIZE: u16 = 20;
+
+/// Minimal width or height of the master and stack areas of a layout.
+const MIN_AREA_SIZE: u16 = 20;
+
+/// Compute the size of a master area from a master factor.
+///
+/// Factors above 100 are treated as 100. The master area keeps a minimal
+/// size, and so does the remaining space if slaves are to be placed in it.
+/// That is, a factor of 0 or 100 doesn't leave any area empty.
+pub fn master_size(total: u16, master_factor: u8, slaves: bool) -> u16 {
+    let factor = cmp::min(master_factor, 100) as u32;
+    let size = (factor * total as u32 / 100) as u16;
+    let min = cmp::min(MIN_AREA_SIZE, total / 2);
+    let max = if slaves { total - min } else { total };
+    cmp::max(min, cmp::min(size, max))
+}
+
+/// Compute an intermediate master factor when animating a change.
+///
+/// `step` out of `steps` steps have been taken, the factor moving linearly
+/// from `from` to `to`. The last step always yields `to`.
+pub fn interpolate_factor(from: u8, to: u8, step: u8, steps: u8) -> u8 {
+    if step >= steps {
+        return to;
+    }
+    let (from, to) = (from as i32, to as i32);
+    (from + (to - from) * step as i32 / steps as i32) as u8
+}
+
+/// Shrink a screen size to leave the outer part of a gap free.
+///
+/// Together with `inner_gap`, this separates windows by `gap` pixels from
+/// each other and from the screen edges. Odd gaps between windows are
+/// rounded down.
+pub fn outer_gap(screen: &ScreenSize, gap: u16) -> ScreenSize {
+    let outer = gap - gap / 2;
+    ScreenSize {
+        offset_x: screen.offset_x + outer,
+        offset_y: screen.offset_y + outer,
+        width: cmp::max(MIN_AREA_SIZE, screen.width.saturating_sub(2 * outer)),
+        height:
+            cmp::max(MIN_AREA_SIZE, screen.height.saturating_sub(2 * outer)),
+    }
+}
+
+/// Shrink window geometries to leave the inner part of a gap free.
+pub fn inner_gap(geometries: Vec<Option<Geometry>>, gap: u16)
+    -> Vec<Option<Geometry>> {
+    let inner = gap / 2;
+    geometries
+        .into_iter()
+        .map(|geometry| geometry.map(|g| Geometry {
+            x: g.x + inner,
+            y: g.y + inner,
+            width: cmp::max(1, g.width.saturating_sub(2 * inner)),
+            height: cmp::max(1, g.height.saturating_sub(2 * inner)),
+        }))
+        .collect()
+}
+
+/// Construct a layout with default parameters from its lowercase name.
+pub fn layout_by_name(name: &str) -> Option<Box<Layout>> {
+    match name {
+        "bsp" => Some(Box::new(bsp::Bsp::default())),
+        "columns" => Some(Box::new(columns::Columns::default())),
+        "dstack" => Some(Box::new(stack::DStack::default())),
+        "dwindle" => Some(Box::new(dwindle::Dwindle::default())),
+        "floating" => Some(Box::new(floating::Floating::default())),
+        "grid" => Some(Box::new(grid::Grid::default())),
+        "hstack" => Some(Box::new(stack::HStack::default())),
+        "monocle" => Some(Box::new(monocle::Monocle::default())),
+        "spiral" => Some(Box::new(spiral::Spiral::default())),
+        "tabbed" => Some(Box::new(tabbed::Tabbed::default())),
+        "vstack" => Some(Box::new(stack::VStack::default())),
+        _ => None,
+    }
+}
+
+impl Geometry {
+    /// Create a geometry of a given size centered on the screen, leaving
+    /// space for the window's border.
+    pub fn centered(screen: &ScreenSize, width: u16, height: u16,
+                    border_width: u16) -> Geometry {
+        let border = 2 * border_width;
+        let width = cmp::min(width, screen.width.saturating_sub(border));
+        let height = cmp::min(height, screen.height.saturating_sub(border));
+        Geometry {
+            x: screen.offset_x +
+                screen.width.saturating_sub(width + border) / 2,
+            y: screen.offset_y +
+                screen.height.saturating_sub(height + border) / 2,
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Shrink the geometry to the largest one of a given aspect ratio fitting
+    /// into it, centered on the original geometry.
+    ///
+    /// The ratio is given as a `(width, height)` pair. If either of them is
+    /// zero, the geometry is returned unchanged.
+    pub fn letterbox(&self, ratio: (u16, u16)) -> Geometry {
+        let (rw, rh) = (ratio.0 as u32, ratio.1 as u32);
+        if rw == 0 || rh == 0 {
+            return self.clone();
+        }
+        let (w, h) = (self.width as u32, self.height as u32);
+        // compare w / h against rw / rh without rounding
+        let (width, height) = if w * rh > h * rw {
+            ((h * rw / rh) as u16, self.height)
+        } else {
+            (self.width, (w * rh / rw) as u16)
+        };
+        Geometry {
+            x: self.x + (self.width - width) / 2,
+            y: self.y + (self.height - height) / 2,
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Stretch the geometry to span the screen vertically, horizontally or
+    /// both, leaving space for the window's border.
+    pub fn maximize(&self, screen: &ScreenSize, vert: bool, horz: bool,
+                    border_width: u16) -> Geometry {
+        let mut res = self.clone();
+        if vert {
+            res.y = screen.offset_y;
+            res.height = screen.height.saturating_sub(2 * border_width);
+        }
+        if horz {
+            res.x = screen.offset_x;
+            res.width = screen.width.saturating_sub(2 * border_width);
+        }
+        res
+    }
+
+    /// Move the geometry by a pointer offset, or resize it by moving its
+    /// bottom right corner, never getting smaller than a minimal size.
+    pub fn dragged(&self, dx: i32, dy: i32, resize: bool) -> Geometry {
+        let mut res = self.clone();
+        if resize {
+            let min = MIN_FLOAT_SIZE as i32;
+            res.width = cmp::max(min, self.width as i32 + dx) as u16;
+            res.height = cmp::max(min, self.height as i32 + dy) as u16;
+        } else {
+            res.x = cmp::max(0, self.x as i32 + dx) as u16;
+            res.y = cmp::max(0, self.y as i32 + dy) as u16;
+        }
+        res
+    }
+
+    /// Move the geometry from one screen to another, keeping its position
+    /// relative to the screens' origins.
+    ///
+    /// The result is clamped to the destination screen, leaving space for
+    /// the window's border, and shrunk if it doesn't fit.
+    pub fn moved_to(&self, src: &ScreenSize, dest: &ScreenSize,
+                    border_width: u16) -> Geometry {
+        let border = 2 * border_width as i32;
+        let clamp = |pos: u16, size: u16, src_start: u16, dest_start: u16,
+                     dest_size: u16| {
+            let size = cmp::max(
+                1, cmp::min(size as i32, dest_size as i32 - border));
+            let pos = pos as i32 - src_start as i32 + dest_start as i32;
+            let max_pos = dest_start as i32 + dest_size as i32 - size - border;
+            (cmp::max(dest_start as i32, cmp::min(pos, max_pos)) as u16,
+             size as u16)
+        };
+        let (x, width) = clamp(self.x, self.width, src.offset_x,
+                               dest.offset_x, dest.width);
+        let (y, height) = clamp(self.y, self.height, src.offset_y,
+                                dest.offset_y, dest.height);
+        Geometry {
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Move one edge of the geometry by a percentage of the screen size.
+    ///
+    /// The edge is determined by `dir`, a positive `percent` grows the
+    /// geometry in that direction, a negative one shrinks it. The result is
+    /// clamped to the screen, leaving space for the window's border, and
+    /// never gets smaller than a minimal size.
+    pub fn resize_rel(&self, screen: &ScreenSize, dir: Direction, percent: i8,
+                      border_width: u16) -> Geometry {
+        let (screen_start, screen_size, start, size) = match dir {
+            Direction::Left | Direction::Right => (
+                screen.offset_x as i32, screen.width as i32,
+                self.x as i32, self.width as i32
+            ),
+            Direction::Top | Direction::Bottom => (
+                screen.offset_y as i32, screen.height as i32,
+                self.y as i32, self.height as i32
+            ),
+        };
+        let delta = screen_size * percent as i32 / 100;
+        let screen_end = screen_start + screen_size - 2 * border_width as i32;
+        let min = MIN_FLOAT_SIZE as i32;
+        let (new_start, new_size) = match dir {
+            Direction::Left | Direction::Top => {
+                // the far edge stays in place
+                let end = start + size;
+                let new_start = cmp::max(
+                    screen_start, cmp::min(start - delta, end - min));
+                (new_start, end - new_start)
+            },
+            Direction::Right | Direction::Bottom => {
+                // the near edge stays in place
+                let new_size = cmp::max(
+                    min, cmp::min(size + delta, screen_end - start));
+                (start, new_size)
+            },
+        };
+        let mut res = self.clone();
+        match dir {
+            Direction::Left | Direction::Right => {
+                res.x = new_start as u16;
+                res.width = new_size as u16;
+            },
+            Direction::Top | Direction::Bottom => {
+                res.y = new_start as u16;
+                res.height = new_size as u16;
+            },
+        }
+        res
+    }
+}
+
+/// A direction on screen.
+///
+/// Used to determine the edge of a floating window to move when resizing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// the left edge
+    Left,
+    /// the right edge
+    Right,
+    /// the top edge
+    Top,
+    /// the bottom edge
+    Bottom,
+}
+
+/// The orientation of a split between a window and its predecessor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Split {
+    /// the area is divided by a horizontal line, windows are stacked
+    Horizontal,
+    /// the area is divided by a vertical line, windows are side by side
+    Vertical,
+}
+
 /// Types that compute geometries for arbitrary amounts of windows.
 ///
-/// The only input such objects get are `ScreenSize` and number of windows.
+/// The only input such objects get are `ScreenSize`, number of windows and
+/// the width of window borders, which geometries leave space for.
 pub trait Layout {
     /// Compute window geometries. 
     ///
     /// If a `None` is returned at a particular position, that window is not
     /// to be made visible.
-    fn arrange(&self, num_windows: usize, screen: &ScreenSize)
-        -> Vec<Option<Geometry>>;
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>>;
+    /// Compute window geometries, given the split orientation each window
+    /// requested, if any.
+    ///
+    /// Layouts ignore the orientations unless they override this method.
+    fn arrange_split(&self, splits: &[Option<Split>], screen: &ScreenSize,
+                     border_width: u16) -> Vec<Option<Geometry>> {
+        self.arrange(splits.len(), screen, border_width)
+    }
     /// Get the window to the right of the nth window.
     fn right_window(&self, index: usize, max: usize) -> Option<usize>;
     /// Get the window to the left of the nth window.
@@ -75,8 +366,35 @@ pub trait Layout {
     fn top_window(&self, index: usize, max: usize) -> Option<usize>;
     /// Get the window to the bottom of the nth window.
     fn bottom_window(&self, index: usize, max: usize) -> Option<usize>;
+    /// Get the name of the layout, as understood by `layout_by_name`.
+    fn name(&self) -> &'static str;
     /// Decide whether to insert new windows as master.
     fn new_window_as_master(&self) -> bool;
+    /// Get notified of a tiled window inserted at the given index, while the
+    /// window with the index `focused` had the focus.
+    ///
+    /// Layouts computing geometries from the number of windows alone don't
+    /// need to override this method.
+    fn on_client_added(&mut self, _index: usize, _focused: usize) { }
+    /// Get notified of the removal of the tiled window with the given index.
+    fn on_client_removed(&mut self, _index: usize) { }
+    /// Decide whether windows are left floating instead of being arranged.
+    ///
+    /// If so, all windows are shown at their floating geometries and
+    /// `arrange` isn't consulted.
+    fn floats_windows(&self) -> bool {
+        false
+    }
+    /// Get the master factor of the layout, if it has a notion of one.
+    fn master_factor(&self) -> Option<u8> {
+        None
+    }
+    /// Get the geometries of the tabs an external bar can draw for a number
+    /// of windows, if the layout leaves room for them.
+    fn tabs(&self, _num_windows: usize, _screen: &ScreenSize)
+        -> Vec<Geometry> {
+        Vec::new()
+    }
     /// React to a `LayoutMessage`, returning true on change.
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool;
     /// React to the first applicable `LayoutMessage`, returning true on
@@ -106,7 +424,8 @@ pub enum LayoutMessage {
     XOffAbs(u16),
     /// Add an offset to the x offset.
     XOffRel(i16),
-    /// Set absolute value of the y offset.
+    /// Set absolute value of the y offset. The tabbed layout uses it as the
+    /// height of its tab strip.
     YOffAbs(u16),
     /// Add an offset to the y offset.
     YOffRel(i16),
@@ -114,4 +433,217 @@ pub enum LayoutMessage {
     ColumnAbs(u8),
     /// Add an offset to the column amount.
     ColumnRel(i8),
+    /// Set the aspect ratio of the master window as `(width, height)`, or
+    /// disable the aspect ratio lock.
+    AspectRatioAbs(Option<(u16, u16)>),
+    /// Set absolute value of the number of master windows.
+    MasterCountAbs(usize),
+    /// Add an offset to the number of master windows.
+    MasterCountRel(i8),
+    /// Set absolute value of the gap between windows.
+    GapAbs(u16),
+    /// Add an offset to the gap between windows.
+    GapRel(i16),
+    /// Change the width of a column, given by index from the left, by adding
+    /// an offset to its weight. Columns have a weight of 100 by default.
+    ResizeColumn { index: usize, delta: i16 },
+    /// Change the ratio of the split creating a window, given by index, by
+    /// adding an offset to the share kept by the window split.
+    ResizeSplit { index: usize, delta: i8 },
+    /// Reset all adjustable attributes to their default values.
+    Reset,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUTS: &'static [&'static str] =
+        &["bsp", "columns", "dstack", "dwindle", "floating", "grid", "hstack",
+          "monocle", "spiral", "tabbed", "vstack"];
+
+    fn screen(offset_x: u16, offset_y: u16, width: u16, height: u16)
+        -> ScreenSize {
+        ScreenSize {
+            offset_x: offset_x,
+            offset_y: offset_y,
+            width: width,
+            height: height,
+        }
+    }
+
+    #[test]
+    fn inset() {
+        assert_eq!(screen(10, 20, 100, 50).inset(1, 2, 3, 4),
+                   screen(13, 21, 93, 47));
+    }
+
+    #[test]
+    fn inset_saturates() {
+        // the near edges are clamped to the far edges of the area
+        assert_eq!(screen(10, 20, 100, 50).inset(60, 0, 120, 0),
+                   screen(110, 70, 0, 0));
+        // the far edges can't move past the near ones
+        assert_eq!(screen(10, 20, 100, 50).inset(0, 60, 0, 120),
+                   screen(10, 20, 0, 0));
+        assert_eq!(screen(10, 20, 100, 50).inset(30, 30, 60, 60),
+                   screen(70, 50, 0, 0));
+        assert_eq!(screen(0, 0, 0, 0).inset(1, 1, 1, 1), screen(0, 0, 0, 0));
+    }
+
+    fn geometry(x: u16, y: u16, width: u16, height: u16) -> Geometry {
+        Geometry { x: x, y: y, width: width, height: height }
+    }
+
+    #[test]
+    fn centered_leaves_space_for_border() {
+        let screen = screen(10, 20, 100, 50);
+        assert_eq!(Geometry::centered(&screen, 40, 20, 5),
+                   geometry(35, 30, 40, 20));
+        assert_eq!(Geometry::centered(&screen, 200, 200, 5),
+                   geometry(10, 20, 90, 40));
+        assert_eq!(Geometry::centered(&screen, 200, 200, 80),
+                   geometry(10, 20, 0, 0));
+    }
+
+    #[test]
+    fn maximize_leaves_space_for_border() {
+        let screen = screen(10, 20, 100, 50);
+        let geom = geometry(30, 30, 20, 10);
+        assert_eq!(geom.maximize(&screen, true, false, 3),
+                   geometry(30, 20, 20, 44));
+        assert_eq!(geom.maximize(&screen, false, true, 3),
+                   geometry(10, 30, 94, 10));
+    }
+
+    #[test]
+    fn resize_rel_clamps_to_screen() {
+        let screen = screen(0, 0, 100, 100);
+        let geom = geometry(10, 10, 50, 50);
+        assert_eq!(geom.resize_rel(&screen, Direction::Right, 50, 1),
+                   geometry(10, 10, 88, 50));
+        assert_eq!(geom.resize_rel(&screen, Direction::Right, 50, 5),
+                   geometry(10, 10, 80, 50));
+        assert_eq!(geom.resize_rel(&screen, Direction::Left, 50, 1),
+                   geometry(0, 10, 60, 50));
+        assert_eq!(geom.resize_rel(&screen, Direction::Top, 10, 1),
+                   geometry(10, 0, 50, 60));
+        assert_eq!(geom.resize_rel(&screen, Direction::Bottom, 10, 1),
+                   geometry(10, 10, 50, 60));
+    }
+
+    #[test]
+    fn resize_rel_keeps_minimal_size() {
+        let screen = screen(0, 0, 100, 100);
+        let geom = geometry(10, 10, 50, 50);
+        assert_eq!(geom.resize_rel(&screen, Direction::Left, -100, 1),
+                   geometry(40, 10, MIN_FLOAT_SIZE, 50));
+        assert_eq!(geom.resize_rel(&screen, Direction::Bottom, -100, 1),
+                   geometry(10, 10, 50, MIN_FLOAT_SIZE));
+    }
+
+    #[test]
+    fn master_size_with_slaves() {
+        assert_eq!(master_size(100, 0, true), MIN_AREA_SIZE);
+        assert_eq!(master_size(100, 40, true), 40);
+        assert_eq!(master_size(100, 100, true), 100 - MIN_AREA_SIZE);
+        assert_eq!(master_size(100, 200, true), 100 - MIN_AREA_SIZE);
+    }
+
+    #[test]
+    fn master_size_without_slaves() {
+        assert_eq!(master_size(100, 0, false), MIN_AREA_SIZE);
+        assert_eq!(master_size(100, 100, false), 100);
+    }
+
+    #[test]
+    fn master_size_small_areas() {
+        // both areas get half of the space if it is too small
+        for factor in &[0, 50, 100] {
+            assert_eq!(master_size(10, *factor, true), 5);
+        }
+        assert_eq!(master_size(1, 100, true), 1);
+        assert_eq!(master_size(0, 50, true), 0);
+    }
+
+    #[test]
+    fn letterbox() {
+        let geom = geometry(10, 20, 200, 100);
+        // too wide: shrink horizontally
+        assert_eq!(geom.letterbox((1, 1)), geometry(60, 20, 100, 100));
+        // too tall: shrink vertically
+        assert_eq!(geom.letterbox((4, 1)), geometry(10, 45, 200, 50));
+        // matching ratio
+        assert_eq!(geom.letterbox((2, 1)), geom);
+        assert_eq!(geom.letterbox((16, 8)), geom);
+    }
+
+    #[test]
+    fn letterbox_invalid_ratio() {
+        let geom = geometry(10, 20, 200, 100);
+        assert_eq!(geom.letterbox((0, 1)), geom);
+        assert_eq!(geom.letterbox((1, 0)), geom);
+    }
+
+    #[test]
+    fn interpolate_factor_growing() {
+        let frames: Vec<_> =
+            (1..5).map(|step| interpolate_factor(50, 70, step, 4)).collect();
+        assert_eq!(frames, vec![55, 60, 65, 70]);
+        assert_eq!(interpolate_factor(50, 70, 0, 4), 50);
+    }
+
+    #[test]
+    fn interpolate_factor_shrinking() {
+        let frames: Vec<_> =
+            (1..4).map(|step| interpolate_factor(70, 0, step, 3)).collect();
+        assert_eq!(frames, vec![47, 24, 0]);
+    }
+
+    #[test]
+    fn interpolate_factor_last_frame() {
+        assert_eq!(interpolate_factor(33, 34, 7, 7), 34);
+        assert_eq!(interpolate_factor(33, 34, 9, 7), 34);
+        assert_eq!(interpolate_factor(33, 34, 0, 0), 34);
+    }
+
+    #[test]
+    fn large_borders_dont_underflow() {
+        let screen = screen(10, 10, 30, 30);
+        for name in LAYOUTS {
+            let layout = layout_by_name(name).unwrap();
+            for num_windows in 0..10 {
+                for geometry in layout.arrange(num_windows, &screen, 20) {
+                    if let Some(g) = geometry {
+                        assert!(g.width <= screen.width, "{}", name);
+                        assert!(g.height <= screen.height, "{}", name);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn moved_between_screens() {
+        let left = screen(0, 20, 1920, 1060);
+        let right = screen(1920, 0, 1280, 1024);
+        let g = geometry(100, 120, 400, 300);
+        assert_eq!(g.moved_to(&left, &right, 1),
+                   geometry(2020, 100, 400, 300));
+        assert_eq!(g.moved_to(&left, &right, 1).moved_to(&right, &left, 1),
+                   g);
+    }
+
+    #[test]
+    fn moved_to_smaller_screen_is_clamped() {
+        let left = screen(0, 20, 1920, 1060);
+        let right = screen(1920, 0, 1280, 1024);
+        // a window partly left of it's screen doesn't underflow
+        assert_eq!(geometry(1900, 0, 200, 100).moved_to(&right, &left, 1),
+                   geometry(0, 20, 200, 100));
+        assert_eq!(geometry(1500, 1000, 300, 100).moved_to(&left, &right, 1),
+                   geometry(2898, 922, 300, 100));
+        assert_eq!(geometry(0, 20, 1918, 1058).moved_to(&left, &right, 1),
+                   geometry(1920, 0, 1278, 1022));
+    }
 }
@@ -4,7 +4,8 @@ use wm::layout::*;
 ///
 /// Shows one window at a time, keeping offsets to the screen border.
 /// New clients are added as master, otherwise they would be invisible
-/// at first.
+/// at first. Directional navigation cycles through all windows, with the
+/// focused window becoming the master.
 pub struct Monocle {
     /// x offset of master window (symmetric)
     pub offset_x: u16,
@@ -22,15 +23,20 @@ impl Default for Monocle {
 }
 
 impl Layout for Monocle {
-    fn arrange(&self, num_windows: usize, screen: &ScreenSize)
-        -> Vec<Option<Geometry>> {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
         let mut res = Vec::with_capacity(num_windows);
+        if num_windows == 0 {
+            return res;
+        }
         // master window is shown
         res.push(Some(Geometry {
             x: self.offset_x + screen.offset_x,
             y: self.offset_y + screen.offset_y,
-            width: screen.width - 2 * self.offset_x - 2,
-            height: screen.height - 2 * self.offset_y - 2,
+            width: screen.width
+                .saturating_sub(2 * self.offset_x + 2 * border_width),
+            height: screen.height
+                .saturating_sub(2 * self.offset_y + 2 * border_width),
         }));
         // all other windows are hidden
         for _ in 1..num_windows {
@@ -39,22 +45,32 @@ impl Layout for Monocle {
         res
     }
 
-    fn right_window(&self, _: usize, _: usize) -> Option<usize> {
-        None
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + 1) % (max + 1))
+        } else {
+            None
+        }
     }
 
-    fn left_window(&self, _: usize, _: usize) -> Option<usize> {
-        None
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + max) % (max + 1))
+        } else {
+            None
+        }
     }
 
-    fn top_window(&self, _: usize, _: usize) -> Option<usize> {
-        None
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.left_window(index, max)
     }
 
-    fn bottom_window(&self, _: usize, _: usize) -> Option<usize> {
-        None
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.right_window(index, max)
     }
 
+    fn name(&self) -> &'static str { "monocle" }
+
     fn new_window_as_master(&self) -> bool { true }
 
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
@@ -69,6 +85,7 @@ impl Layout for Monocle {
                 self.offset_y = if y < 0 {
                     self.offset_y.saturating_sub(y.abs() as u16)
                 } else { self.offset_y.saturating_add(y.abs() as u16) },
+            LayoutMessage::Reset => *self = Monocle::default(),
             _ => return false,
         };
         true
@@ -2,19 +2,52 @@ use std::cmp;
 
 use wm::layout::*;
 
+/// Spiral layout.
+///
+/// ```plaintext
+/// +---+---+
+/// |   | B |
+/// | A +-+-+ A: master window
+/// |   |D|C| B, C, D: windows placed in a clockwise spiral
+/// +---+-+-+
+/// ```
+/// Each window takes half of the area left by its predecessor, the splits
+/// alternating between vertical and horizontal, spiraling inwards. The last
+/// window shown fills the remaining area, windows beyond `max_windows` are
+/// hidden.
 pub struct Spiral {
-    pub max_windows: u8
+    /// maximal number of windows shown
+    pub max_windows: u8,
+    /// gap in pixels between windows and to the screen edges
+    pub gap: u16,
 }
 
 impl Default for Spiral {
     fn default() -> Spiral {
-        Spiral { max_windows: 8 }
+        Spiral {
+            max_windows: 8,
+            gap: 0,
+        }
     }
 }
 
+impl Spiral {
+    /// Get the index of the last window shown, given the index of the last
+    /// window present.
+    fn last_shown(&self, max: usize) -> usize {
+        cmp::min(max, cmp::max(1, self.max_windows as usize) - 1)
+    }
+}
+
+/// Halve a size, leaving space for the borders of both halves.
+fn half(size: u16, border_width: u16) -> u16 {
+    (size / 2).saturating_sub(border_width)
+}
+
 impl Layout for Spiral {
-    fn arrange(&self, num_windows: usize, screen: &ScreenSize)
-        -> Vec<Option<Geometry>> {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        let screen = &outer_gap(screen, self.gap);
         let mut east = true;
         let mut north = true;
         let mut cur_width = screen.width;
@@ -23,42 +56,44 @@ impl Layout for Spiral {
         let mut cur_y = screen.offset_y;
 
         let min = if num_windows != 0 {
-            cmp::min(num_windows, self.max_windows as usize) - 1
+            self.last_shown(num_windows - 1)
         } else { 0 };
 
-        (0..num_windows)
+        let res = (0..num_windows)
             .map(|i| {
                 if num_windows == 1 {
                     // thus, i is 0 as well
                 } else if i == 0 {
-                    cur_width = cur_width / 2 - 1;
+                    cur_width = half(cur_width, border_width);
                 } else if i > min {
                     return None;
                 } else if north && east {
                     if i < min {
-                        cur_height = cur_height / 2 - 1;
+                        cur_height = half(cur_height, border_width);
                     }
-                    cur_x += cur_width + 2;
+                    cur_x += cur_width + 2 * border_width;
                     north = false;
                 } else if !north && east {
                     if i < min {
-                        cur_width = cur_width / 2 - 1;
-                        cur_x += cur_width + 2;
+                        cur_width = half(cur_width, border_width);
+                        cur_x += cur_width + 2 * border_width;
                     }
-                    cur_y += cur_height + 2;
+                    cur_y += cur_height + 2 * border_width;
                     east = false;
                 } else if !north && !east {
                     if i < min {
-                        cur_height = cur_height / 2 - 1;
-                        cur_y += cur_height + 2;
+                        cur_height = half(cur_height, border_width);
+                        cur_y += cur_height + 2 * border_width;
                     }
-                    cur_x -= cur_width + 2;
+                    cur_x = cur_x
+                        .saturating_sub(cur_width + 2 * border_width);
                     north = true;
                 } else {
                     if i < min {
-                        cur_width = cur_width / 2 - 1;
+                        cur_width = half(cur_width, border_width);
                     }
-                    cur_y -= cur_height + 2;
+                    cur_y = cur_y
+                        .saturating_sub(cur_height + 2 * border_width);
                     east = true;
                 }
                 Some(Geometry {
@@ -68,11 +103,12 @@ impl Layout for Spiral {
                     height: cur_height,
                 })
             })
-            .collect()
+            .collect();
+        inner_gap(res, self.gap)
     }
 
     fn right_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index < cmp::max(max, self.max_windows as usize) - 1 {
+        if index < self.last_shown(max) {
             Some(index + 1)
         } else {
             None
@@ -96,14 +132,29 @@ impl Layout for Spiral {
     }
 
     fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index < cmp::max(max, self.max_windows as usize) - 1 {
+        if index < self.last_shown(max) {
             Some(index + 1)
         } else {
             None
         }
     }
 
+    fn name(&self) -> &'static str { "spiral" }
+
     fn new_window_as_master(&self) -> bool { false }
 
-    fn edit_layout(&mut self, _: LayoutMessage) -> bool { false }
+    fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
+        match msg {
+            LayoutMessage::GapAbs(gap) => self.gap = gap,
+            LayoutMessage::GapRel(gap) =>
+                self.gap = if gap < 0 {
+                    self.gap.saturating_sub(gap.abs() as u16)
+                } else {
+                    self.gap.saturating_add(gap.abs() as u16)
+                },
+            LayoutMessage::Reset => *self = Spiral::default(),
+            _ => return false,
+        };
+        true
+    }
 }
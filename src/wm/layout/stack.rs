@@ -1,5 +1,13 @@
+use std::cmp;
+
 use wm::layout::*;
 
+/// Get the number of windows in the master area, at least one unless no
+/// windows are present.
+fn num_masters(master_count: usize, num_windows: usize) -> usize {
+    cmp::min(cmp::max(1, master_count), num_windows)
+}
+
 /// Dual stack layout.
 ///
 /// ```plaintext
@@ -30,24 +38,22 @@ impl Default for DStack {
 }
 
 impl Layout for DStack {
-    fn arrange(&self, num_windows: usize, screen: &ScreenSize)
-        -> Vec<Option<Geometry>> {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
         let mut res = Vec::with_capacity(num_windows);
-        // set master window width, capping factor
-        let master_width = if self.master_factor >= 100 {
-            screen.width
-        } else {
-            self.master_factor as u16 * screen.width / 100
-        };
+        // set master window width, keeping space for the stack
+        let master_width =
+            master_size(screen.width, self.master_factor, num_windows > 1);
         if num_windows == 1 && !self.fixed {
             // one window only - fullscreen
             res.push(Some(Geometry {
                 x: screen.offset_x,
                 y: screen.offset_y,
-                width: screen.width,
-                height: screen.height - 2,
+                width: screen.width.saturating_sub(2 * border_width),
+                height: screen.height.saturating_sub(2 * border_width),
             }));
-        } else if num_windows > 1 {
+        } else if num_windows > 0 {
+            // a fixed layout keeps the master area centered
             let slave_width = (screen.width - master_width) / 2;
             // setup two slave stacks if needed
             let (master_x, slave_right_x) = if num_windows == 2 &&
@@ -61,8 +67,8 @@ impl Layout for DStack {
             res.push(Some(Geometry {
                 x: master_x + screen.offset_x,
                 y: screen.offset_y,
-                width: master_width - 2,
-                height: screen.height - 2,
+                width: master_width.saturating_sub(2 * border_width),
+                height: screen.height.saturating_sub(2 * border_width),
             }));
             // num_left_slaves <= num_right_slaves
             let num_left_slaves = (num_windows - 1) / 2;
@@ -73,8 +79,9 @@ impl Layout for DStack {
                     res.push(Some(Geometry {
                         x: screen.offset_x,
                         y: i as u16 * slave_height_left + screen.offset_y,
-                        height: slave_height_left - 2,
-                        width: slave_width - 2,
+                        height: slave_height_left
+                            .saturating_sub(2 * border_width),
+                        width: slave_width.saturating_sub(2 * border_width),
                     }));
                 }
             }
@@ -94,8 +101,9 @@ impl Layout for DStack {
                     res.push(Some(Geometry {
                         x: slave_right_x + screen.offset_x,
                         y: i as u16 * slave_height_right + screen.offset_y,
-                        height: slave_height_right - 2,
-                        width: width - 2,
+                        height: slave_height_right
+                            .saturating_sub(2 * border_width),
+                        width: width.saturating_sub(2 * border_width),
                     }));
                 }
             }
@@ -159,8 +167,14 @@ impl Layout for DStack {
         }
     }
 
+    fn name(&self) -> &'static str { "dstack" }
+
     fn new_window_as_master(&self) -> bool { false }
 
+    fn master_factor(&self) -> Option<u8> {
+        Some(self.master_factor)
+    }
+
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
         match msg {
             LayoutMessage::MasterFactorAbs(mf) =>
@@ -173,6 +187,11 @@ impl Layout for DStack {
                 },
             LayoutMessage::FixedAbs(f) => self.fixed = f,
             LayoutMessage::FixedRel => self.fixed = !self.fixed,
+            LayoutMessage::Reset => {
+                let default = DStack::default();
+                self.master_factor = default.master_factor;
+                self.fixed = default.fixed;
+            },
             _ => return false,
         };
         true
@@ -197,6 +216,10 @@ pub struct HStack {
     pub inverted: bool,
     /// keep the height(s) of the areas even if they are empty?
     pub fixed: bool,
+    /// gap in pixels between windows and to the screen edges
+    pub gap: u16,
+    /// number of windows placed in the master area, at least one
+    pub master_count: usize,
 }
 
 impl Default for HStack {
@@ -205,6 +228,8 @@ impl Default for HStack {
             master_factor: 50,
             inverted: false,
             fixed: false,
+            gap: 0,
+            master_count: 1,
         }
     }
 }
@@ -212,68 +237,72 @@ impl Default for HStack {
 impl Layout for HStack {
     fn arrange(&self,
                num_windows: usize,
-               screen: &ScreenSize)
+               screen: &ScreenSize,
+               border_width: u16)
                -> Vec<Option<Geometry>> {
+        let screen = &outer_gap(screen, self.gap);
         let mut res = Vec::with_capacity(num_windows);
-        // set master window height, capping factor
-        let master_height = if self.master_factor >= 100 {
+        if num_windows == 0 {
+            return res;
+        }
+        let masters = num_masters(self.master_count, num_windows);
+        let slaves = num_windows - masters;
+        // set master area height, keeping space for the stack
+        let master_height = if slaves == 0 && !self.fixed {
+            // no stack - the master area takes the whole screen
             screen.height
         } else {
-            self.master_factor as u16 * screen.height / 100
+            master_size(screen.height, self.master_factor, slaves > 0)
         };
-        if num_windows == 1 {
-            // one window only - fullscreen or fixed size
-            let h = if self.fixed {
-                master_height
-            } else {
-                screen.height
-            };
-            res.push(Some(Geometry {
-                x: screen.offset_x,
-                y: screen.offset_y,
-                width: screen.width,
-                height: h - 2,
-            }));
-        } else if num_windows > 1 {
-            // optionally swap stack and master area
-            let (master_y, slave_y) = if self.inverted {
-                (screen.height - master_height, 0)
-            } else {
-                (0, master_height)
-            };
-            // master window
+        // optionally swap stack and master area
+        let slave_height = screen.height - master_height;
+        let (master_area, slave_area) = if self.inverted {
+            (screen.inset(slave_height, 0, 0, 0),
+             screen.inset(0, master_height, 0, 0))
+        } else {
+            (screen.inset(0, slave_height, 0, 0),
+             screen.inset(master_height, 0, 0, 0))
+        };
+        // master windows
+        let master_width = master_area.width / masters as u16;
+        for i in 0..masters {
             res.push(Some(Geometry {
-                x: screen.offset_x,
-                y: master_y + screen.offset_y,
-                width: screen.width - 2,
-                height: master_height - 2,
+                x: i as u16 * master_width + master_area.offset_x,
+                y: master_area.offset_y,
+                width: master_width.saturating_sub(2 * border_width),
+                height: master_area.height.saturating_sub(2 * border_width),
             }));
-            // slave windows
-            let slave_width = screen.width / (num_windows as u16 - 1);
-            for i in 1..num_windows {
+        }
+        // slave windows
+        if slaves > 0 {
+            let slave_width = slave_area.width / slaves as u16;
+            for i in 0..slaves {
                 res.push(Some(Geometry {
-                    x: (i as u16 - 1) * slave_width + screen.offset_x,
-                    y: slave_y + screen.offset_y,
-                    width: slave_width - 2,
-                    height: screen.height - master_height - 2,
+                    x: i as u16 * slave_width + slave_area.offset_x,
+                    y: slave_area.offset_y,
+                    width: slave_width.saturating_sub(2 * border_width),
+                    height: slave_area.height.saturating_sub(2 * border_width),
                 }));
             }
         }
-        res
+        inner_gap(res, self.gap)
     }
 
     fn right_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            Some(max)
-        } else if index < max {
+        let masters = num_masters(self.master_count, max + 1);
+        if index + 1 < masters || (index >= masters && index < max) {
             Some(index + 1)
+        } else if index + 1 == masters && max > index {
+            // the last master window has the stack's end to its right
+            Some(max)
         } else {
             None
         }
     }
 
-    fn left_window(&self, index: usize, _: usize) -> Option<usize> {
-        if index <= 1 {
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        let masters = num_masters(self.master_count, max + 1);
+        if index == 0 || index == masters {
             None
         } else {
             Some(index - 1)
@@ -281,9 +310,10 @@ impl Layout for HStack {
     }
 
     fn top_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            if self.inverted && max >= 1 {
-                Some(1)
+        let masters = num_masters(self.master_count, max + 1);
+        if index < masters {
+            if self.inverted && max >= masters {
+                Some(masters)
             } else {
                 None
             }
@@ -297,9 +327,10 @@ impl Layout for HStack {
     }
 
     fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            if !self.inverted && max >= 1 {
-                Some(1)
+        let masters = num_masters(self.master_count, max + 1);
+        if index < masters {
+            if !self.inverted && max >= masters {
+                Some(masters)
             } else {
                 None
             }
@@ -312,8 +343,14 @@ impl Layout for HStack {
         }
     }
 
+    fn name(&self) -> &'static str { "hstack" }
+
     fn new_window_as_master(&self) -> bool { false }
 
+    fn master_factor(&self) -> Option<u8> {
+        Some(self.master_factor)
+    }
+
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
         match msg {
             LayoutMessage::MasterFactorAbs(mf) =>
@@ -326,6 +363,29 @@ impl Layout for HStack {
                 },
             LayoutMessage::FixedAbs(f) => self.fixed = f,
             LayoutMessage::FixedRel => self.fixed = !self.fixed,
+            LayoutMessage::GapAbs(gap) => self.gap = gap,
+            LayoutMessage::GapRel(gap) =>
+                self.gap = if gap < 0 {
+                    self.gap.saturating_sub(gap.abs() as u16)
+                } else {
+                    self.gap.saturating_add(gap.abs() as u16)
+                },
+            LayoutMessage::MasterCountAbs(count) =>
+                self.master_count = cmp::max(1, count),
+            LayoutMessage::MasterCountRel(count) =>
+                self.master_count = if count < 0 {
+                    cmp::max(1, self.master_count
+                             .saturating_sub(count.abs() as usize))
+                } else {
+                    self.master_count.saturating_add(count.abs() as usize)
+                },
+            LayoutMessage::Reset => {
+                let default = HStack::default();
+                self.master_factor = default.master_factor;
+                self.fixed = default.fixed;
+                self.gap = default.gap;
+                self.master_count = default.master_count;
+            },
             _ => return false,
         };
         true
@@ -341,7 +401,9 @@ impl Layout for HStack {
 /// |    | B|
 /// +----+--+
 /// ```
-/// New windows are added as slaves to the stack.
+/// New windows are added as slaves to the stack. If an aspect ratio is set,
+/// the master window is letterboxed within its area, leaving the remaining
+/// space blank.
 pub struct VStack {
     /// percentage of screen height taken by the master window area,
     /// saturating semantics
@@ -350,6 +412,12 @@ pub struct VStack {
     pub inverted: bool,
     /// keep the height(s) of the areas even if they are empty?
     pub fixed: bool,
+    /// gap in pixels between windows and to the screen edges
+    pub gap: u16,
+    /// number of windows placed in the master area, at least one
+    pub master_count: usize,
+    /// aspect ratio of the master window as `(width, height)`, if locked
+    pub aspect_ratio: Option<(u16, u16)>,
 }
 
 impl Default for VStack {
@@ -358,6 +426,19 @@ impl Default for VStack {
             master_factor: 50,
             inverted: false,
             fixed: false,
+            gap: 0,
+            master_count: 1,
+            aspect_ratio: None,
+        }
+    }
+}
+
+impl VStack {
+    /// Fit the master window into its area, respecting the aspect ratio.
+    fn master_geometry(&self, area: Geometry) -> Geometry {
+        match self.aspect_ratio {
+            Some(ratio) => area.letterbox(ratio),
+            None => area,
         }
     }
 }
@@ -365,60 +446,62 @@ impl Default for VStack {
 impl Layout for VStack {
     fn arrange(&self,
                num_windows: usize,
-               screen: &ScreenSize)
+               screen: &ScreenSize,
+               border_width: u16)
                -> Vec<Option<Geometry>> {
+        let screen = &outer_gap(screen, self.gap);
         let mut res = Vec::with_capacity(num_windows);
-        // set master window width, capping factor
-        let master_width = if self.master_factor >= 100 {
+        if num_windows == 0 {
+            return res;
+        }
+        let masters = num_masters(self.master_count, num_windows);
+        let slaves = num_windows - masters;
+        // set master area width, keeping space for the stack
+        let master_width = if slaves == 0 && !self.fixed {
+            // no stack - the master area takes the whole screen
             screen.width
         } else {
-            self.master_factor as u16 * screen.width / 100
+            master_size(screen.width, self.master_factor, slaves > 0)
         };
-        if num_windows == 1 {
-            // one window only - fullscreen or fixed size
-            let w = if self.fixed {
-                master_width
-            } else {
-                screen.width
-            };
-            res.push(Some(Geometry {
-                x: screen.offset_x,
-                y: screen.offset_y,
-                width: w - 2,
-                height: screen.height - 2,
-            }));
-        } else if num_windows > 1 {
-            // optionally swap stack and master area
-            let (master_x, slave_x) = if self.inverted {
-                (screen.width - master_width, 0)
-            } else {
-                (0, master_width)
-            };
-            // master window
-            res.push(Some(Geometry {
-                x: master_x + screen.offset_x,
-                y: screen.offset_y,
-                width: master_width - 2,
-                height: screen.height - 2,
-            }));
-            // slave windows
-            let slave_height = screen.height / (num_windows as u16 - 1);
-            for i in 1..num_windows {
+        // optionally swap stack and master area
+        let slave_width = screen.width - master_width;
+        let (master_area, slave_area) = if self.inverted {
+            (screen.inset(0, 0, slave_width, 0),
+             screen.inset(0, 0, 0, master_width))
+        } else {
+            (screen.inset(0, 0, 0, slave_width),
+             screen.inset(0, 0, master_width, 0))
+        };
+        // master windows
+        let master_height = master_area.height / masters as u16;
+        for i in 0..masters {
+            res.push(Some(self.master_geometry(Geometry {
+                x: master_area.offset_x,
+                y: i as u16 * master_height + master_area.offset_y,
+                width: master_area.width.saturating_sub(2 * border_width),
+                height: master_height.saturating_sub(2 * border_width),
+            })));
+        }
+        // slave windows
+        if slaves > 0 {
+            let slave_height = slave_area.height / slaves as u16;
+            for i in 0..slaves {
                 res.push(Some(Geometry {
-                    x: slave_x + screen.offset_x,
-                    y: (i as u16 - 1) * slave_height + screen.offset_y,
-                    width: screen.width - master_width - 2,
-                    height: slave_height - 2,
+                    x: slave_area.offset_x,
+                    y: i as u16 * slave_height + slave_area.offset_y,
+                    width: slave_area.width.saturating_sub(2 * border_width),
+                    height: slave_height.saturating_sub(2 * border_width),
                 }));
             }
         }
-        res
+        inner_gap(res, self.gap)
     }
 
     fn right_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            if !self.inverted && max >= 1 {
-                Some(1)
+        let masters = num_masters(self.master_count, max + 1);
+        if index < masters {
+            if !self.inverted && max >= masters {
+                Some(masters)
             } else {
                 None
             }
@@ -432,9 +515,10 @@ impl Layout for VStack {
     }
 
     fn left_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            if self.inverted && max >= 1 {
-                Some(1)
+        let masters = num_masters(self.master_count, max + 1);
+        if index < masters {
+            if self.inverted && max >= masters {
+                Some(masters)
             } else {
                 None
             }
@@ -447,8 +531,9 @@ impl Layout for VStack {
         }
     }
 
-    fn top_window(&self, index: usize, _: usize) -> Option<usize> {
-        if index <= 1 {
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        let masters = num_masters(self.master_count, max + 1);
+        if index == 0 || index == masters {
             None
         } else {
             Some(index - 1)
@@ -456,17 +541,25 @@ impl Layout for VStack {
     }
 
     fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
-        if index == 0 {
-            Some(max)
-        } else if index < max {
+        let masters = num_masters(self.master_count, max + 1);
+        if index + 1 < masters || (index >= masters && index < max) {
             Some(index + 1)
+        } else if index + 1 == masters && max > index {
+            // the last master window has the stack's end below it
+            Some(max)
         } else {
             None
         }
     }
 
+    fn name(&self) -> &'static str { "vstack" }
+
     fn new_window_as_master(&self) -> bool { false }
 
+    fn master_factor(&self) -> Option<u8> {
+        Some(self.master_factor)
+    }
+
     fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
         match msg {
             LayoutMessage::MasterFactorAbs(mf) =>
@@ -479,6 +572,31 @@ impl Layout for VStack {
                 },
             LayoutMessage::FixedAbs(f) => self.fixed = f,
             LayoutMessage::FixedRel => self.fixed = !self.fixed,
+            LayoutMessage::AspectRatioAbs(ratio) => self.aspect_ratio = ratio,
+            LayoutMessage::GapAbs(gap) => self.gap = gap,
+            LayoutMessage::GapRel(gap) =>
+                self.gap = if gap < 0 {
+                    self.gap.saturating_sub(gap.abs() as u16)
+                } else {
+                    self.gap.saturating_add(gap.abs() as u16)
+                },
+            LayoutMessage::MasterCountAbs(count) =>
+                self.master_count = cmp::max(1, count),
+            LayoutMessage::MasterCountRel(count) =>
+                self.master_count = if count < 0 {
+                    cmp::max(1, self.master_count
+                             .saturating_sub(count.abs() as usize))
+                } else {
+                    self.master_count.saturating_add(count.abs() as usize)
+                },
+            LayoutMessage::Reset => {
+                let default = VStack::default();
+                self.master_factor = default.master_factor;
+                self.fixed = default.fixed;
+                self.gap = default.gap;
+                self.master_count = default.master_count;
+                self.aspect_ratio = default.aspect_ratio;
+            },
             _ => return false,
         };
         true
@@ -0,0 +1,159 @@
+use std::cmp;
+
+use wm::layout::*;
+
+/// Tabbed layout.
+///
+/// ```plaintext
+/// +--+--+--+
+/// |A*|B |C | A, B, C: tabs of all windows
+/// +--+--+--+
+/// |        |
+/// |   A    | A: focused window
+/// |        |
+/// +--------+
+/// ```
+/// Shows one window at a time below a strip left free for tabs, which an
+/// external bar can draw using the geometries returned by `tabs`, as
+/// reported by the `query tabs` IPC command. Like
+/// `Monocle`, the focused window becomes the master, directional navigation
+/// cycles through all windows.
+pub struct Tabbed {
+    /// height of the tab strip at the top of the screen
+    pub tab_height: u16,
+}
+
+impl Default for Tabbed {
+    fn default() -> Tabbed {
+        Tabbed { tab_height: 20 }
+    }
+}
+
+impl Tabbed {
+    /// Get the height of the tab strip, leaving some space for the window.
+    fn strip_height(&self, screen: &ScreenSize) -> u16 {
+        cmp::min(self.tab_height, screen.height / 2)
+    }
+}
+
+impl Layout for Tabbed {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        let mut res = Vec::with_capacity(num_windows);
+        if num_windows == 0 {
+            return res;
+        }
+        // master window is shown below the tab strip
+        let strip = self.strip_height(screen);
+        res.push(Some(Geometry {
+            x: screen.offset_x,
+            y: screen.offset_y + strip,
+            width: screen.width.saturating_sub(2 * border_width),
+            height: (screen.height - strip).saturating_sub(2 * border_width),
+        }));
+        // all other windows are hidden
+        for _ in 1..num_windows {
+            res.push(None);
+        }
+        res
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + 1) % (max + 1))
+        } else {
+            None
+        }
+    }
+
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        if max > 0 {
+            Some((index + max) % (max + 1))
+        } else {
+            None
+        }
+    }
+
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.left_window(index, max)
+    }
+
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        self.right_window(index, max)
+    }
+
+    fn name(&self) -> &'static str { "tabbed" }
+
+    fn new_window_as_master(&self) -> bool { true }
+
+    fn tabs(&self, num_windows: usize, screen: &ScreenSize)
+        -> Vec<Geometry> {
+        let mut res = Vec::with_capacity(num_windows);
+        if num_windows == 0 {
+            return res;
+        }
+        // the tab strip is split evenly, the first tab belonging to the
+        // shown window
+        let tab_width = screen.width / num_windows as u16;
+        for i in 0..num_windows {
+            let x = i as u16 * tab_width;
+            // the last tab takes up the remaining space
+            let width = if i == num_windows - 1 {
+                screen.width - x
+            } else {
+                tab_width
+            };
+            res.push(Geometry {
+                x: screen.offset_x + x,
+                y: screen.offset_y,
+                width: width,
+                height: self.strip_height(screen),
+            });
+        }
+        res
+    }
+
+    fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
+        match msg {
+            LayoutMessage::YOffAbs(y) => self.tab_height = y,
+            LayoutMessage::YOffRel(y) =>
+                self.tab_height = if y < 0 {
+                    self.tab_height.saturating_sub(y.abs() as u16)
+                } else { self.tab_height.saturating_add(y.abs() as u16) },
+            LayoutMessage::Reset => *self = Tabbed::default(),
+            _ => return false,
+        };
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wm::layout::*;
+    use wm::layout::stack::VStack;
+    use wm::layout::transform::Reflected;
+    use super::Tabbed;
+
+    fn screen() -> ScreenSize {
+        ScreenSize { offset_x: 10, offset_y: 20, width: 100, height: 60 }
+    }
+
+    #[test]
+    fn tabs_split_strip() {
+        let tabs = Tabbed::default().tabs(3, &screen());
+        assert_eq!(tabs, vec![
+            Geometry { x: 10, y: 20, width: 33, height: 20 },
+            Geometry { x: 43, y: 20, width: 33, height: 20 },
+            Geometry { x: 76, y: 20, width: 34, height: 20 },
+        ]);
+        assert_eq!(Tabbed::default().tabs(0, &screen()), vec![]);
+    }
+
+    #[test]
+    fn tabs_of_other_layouts() {
+        assert_eq!(VStack::default().tabs(3, &screen()), vec![]);
+        let reflected = Reflected::new(Tabbed::default(), false, true);
+        assert_eq!(reflected.tabs(1, &screen()),
+                   vec![Geometry { x: 10, y: 60, width: 100, height: 20 }]);
+    }
+}
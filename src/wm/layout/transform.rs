@@ -0,0 +1,186 @@
+use wm::layout::*;
+
+/// A layout mirrored horizontally, vertically or both.
+///
+/// Wraps any other layout and mirrors the geometries it computes across the
+/// center of the screen, swapping directional navigation accordingly. For
+/// instance, a horizontally reflected `VStack` has its master on the right.
+/// Transforms can be composed by reflecting a reflected layout.
+pub struct Reflected<L: Layout> {
+    /// the layout being mirrored
+    pub inner: L,
+    /// mirror geometries horizontally, swapping left and right?
+    pub horizontal: bool,
+    /// mirror geometries vertically, swapping top and bottom?
+    pub vertical: bool,
+}
+
+impl<L: Layout> Reflected<L> {
+    /// Mirror a layout horizontally, vertically or both.
+    pub fn new(inner: L, horizontal: bool, vertical: bool) -> Reflected<L> {
+        Reflected {
+            inner: inner,
+            horizontal: horizontal,
+            vertical: vertical,
+        }
+    }
+
+    /// Mirror a geometry computed by the inner layout.
+    ///
+    /// Window borders are accounted for, so that the mirrored window
+    /// occupies exactly the space of the original one on the other side.
+    fn reflect(&self, geometry: Geometry, screen: &ScreenSize,
+               border_width: u16) -> Geometry {
+        let mut res = geometry;
+        if self.horizontal {
+            res.x = (2 * screen.offset_x + screen.width)
+                .saturating_sub(res.x + res.width + 2 * border_width);
+        }
+        if self.vertical {
+            res.y = (2 * screen.offset_y + screen.height)
+                .saturating_sub(res.y + res.height + 2 * border_width);
+        }
+        res
+    }
+}
+
+impl<L: Layout> Layout for Reflected<L> {
+    fn arrange(&self, num_windows: usize, screen: &ScreenSize,
+               border_width: u16) -> Vec<Option<Geometry>> {
+        self.inner
+            .arrange(num_windows, screen, border_width)
+            .into_iter()
+            .map(|g| g.map(|g| self.reflect(g, screen, border_width)))
+            .collect()
+    }
+
+    fn arrange_split(&self, splits: &[Option<Split>], screen: &ScreenSize,
+                     border_width: u16) -> Vec<Option<Geometry>> {
+        self.inner
+            .arrange_split(splits, screen, border_width)
+            .into_iter()
+            .map(|g| g.map(|g| self.reflect(g, screen, border_width)))
+            .collect()
+    }
+
+    fn right_window(&self, index: usize, max: usize) -> Option<usize> {
+        if self.horizontal {
+            self.inner.left_window(index, max)
+        } else {
+            self.inner.right_window(index, max)
+        }
+    }
+
+    fn left_window(&self, index: usize, max: usize) -> Option<usize> {
+        if self.horizontal {
+            self.inner.right_window(index, max)
+        } else {
+            self.inner.left_window(index, max)
+        }
+    }
+
+    fn top_window(&self, index: usize, max: usize) -> Option<usize> {
+        if self.vertical {
+            self.inner.bottom_window(index, max)
+        } else {
+            self.inner.top_window(index, max)
+        }
+    }
+
+    fn bottom_window(&self, index: usize, max: usize) -> Option<usize> {
+        if self.vertical {
+            self.inner.top_window(index, max)
+        } else {
+            self.inner.bottom_window(index, max)
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn new_window_as_master(&self) -> bool {
+        self.inner.new_window_as_master()
+    }
+
+    fn master_factor(&self) -> Option<u8> {
+        self.inner.master_factor()
+    }
+
+    fn tabs(&self, num_windows: usize, screen: &ScreenSize)
+        -> Vec<Geometry> {
+        self.inner
+            .tabs(num_windows, screen)
+            .into_iter()
+            .map(|g| self.reflect(g, screen, 0))
+            .collect()
+    }
+
+    fn on_client_added(&mut self, index: usize, focused: usize) {
+        self.inner.on_client_added(index, focused);
+    }
+
+    fn on_client_removed(&mut self, index: usize) {
+        self.inner.on_client_removed(index);
+    }
+
+    fn edit_layout(&mut self, msg: LayoutMessage) -> bool {
+        self.inner.edit_layout(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wm::layout::*;
+    use wm::layout::grid::Grid;
+    use wm::layout::stack::VStack;
+    use super::Reflected;
+
+    fn screen() -> ScreenSize {
+        ScreenSize { offset_x: 0, offset_y: 0, width: 100, height: 60 }
+    }
+
+    #[test]
+    fn reflect_horizontally() {
+        let layout = Reflected::new(Grid::default(), true, false);
+        for num_windows in 1..7 {
+            let orig = Grid::default().arrange(num_windows, &screen(), 0);
+            let res = layout.arrange(num_windows, &screen(), 0);
+            for (o, r) in orig.iter().zip(res.iter()) {
+                let (o, r) = (o.clone().unwrap(), r.clone().unwrap());
+                assert_eq!(r.x, screen().width - o.x - o.width);
+                assert_eq!((r.y, r.width, r.height),
+                           (o.y, o.width, o.height));
+            }
+        }
+    }
+
+    #[test]
+    fn reflect_both_with_borders() {
+        let screen = ScreenSize {
+            offset_x: 10,
+            offset_y: 20,
+            width: 100,
+            height: 60,
+        };
+        let layout = Reflected::new(VStack::default(), true, true);
+        // the master window ends up in the bottom right corner
+        assert_eq!(layout.arrange(1, &screen, 1)[0],
+                   Some(Geometry { x: 10, y: 20, width: 98, height: 58 }));
+        assert_eq!(layout.arrange(2, &screen, 1)[0],
+                   Some(Geometry { x: 60, y: 20, width: 48, height: 58 }));
+        assert_eq!(layout.arrange(2, &screen, 1)[1],
+                   Some(Geometry { x: 10, y: 20, width: 48, height: 58 }));
+    }
+
+    #[test]
+    fn swap_navigation() {
+        let layout = Reflected::new(VStack::default(), true, false);
+        assert_eq!(layout.left_window(0, 1),
+                   VStack::default().right_window(0, 1));
+        assert_eq!(layout.right_window(1, 1),
+                   VStack::default().left_window(1, 1));
+        assert_eq!(layout.top_window(1, 2),
+                   VStack::default().top_window(1, 2));
+    }
+}
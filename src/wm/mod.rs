@@ -9,6 +9,9 @@ pub mod util;
 pub mod client;
 pub mod config;
 pub mod err;
+pub mod ipc;
 pub mod kbd;
 pub mod layout;
+pub mod output;
+pub mod timer;
 pub mod window_system;
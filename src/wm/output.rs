@@ -0,0 +1,189 @@
+use std::slice;
+
+use xcb::base;
+use xcb::randr;
+use xcb::xproto;
+
+use wm::client::TagStack;
+use wm::layout::ScreenSize;
+
+/// An output, that is, a monitor showing a part of the root window.
+///
+/// Each output displays a tagset of it's own, keeping a separate history.
+/// Clients visible on the tagsets of multiple outputs are only shown on the
+/// first of them.
+pub struct Output {
+    /// the output's name as reported by RandR, like `HDMI1`
+    pub name: String,
+    /// the area of the root window covered by the output
+    pub area: ScreenSize,
+    /// tagsets shown on the output and their display history
+    pub tag_stack: TagStack,
+}
+
+impl Output {
+    /// Setup a new output with a name and an area, without any tagsets.
+    pub fn new(name: String, area: ScreenSize) -> Output {
+        Output {
+            name: name,
+            area: area,
+            tag_stack: TagStack::new(),
+        }
+    }
+}
+
+/// A nonempty set of outputs, one of which is the current output.
+///
+/// The current output is the one user commands act upon, and new clients
+/// are placed on.
+pub struct OutputSet {
+    /// all outputs, ordered by position
+    outputs: Vec<Output>,
+    /// index of the current output
+    current: usize,
+}
+
+impl OutputSet {
+    /// Setup a set of outputs, the first of which is the current output.
+    ///
+    /// Panics if `outputs` is empty.
+    pub fn new(outputs: Vec<Output>) -> OutputSet {
+        assert!(!outputs.is_empty(), "an output set can't be empty");
+        OutputSet {
+            outputs: outputs,
+            current: 0,
+        }
+    }
+
+    /// Take the outputs out of the set.
+    pub fn into_outputs(self) -> Vec<Output> {
+        self.outputs
+    }
+
+    /// Get the index of an output by name.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.outputs.iter().position(|output| output.name == name)
+    }
+
+    /// Get the number of outputs.
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Get the output at an index.
+    pub fn get(&self, index: usize) -> Option<&Output> {
+        self.outputs.get(index)
+    }
+
+    /// Get the output at an index, mutably.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Output> {
+        self.outputs.get_mut(index)
+    }
+
+    /// Get all outputs, mutably.
+    pub fn iter_mut<'a>(&'a mut self) -> slice::IterMut<'a, Output> {
+        self.outputs.iter_mut()
+    }
+
+    /// Get the index of the current output.
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// Get the current output.
+    pub fn current(&self) -> &Output {
+        &self.outputs[self.current]
+    }
+
+    /// Get the current output, mutably.
+    pub fn current_mut(&mut self) -> &mut Output {
+        &mut self.outputs[self.current]
+    }
+
+    /// Get the index of the output containing a point on the root window,
+    /// if any.
+    pub fn index_at(&self, x: i16, y: i16) -> Option<usize> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let (x, y) = (x as u32, y as u32);
+        self.outputs.iter().position(|output| {
+            let area = &output.area;
+            x >= area.offset_x as u32 &&
+                x < area.offset_x as u32 + area.width as u32 &&
+                y >= area.offset_y as u32 &&
+                y < area.offset_y as u32 + area.height as u32
+        })
+    }
+
+    /// Get the index of the output following the current one, wrapping
+    /// around.
+    pub fn next_index(&self) -> usize {
+        (self.current + 1) % self.outputs.len()
+    }
+
+    /// Make the output at an index the current output, returning whether
+    /// changes have been made.
+    pub fn focus(&mut self, index: usize) -> bool {
+        if index < self.outputs.len() && index != self.current {
+            self.current = index;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Query the names and areas of all active outputs using RandR.
+///
+/// Outputs mirroring others are only reported once. The result is ordered
+/// by position, from left to right. If RandR is unavailable, an empty vector
+/// is returned.
+pub fn query_outputs(con: &base::Connection, root: xproto::Window)
+    -> Vec<(String, ScreenSize)> {
+    let resources =
+        match randr::get_screen_resources_current(con, root).get_reply() {
+            Ok(resources) => resources,
+            Err(_) => {
+                error!("could not query outputs");
+                return Vec::new();
+            },
+        };
+    let timestamp = resources.config_timestamp();
+    let cookies: Vec<_> = resources
+        .crtcs()
+        .iter()
+        .map(|crtc| randr::get_crtc_info(con, *crtc, timestamp))
+        .collect();
+    let mut res: Vec<(String, ScreenSize)> = Vec::new();
+    for cookie in cookies {
+        let crtc = match cookie.get_reply() {
+            Ok(crtc) => crtc,
+            Err(_) => continue,
+        };
+        // disabled crtcs don't have a size
+        if crtc.width() == 0 || crtc.height() == 0 {
+            continue;
+        }
+        let area = ScreenSize {
+            offset_x: crtc.x() as u16,
+            offset_y: crtc.y() as u16,
+            width: crtc.width(),
+            height: crtc.height(),
+        };
+        if res.iter().any(|&(_, ref a)| *a == area) {
+            continue;
+        }
+        let name = crtc
+            .outputs()
+            .first()
+            .and_then(|output| randr::get_output_info(con, *output, timestamp)
+                .get_reply()
+                .ok())
+            .map(|info| String::from_utf8_lossy(info.name()).into_owned())
+            .unwrap_or_default();
+        res.push((name, area));
+    }
+    res.sort_by_key(|&(_, ref a)| (a.offset_x, a.offset_y));
+    res
+}
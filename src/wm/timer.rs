@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+
+/// A queue of values becoming due at specific points in time.
+///
+/// Used to defer actions in the event loop: the loop waits at most until the
+/// next value becomes due, and handles all due values afterwards. The current
+/// time is passed in explicitly by the caller.
+pub struct TimerQueue<T> {
+    /// scheduled values and the points in time they become due at
+    timers: Vec<(Instant, T)>,
+}
+
+impl<T> TimerQueue<T> {
+    /// Create an empty timer queue.
+    pub fn new() -> TimerQueue<T> {
+        TimerQueue {
+            timers: Vec::new(),
+        }
+    }
+
+    /// Schedule a value to become due after a delay.
+    pub fn schedule(&mut self, now: Instant, delay: Duration, value: T) {
+        self.timers.push((now + delay, value));
+    }
+
+    /// Remove all scheduled values matching a predicate.
+    pub fn cancel<F>(&mut self, pred: F) where F: Fn(&T) -> bool {
+        self.timers.retain(|&(_, ref value)| !pred(value));
+    }
+
+    /// Get the time until the next value becomes due, if any are scheduled.
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.timers
+            .iter()
+            .map(|&(due, _)| if due > now {
+                due - now
+            } else {
+                Duration::from_millis(0)
+            })
+            .min()
+    }
+
+    /// Remove and return all values that are due, in order of due time.
+    pub fn pop_due(&mut self, now: Instant) -> Vec<T> {
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < self.timers.len() {
+            if self.timers[i].0 <= now {
+                due.push(self.timers.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        due.sort_by_key(|&(time, _)| time);
+        due.into_iter().map(|(_, value)| value).collect()
+    }
+}
+
+/// Convert a duration to milliseconds, rounding up.
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 +
+        (duration.subsec_nanos() as u64 + 999999) / 1000000
+}
+
+/// A clock advancing in fixed ticks, starting at an arbitrary instant.
+#[cfg(test)]
+pub struct MockClock {
+    start: Instant,
+    tick: Duration,
+    pub ticks: u32,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(tick: Duration) -> MockClock {
+        MockClock { start: Instant::now(), tick: tick, ticks: 0 }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.start + self.tick * self.ticks
+    }
+
+    pub fn advance(&mut self) -> Instant {
+        self.ticks += 1;
+        self.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_after_expected_ticks() {
+        let mut clock = MockClock::new(Duration::from_millis(10));
+        let mut queue = TimerQueue::new();
+        queue.schedule(clock.now(), Duration::from_millis(35), 'a');
+        queue.schedule(clock.now(), Duration::from_millis(20), 'b');
+        let mut fired = Vec::new();
+        while clock.ticks < 10 {
+            let now = clock.advance();
+            for value in queue.pop_due(now) {
+                fired.push((clock.ticks, value));
+            }
+        }
+        assert_eq!(fired, vec![(2, 'b'), (4, 'a')]);
+        assert_eq!(queue.timeout(clock.now()), None);
+    }
+
+    #[test]
+    fn timeout_until_next_due() {
+        let mut clock = MockClock::new(Duration::from_millis(10));
+        let mut queue = TimerQueue::new();
+        queue.schedule(clock.now(), Duration::from_millis(25), 1);
+        queue.schedule(clock.now(), Duration::from_millis(40), 2);
+        assert_eq!(queue.timeout(clock.now()),
+                   Some(Duration::from_millis(25)));
+        queue.cancel(|v| *v == 1);
+        let now = clock.advance();
+        assert_eq!(queue.timeout(now), Some(Duration::from_millis(30)));
+        for _ in 0..5 {
+            clock.advance();
+        }
+        // overdue values don't wait at all
+        assert_eq!(queue.timeout(clock.now()),
+                   Some(Duration::from_millis(0)));
+        assert_eq!(queue.pop_due(clock.now()), vec![2]);
+    }
+
+    #[test]
+    fn millis_round_up() {
+        assert_eq!(millis(Duration::new(1, 0)), 1000);
+        assert_eq!(millis(Duration::new(0, 1)), 1);
+        assert_eq!(millis(Duration::new(2, 1000001)), 2002);
+    }
+}
@@ -101,42 +101,24 @@ macro_rules! push_tagset {
 #[macro_export]
 macro_rules! toggle_tag {
     ($tag:expr;; $print:expr) => {
-        |c, s| s
-            .current()
-            .and_then(|t| c.get_focused_window(&t.tags))
-            .and_then(|w| c.update_client(w, |mut cl| {
-                cl.toggle_tag($tag);
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd = c.toggle_tag_on_focused(&tags, $tag);
                 println!("{}", $print(c, s));
-                if s.current()
-                    .unwrap()
-                    .tags
-                    .iter()
-                    .find(|t| **t == $tag).is_some() {
-                    WmCommand::Redraw
-                } else {
-                    WmCommand::NoCommand
-                }
-            }))
-            .unwrap_or(WmCommand::NoCommand)
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
     };
     ($tag:expr $(; $print:expr)*) => {
-        |c, s| s
-            .current()
-            .and_then(|t| c.get_focused_window(&t.tags))
-            .and_then(|w| c.update_client(w, |mut cl| {
-                cl.toggle_tag($tag);
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd = c.toggle_tag_on_focused(&tags, $tag);
                 $( println!("{}", $print); )*
-                if s.current()
-                    .unwrap()
-                    .tags
-                    .iter()
-                    .find(|t| **t == $tag).is_some() {
-                    WmCommand::Redraw
-                } else {
-                    WmCommand::NoCommand
-                }
-            }))
-            .unwrap_or(WmCommand::NoCommand)
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
     }
 }
 
@@ -174,6 +156,36 @@ macro_rules! toggle_show_tag {
     }
 }
 
+/// Exchange the clients on two tags.
+///
+/// Returns a closure for use with `bind!`.
+///
+/// # Usage
+/// The `swap_tags!` macro expects two tags whose clients are to be
+/// exchanged. Clients on both tags stay where they are.
+///
+/// As always, the last parameter(s) specify objects to be printed after
+/// completion of the action.
+#[macro_export]
+macro_rules! swap_tags {
+    ($a:expr, $b:expr;; $print:expr) => {
+        |c, s| if c.swap_tags(&$a, &$b) {
+            println!("{}", $print(c, s));
+            WmCommand::Redraw
+        } else {
+            WmCommand::NoCommand
+        }
+    };
+    ($a:expr, $b:expr $(; $print:expr)*) => {
+        |c, _| if c.swap_tags(&$a, &$b) {
+            $( println!("{}", $print); )*
+            WmCommand::Redraw
+        } else {
+            WmCommand::NoCommand
+        }
+    }
+}
+
 /// Move a client to a tag.
 ///
 /// Returns a closure for use with `bind!`.
@@ -187,28 +199,61 @@ macro_rules! toggle_show_tag {
 #[macro_export]
 macro_rules! move_to_tag {
     ($($tag:expr),*;; $print:expr) => {
-        |c, s| s
-            .current()
-            .and_then(|t| c.get_focused_window(&t.tags))
-            .and_then(|w| c.update_client(w, |mut cl| {
-                cl.set_tags(&[$($tag),*]);
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd = c.move_focused_to_tags(&tags, &[$($tag),*]);
                 println!("{}", $print(c, s));
-                // TODO: optimize for cases, where current tags are present
-                WmCommand::Redraw
-            }))
-            .unwrap_or(WmCommand::NoCommand)
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
     };
     ($($tag:expr),* $(; $print:expr)*) => {
-        |c, s| s
-            .current()
-            .and_then(|t| c.get_focused_window(&t.tags))
-            .and_then(|w| c.update_client(w, |mut cl| {
-                cl.set_tags(&[$($tag),*]);
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd = c.move_focused_to_tags(&tags, &[$($tag),*]);
                 $( println!("{}", $print); )*
-                // TODO: optimize for cases, where current tags are present
-                WmCommand::Redraw
-            }))
-            .unwrap_or(WmCommand::NoCommand)
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
+    }
+}
+
+/// Move a client to a tag and make it the master there.
+///
+/// Returns a closure for use with `bind!`.
+///
+/// # Usage
+/// The `move_to_tag_master!` macro expects one or more tags for the current
+/// client to be moved to. On the set of exactly these tags, the client
+/// becomes the master window.
+///
+/// As always, the last parameter(s) specify objects to be printed after
+/// completion of the action.
+#[macro_export]
+macro_rules! move_to_tag_master {
+    ($($tag:expr),*;; $print:expr) => {
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd =
+                    c.move_focused_to_tags_as_master(&tags, &[$($tag),*]);
+                println!("{}", $print(c, s));
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
+    };
+    ($($tag:expr),* $(; $print:expr)*) => {
+        |c, s| match s.current().map(|t| t.tags.clone()) {
+            Some(tags) => {
+                let cmd =
+                    c.move_focused_to_tags_as_master(&tags, &[$($tag),*]);
+                $( println!("{}", $print); )*
+                cmd
+            },
+            None => WmCommand::NoCommand,
+        }
     }
 }
 
@@ -308,7 +353,7 @@ macro_rules! edit_layout {
         |_, s| s
             .current_mut()
             .map_or(WmCommand::NoCommand, |t| {
-                if t.layout.edit_layout_retry(vec![$($cmd,)*]) {
+                if t.edit_layout(vec![$($cmd,)*]) {
                     println!("{}", $print(c, s));
                     WmCommand::Redraw
                 } else {
@@ -320,7 +365,7 @@ macro_rules! edit_layout {
         |_, s| s
             .current_mut()
             .map_or(WmCommand::NoCommand, |t| {
-                if t.layout.edit_layout_retry(vec![$($cmd,)*]) {
+                if t.edit_layout(vec![$($cmd,)*]) {
                     $( println!("{}", $print); )*
                     WmCommand::Redraw
                 } else {
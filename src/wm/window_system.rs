@@ -1,29 +1,77 @@
+use libc;
 use libc::c_char;
 
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CStr;
-use std::process::exit;
+use std::io;
+use std::mem;
+use std::os::unix::process::CommandExt;
+use std::process::{exit, Command};
 use std::str;
+use std::time::{Duration, Instant};
 
 use xcb::base;
+use xcb::randr;
 use xcb::xkb;
 use xcb::xproto;
+use xcb::ffi::base::xcb_get_file_descriptor;
 use xcb::ffi::xcb_client_message_data_t;
 
 use wm::client::*;
-use wm::config::{Tag,Mode};
+use wm::config::{Tag, Mode, generate_matching, generate_rules};
 use wm::err::*;
+use wm::ipc::*;
 use wm::kbd::*;
 use wm::layout::*;
+use wm::output::*;
+use wm::timer::{millis, TimerQueue};
 
 /// Atoms we register with the X server for partial EWMH compliance.
-static ATOM_VEC: [&'static str; 9] =
+static ATOM_VEC: [&'static str; 33] =
     ["WM_PROTOCOLS", "WM_DELETE_WINDOW", "WM_STATE",
      "WM_TAKE_FOCUS", "_NET_WM_TAKE_FOCUS", "_NET_WM_NAME", "_NET_WM_CLASS",
-     "_NET_WM_WINDOW_TYPE", "_NET_WM_WINDOW_TYPE_DOCK"];
+     "_NET_WM_WINDOW_TYPE", "_NET_WM_WINDOW_TYPE_DOCK",
+     "_NET_WM_WINDOW_TYPE_DESKTOP", "_NET_WM_WINDOW_TYPE_DIALOG",
+     "_NET_WM_WINDOW_TYPE_UTILITY", "_NET_WM_WINDOW_TYPE_SPLASH",
+     "_NET_WM_WINDOW_TYPE_NOTIFICATION", "_NET_WM_WINDOW_TYPE_TOOLTIP",
+     "_NET_WM_STRUT_PARTIAL", "_NET_WM_STRUT",
+     "_NET_WM_ALLOWED_ACTIONS", "_NET_WM_ACTION_CLOSE",
+     "_NET_WM_ACTION_MOVE", "_NET_WM_ACTION_RESIZE", "_NET_WM_STATE",
+     "_NET_WM_STATE_MAXIMIZED_VERT", "_NET_WM_STATE_MAXIMIZED_HORZ",
+     "_NET_WM_STATE_FULLSCREEN", "_NET_WM_PID", "_NET_WM_USER_TIME",
+     "_NET_CLIENT_LIST",
+     "_NET_ACTIVE_WINDOW", "_NET_SUPPORTED", "_NET_SUPPORTING_WM_CHECK",
+     "_NET_WM_DESKTOP", "UTF8_STRING"];
 
-/// Association vector type for atoms and their names.
-type AtomList<'a> = Vec<(xproto::Atom, &'a str)>;
+/// The EWMH hints implemented, as listed in `_NET_SUPPORTED`.
+///
+/// Atoms only read from clients, like `_NET_WM_PID`, aren't hints the window
+/// manager supports and are left out.
+static SUPPORTED_HINTS: [&'static str; 24] =
+    ["_NET_SUPPORTED", "_NET_SUPPORTING_WM_CHECK", "_NET_CLIENT_LIST",
+     "_NET_ACTIVE_WINDOW", "_NET_WM_NAME", "_NET_WM_WINDOW_TYPE",
+     "_NET_WM_WINDOW_TYPE_DOCK", "_NET_WM_WINDOW_TYPE_DESKTOP",
+     "_NET_WM_WINDOW_TYPE_DIALOG", "_NET_WM_WINDOW_TYPE_UTILITY",
+     "_NET_WM_WINDOW_TYPE_SPLASH", "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+     "_NET_WM_WINDOW_TYPE_TOOLTIP", "_NET_WM_STRUT_PARTIAL", "_NET_WM_STRUT",
+     "_NET_WM_ALLOWED_ACTIONS", "_NET_WM_ACTION_CLOSE",
+     "_NET_WM_ACTION_MOVE", "_NET_WM_ACTION_RESIZE", "_NET_WM_STATE",
+     "_NET_WM_STATE_MAXIMIZED_VERT", "_NET_WM_STATE_MAXIMIZED_HORZ",
+     "_NET_WM_STATE_FULLSCREEN", "_NET_WM_DESKTOP"];
+
+/// The `WM_STATE` of a window that is shown.
+const NORMAL_STATE: u32 = 1;
+
+/// The `WM_STATE` of a window that is iconified.
+const ICONIC_STATE: u32 = 3;
+
+/// Association map type for atoms and their names.
+type AtomList<'a> = HashMap<&'a str, xproto::Atom>;
+
+/// Space reserved by a dock window at the left, right, top and bottom edges
+/// of the root window, in that order.
+type Strut = (u16, u16, u16, u16);
 
 /// Closure type of a callback function determining client placement on
 /// creation.
@@ -42,6 +90,31 @@ pub enum WmCommand {
     Focus,
     /// kill the client associated with the window
     Kill(xproto::Window),
+    /// resize the focused floating client by moving an edge by a percentage
+    /// of the screen size
+    ResizeFloating(Direction, i8),
+    /// toggle whether space reserved by dock windows is honored
+    ToggleStruts,
+    /// view the tagset a number of steps away from the current one, in the
+    /// order of their indices
+    CycleTagset(isize),
+    /// make the next output the current one
+    FocusNextOutput,
+    /// move the focused client to the tagset shown on the next output
+    SendToNextOutput,
+    /// temporarily enlarge the focused tiled client, or restore it
+    ToggleZoom,
+    /// make the focused client the scratchpad, shown regardless of tags
+    MarkScratchpad,
+    /// show or hide the scratchpad above the current tagset
+    ToggleScratchpad,
+    /// view a tagset showing the most recently urgent client and focus it
+    FocusUrgent,
+    /// rebuild the client set from the window tree, to recover from
+    /// inconsistent state
+    Rebuild,
+    /// run a program with arguments in the background
+    Spawn(Vec<String>),
     /// switch keyboard mode
     ModeSwitch(Mode),
     /// quit window manager
@@ -50,6 +123,169 @@ pub enum WmCommand {
     NoCommand,
 }
 
+/// Policy deciding whether newly mapped windows receive focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FocusOnMap {
+    /// new windows are always focused on the tagsets they are shown on
+    Always,
+    /// new windows are only focused if they are visible on the current
+    /// tagset, otherwise they are marked urgent to be visited later
+    SameTagOnly,
+    /// new windows never receive focus
+    Never,
+    /// new windows are only focused if they have the urgency hint set
+    SmartUrgentOnly,
+}
+
+impl FocusOnMap {
+    /// Decide what happens to the focus when a client gets mapped, given
+    /// whether it is shown on the current tagset.
+    ///
+    /// Unless new windows are always focused, clients stating that they
+    /// were mapped without user activity by a `_NET_WM_USER_TIME` of zero
+    /// don't receive focus.
+    fn new_client_focus(&self, client: &Client, shown: bool)
+        -> NewClientFocus {
+        let user_time = client.props().user_time;
+        let focus = match *self {
+            FocusOnMap::Always => true,
+            FocusOnMap::SameTagOnly => user_time != Some(0) && shown,
+            FocusOnMap::Never => false,
+            FocusOnMap::SmartUrgentOnly =>
+                user_time != Some(0) && client.is_urgent(),
+        };
+        if focus {
+            NewClientFocus::Focus
+        } else if shown {
+            NewClientFocus::KeepFocus
+        } else if *self == FocusOnMap::SameTagOnly {
+            NewClientFocus::MarkUrgent
+        } else {
+            NewClientFocus::Focus
+        }
+    }
+}
+
+/// What happens to the focus when a client gets mapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum NewClientFocus {
+    /// the client is focused on the tagsets it is shown on
+    Focus,
+    /// the focus stays on the client focused on the current tagset
+    KeepFocus,
+    /// the client is marked urgent, to be visited later
+    MarkUrgent,
+}
+
+/// Enumeration type of actions deferred using the timer queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Timer {
+    /// focus the window the pointer has been resting on
+    FocusDwell(xproto::Window),
+    /// kill a client that didn't close after being asked to
+    KillClient(xproto::Window),
+    /// set the master factor of the current layout, animating a change
+    MasterFactor(u8),
+    /// drop the client of a window if it didn't show up after mapping it
+    MapCheck(xproto::Window),
+    /// there has been no input for the configured idle timeout
+    Idle,
+    /// query the outputs after the screen configuration changed
+    UpdateOutputs,
+}
+
+impl Timer {
+    /// Check whether the timer concerns a window, so that it becomes
+    /// obsolete as soon as the window is destroyed.
+    fn concerns(&self, window: xproto::Window) -> bool {
+        match *self {
+            Timer::FocusDwell(w) | Timer::KillClient(w) |
+            Timer::MapCheck(w) => w == window,
+            _ => false,
+        }
+    }
+}
+
+/// The way a client is closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CloseAction {
+    /// ask the client to close, killing it if it doesn't in time
+    Delete,
+    /// kill the client right away
+    Kill,
+}
+
+impl CloseAction {
+    /// Decide how to close a client supporting a set of protocols, given
+    /// the atom of `WM_DELETE_WINDOW`.
+    ///
+    /// Only clients listing the protocol in `WM_PROTOCOLS` get asked.
+    fn for_protocols(protocols: &[xproto::Atom],
+                     delete_window: xproto::Atom) -> CloseAction {
+        if protocols.contains(&delete_window) {
+            CloseAction::Delete
+        } else {
+            CloseAction::Kill
+        }
+    }
+}
+
+/// The reaction to a mouse button pressed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ClickAction {
+    /// start dragging the floating client under the pointer
+    Drag,
+    /// focus the client clicked and pass the click on to it
+    FocusAndReplay,
+    /// pass the click on to the client clicked
+    Replay,
+}
+
+impl ClickAction {
+    /// Decide how to react to a click on a window, given the root window and
+    /// the focused window, if any.
+    ///
+    /// Clicks on the root window come from the grab used for dragging, all
+    /// others from grabs on clients done for click-to-focus.
+    fn for_click(window: xproto::Window, root: xproto::Window,
+                 focused: Option<xproto::Window>) -> ClickAction {
+        if window == root {
+            ClickAction::Drag
+        } else if focused != Some(window) {
+            ClickAction::FocusAndReplay
+        } else {
+            ClickAction::Replay
+        }
+    }
+}
+
+/// Percentage of the screen's width and height a zoomed client takes.
+const ZOOM_FACTOR: u16 = 80;
+
+/// Time in milliseconds a newly mapped window has to show up before the
+/// corresponding client is dropped.
+const MAP_TIMEOUT: u64 = 1000;
+
+/// Time in milliseconds to wait for further screen configuration changes
+/// before querying the outputs.
+const OUTPUT_UPDATE_DELAY: u64 = 250;
+
+/// Minimal time in seconds between two programs started because the same tag
+/// became empty.
+const AUTOSTART_INTERVAL: u64 = 5;
+
+/// A floating client being moved or resized with the mouse.
+struct Drag {
+    /// the window dragged
+    window: xproto::Window,
+    /// resize the window instead of moving it?
+    resize: bool,
+    /// pointer position when the drag started
+    origin: (i16, i16),
+    /// geometry of the window when the drag started
+    geometry: Geometry,
+}
+
 /// Configuration information used by the window manager.
 #[derive(Clone)]
 pub struct WmConfig {
@@ -59,8 +295,288 @@ pub struct WmConfig {
     pub u_color: (u16, u16, u16),
     /// window border width
     pub border_width: u8,
+    /// only draw a border around the focused window
+    pub border_on_focus_only: bool,
+    /// don't draw a border around a window shown alone on an output
+    pub smart_borders: bool,
+    /// send the geometries of all windows on an output before waiting for
+    /// the server to process them, which is faster, but makes some servers
+    /// like Xephyr render redraws lazily
+    pub batch_configure: bool,
+    /// center tiled windows in their tiles instead of stretching them, if
+    /// their size hints ask for it
+    pub respect_size_hints: bool,
     /// screen parameters requested by user
     pub screen: ScreenSize,
+    /// focus and raise windows when they are clicked
+    pub click_to_focus: bool,
+    /// whether newly mapped windows receive focus
+    pub focus_on_map: FocusOnMap,
+    /// place new windows after the focused one instead of at the end of the
+    /// stack, unless the layout makes them the master window
+    pub new_window_after_focused: bool,
+    /// focus windows when the pointer enters them
+    pub focus_follows_mouse: bool,
+    /// time in milliseconds the pointer has to rest on a window before it
+    /// gets focused, if focus follows the mouse
+    pub focus_dwell: u64,
+    /// time in seconds a client has to close after being asked to, before
+    /// it gets killed
+    pub kill_grace: u64,
+    /// make the destination output the current one when sending a client
+    /// to another output
+    pub follow_sent_client: bool,
+    /// number of frames master factor changes are animated with, 0 to
+    /// disable animations
+    pub animation_steps: u8,
+    /// time in milliseconds between two frames of an animation
+    pub animation_interval: u64,
+    /// time in seconds without input after which the focused window's
+    /// border is drawn in the unfocused color, 0 to disable
+    pub idle_timeout: u64,
+    /// modifier mask held to move floating clients with the first and
+    /// resize them with the third mouse button, `NO_MODIFIER` to disable
+    pub drag_modifier: u8,
+    /// shell commands run when the last client on a tag is destroyed
+    pub autostart_on_empty: Vec<(Tag, String)>,
+    /// order in which tags are reported to other programs, tags not listed
+    /// are reported last
+    pub tag_order: Vec<Tag>,
+}
+
+impl WmConfig {
+    /// Sort a set of tags by the configured display order.
+    ///
+    /// Tags missing from the display order keep their relative order and
+    /// are placed after all others.
+    pub fn display_order(&self, tags: &[Tag]) -> Vec<Tag> {
+        let mut res = tags.to_vec();
+        res.sort_by_key(|tag| self
+            .tag_order
+            .iter()
+            .position(|t| t == tag)
+            .unwrap_or(self.tag_order.len()));
+        res
+    }
+
+    /// Get the border width of a window, given whether it is drawn without a
+    /// border as it is shown alone, and whether it is focused.
+    ///
+    /// If borders are only drawn on focus, unfocused windows have none.
+    fn border_width_for(&self, borderless: bool, focused: bool) -> u16 {
+        if borderless || self.border_on_focus_only && !focused {
+            0
+        } else {
+            self.border_width as u16
+        }
+    }
+
+    /// Get the desktop number published in `_NET_WM_DESKTOP` for a client
+    /// with a set of tags: the position of it's first tag in the display
+    /// order, if that tag is listed.
+    pub fn desktop_of(&self, tags: &[Tag]) -> Option<u32> {
+        self.display_order(tags)
+            .first()
+            .and_then(|tag| self.tag_order.iter().position(|t| t == tag))
+            .map(|index| index as u32)
+    }
+
+    /// Get the tags of a client from the desktop number it has in
+    /// `_NET_WM_DESKTOP`, if the number corresponds to a tag.
+    pub fn tags_of_desktop(&self, desktop: u32) -> Option<Vec<Tag>> {
+        self.tag_order.get(desktop as usize).map(|tag| vec![tag.clone()])
+    }
+}
+
+/// Prepare a command to run detached from the window manager.
+///
+/// The child is placed in a session of it's own and gets the default
+/// disposition of `SIGCHLD` back, which the window manager ignores to have
+/// it's children reaped. Otherwise, the ignored signal would be inherited,
+/// breaking any child waiting for processes of it's own.
+fn detach(command: &mut Command) -> &mut Command {
+    command.before_exec(|| {
+        unsafe {
+            libc::signal(libc::SIGCHLD, libc::SIG_DFL);
+            libc::setsid();
+        }
+        Ok(())
+    })
+}
+
+/// Schedule focusing a window the pointer entered once it rested on it for
+/// a dwell time in milliseconds, cancelling the focus of windows entered
+/// before.
+///
+/// Returns whether to focus the window right away, as no dwell time is
+/// configured.
+fn dwell_on(timers: &mut TimerQueue<Timer>, now: Instant,
+            window: xproto::Window, focused: Option<xproto::Window>,
+            dwell: u64) -> bool {
+    // the pointer left any window it has been resting on before
+    timers.cancel(|t| match *t {
+        Timer::FocusDwell(_) => true,
+        _ => false,
+    });
+    if focused == Some(window) {
+        false
+    } else if dwell == 0 {
+        true
+    } else {
+        timers.schedule(now, Duration::from_millis(dwell),
+                        Timer::FocusDwell(window));
+        false
+    }
+}
+
+/// Map a new window placed on the output under the pointer, returning
+/// whether that succeeded.
+///
+/// A window we can't map isn't managed at all, so the output focused before
+/// gets focused again.
+fn map_new_window<F>(outputs: &mut OutputSet, old_output: usize, map: F)
+    -> bool where F: FnOnce() -> bool {
+    if map() {
+        true
+    } else {
+        outputs.focus(old_output);
+        false
+    }
+}
+
+/// Get the command to run for a tag that became empty, if any, recording
+/// when it has been run last.
+///
+/// The command isn't run again if it has been run for the same tag
+/// shortly before, so that programs failing to start don't cause a loop.
+fn autostart_command<'a>(commands: &'a [(Tag, String)],
+                         last_run: &mut HashMap<Tag, Instant>,
+                         now: Instant, tag: &Tag) -> Option<&'a str> {
+    let command = match commands.iter().find(|&&(ref t, _)| t == tag) {
+        Some(&(_, ref command)) => command,
+        None => return None,
+    };
+    if last_run
+        .get(tag)
+        .map_or(false, |last| now.duration_since(*last) <
+                Duration::from_secs(AUTOSTART_INTERVAL)) {
+        info!("not restarting program on empty tag too quickly");
+        return None;
+    }
+    last_run.insert(tag.clone(), now);
+    Some(command)
+}
+
+/// Restart waiting for the idle timeout in seconds after user input.
+///
+/// Returns whether the timeout has been restarted, which isn't the case if
+/// none is configured.
+fn restart_idle(timers: &mut TimerQueue<Timer>, now: Instant, timeout: u64)
+    -> bool {
+    if timeout == 0 {
+        return false;
+    }
+    timers.cancel(|t| *t == Timer::Idle);
+    timers.schedule(now, Duration::from_secs(timeout), Timer::Idle);
+    true
+}
+
+/// Get the window zoomed after toggling the zoom, given the window zoomed
+/// before and the focused window, if any.
+///
+/// A zoomed window is restored, otherwise the focused window is zoomed if
+/// it belongs to a tiled client.
+fn toggled_zoom(zoomed: Option<xproto::Window>,
+                focused: Option<xproto::Window>, focused_tiled: bool)
+    -> Option<xproto::Window> {
+    if zoomed.is_some() || !focused_tiled {
+        None
+    } else {
+        focused
+    }
+}
+
+/// Get the geometry a tiled client is shown at, given the geometry its
+/// layout assigned and the size constraints to respect, if any.
+///
+/// Zoomed clients are enlarged and centered on the screen instead, and
+/// need to be raised above the others, which is returned as well.
+fn tiled_geometry(geometry: &Geometry, screen: &ScreenSize,
+                  border_width: u16, zoomed: bool,
+                  size_hints: Option<&SizeHints>) -> (Geometry, bool) {
+    if zoomed {
+        let zoomed = Geometry::centered(
+            screen,
+            (screen.width as u32 * ZOOM_FACTOR as u32 / 100) as u16,
+            (screen.height as u32 * ZOOM_FACTOR as u32 / 100) as u16,
+            border_width);
+        (zoomed, true)
+    } else if let Some(size_hints) = size_hints {
+        (size_hints.fit(geometry), false)
+    } else {
+        (geometry.clone(), false)
+    }
+}
+
+/// Get the names of the actions we allow a client to perform, depending on
+/// whether it is floating.
+///
+/// Tiled clients can only be closed, as the layout determines their
+/// geometry. Floating clients can be moved and resized as well.
+fn allowed_actions(floating: bool) -> Vec<&'static str> {
+    let mut actions = vec!["_NET_WM_ACTION_CLOSE"];
+    if floating {
+        actions.push("_NET_WM_ACTION_MOVE");
+        actions.push("_NET_WM_ACTION_RESIZE");
+    }
+    actions
+}
+
+/// Get whether a client asks to start iconic and whether it accepts input,
+/// given the values of its `WM_HINTS` property.
+fn parse_wm_hints(hints: &[u32]) -> (bool, bool) {
+    // the initial state is the third value, used if the second bit of the
+    // flags is set
+    let start_iconic = hints.len() >= 3 &&
+        hints[0] & (1 << 1) != 0 && hints[2] == ICONIC_STATE;
+    // the input hint is the second value, used if the first bit of the
+    // flags is set, clients not setting it get the focus
+    let accepts_input = hints.len() < 2 || hints[0] & 1 == 0 || hints[1] != 0;
+    (start_iconic, accepts_input)
+}
+
+/// Shrink an output's area to keep the space reserved by dock windows free,
+/// unless struts are ignored.
+///
+/// Struts are relative to the edges of the root window of the given size.
+/// If they leave no space on the output, the whole area is used.
+fn free_area(area: &ScreenSize, root_size: (u16, u16), struts: &[Strut],
+             honor_struts: bool) -> ScreenSize {
+    if !honor_struts {
+        return area.clone();
+    }
+    let (left, right, top, bottom) = struts
+        .iter()
+        .fold((0, 0, 0, 0), |(l, r, t, b), s| (
+            cmp::max(l, s.0), cmp::max(r, s.1),
+            cmp::max(t, s.2), cmp::max(b, s.3)
+        ));
+    let x_start = cmp::max(area.offset_x, left);
+    let x_end = cmp::min(area.offset_x + area.width,
+                         root_size.0.saturating_sub(right));
+    let y_start = cmp::max(area.offset_y, top);
+    let y_end = cmp::min(area.offset_y + area.height,
+                         root_size.1.saturating_sub(bottom));
+    if x_start >= x_end || y_start >= y_end {
+        error!("struts leave no space for tiling, ignoring them");
+        return area.clone();
+    }
+    ScreenSize {
+        offset_x: x_start,
+        offset_y: y_start,
+        width: x_end - x_start,
+        height: y_end - y_start,
+    }
 }
 
 /// A window manager master-structure.
@@ -76,12 +592,18 @@ pub struct Wm<'a> {
     root: xproto::Window,
     /// user-defined configuration parameters
     config: WmConfig,
-    /// screen parameters as obtained from the X server upon connection
-    screen: ScreenSize,
+    /// outputs showing the root window, each with it's own tagsets
+    outputs: OutputSet,
+    /// dimensions of the root window
+    root_size: (u16, u16),
     /// colors used for window borders, first denotes focused windows
     border_colors: (u32, u32),
     /// keybinding callbacks
     bindings: Keybindings,
+    /// callback for key presses without a binding
+    fallback: Option<FallbackCallback>,
+    /// rules for client placement, tried before the matching function
+    rules: Vec<ClientRule>,
     /// matching function for client placement
     matching: Option<Matching>,
     /// plugin container
@@ -90,16 +612,54 @@ pub struct Wm<'a> {
     mode: Mode,
     /// set of currently present clients
     clients: ClientSet,
-    /// set of currently present tagsets and their display history
-    tag_stack: TagStack,
     /// atoms registered at runtime
     atoms: AtomList<'a>,
     /// all windows currently visible
     visible_windows: Vec<xproto::Window>,
+    /// the outputs visible windows are shown on
+    window_outputs: HashMap<xproto::Window, usize>,
+    /// visible windows drawn without a border, as they are shown alone
+    borderless_windows: HashSet<xproto::Window>,
+    /// visible windows of tiled clients left floating by their layout
+    layout_floating_windows: HashSet<xproto::Window>,
     /// currently focused window
     focused_window: Option<xproto::Window>,
     /// windows we know about, but do not manage
     unmanaged_windows: Vec<xproto::Window>,
+    /// space reserved by dock windows
+    struts: HashMap<xproto::Window, Strut>,
+    /// whether the space reserved by dock windows is kept free
+    honor_struts: bool,
+    /// socket accepting commands from other processes, if available
+    ipc: Option<IpcSocket>,
+    /// actions deferred to a later point in time
+    timers: TimerQueue<Timer>,
+    /// tiled window temporarily enlarged above the others, if any
+    zoomed_window: Option<xproto::Window>,
+    /// window toggled above the current tagset regardless of it's tags,
+    /// if any
+    scratchpad: Option<xproto::Window>,
+    /// whether the scratchpad is currently shown
+    scratchpad_shown: bool,
+    /// windows we mapped, but didn't receive a `MapNotifyEvent` for yet
+    unconfirmed_maps: HashSet<xproto::Window>,
+    /// number of `UnmapNotifyEvent`s we expect for windows we unmapped
+    pending_unmaps: HashMap<xproto::Window, usize>,
+    /// desktop numbers last published in `_NET_WM_DESKTOP`, by window
+    published_desktops: HashMap<xproto::Window, u32>,
+    /// whether there has been no input for the configured idle timeout
+    idle: bool,
+    /// the last time a command has been run because a tag became empty
+    last_autostart: HashMap<Tag, Instant>,
+    /// closure setting up the tagset stack of an output by index, if any
+    tag_stacks: Option<Box<Fn(usize) -> TagStack>>,
+    /// first event number of the RandR extension, if present
+    randr_base: Option<u8>,
+    /// floating client currently dragged with the mouse, if any
+    drag: Option<Drag>,
+    /// sequence numbers of the requests issued before and after the last
+    /// arrangement of windows, enter events in between are caused by it
+    arrange_sequences: (u16, u16),
 }
 
 impl<'a> Wm<'a> {
@@ -111,28 +671,62 @@ impl<'a> Wm<'a> {
             let width = screen.width_in_pixels();
             let height = screen.height_in_pixels();
             let colormap = screen.default_colormap();
-            let new_screen = ScreenSize::new(&config.screen, width, height);
+            let outputs = Wm::setup_outputs(con, screen.root(), &config,
+                                            width, height);
             match Wm::get_atoms(con, &ATOM_VEC) {
                 Ok(atoms) => {
                     Ok(Wm {
                         con: con,
                         root: screen.root(),
                         config: config.clone(),
-                        screen: new_screen,
+                        outputs: outputs,
+                        root_size: (width, height),
                         border_colors: Wm::setup_colors(con,
                                                         colormap,
                                                         config.f_color,
                                                         config.u_color),
                         bindings: HashMap::new(),
+                        rules: Vec::new(),
                         matching: None,
                         plugins: HashMap::new(),
+                        fallback: None,
                         mode: Mode::default(),
                         clients: ClientSet::new(),
-                        tag_stack: TagStack::new(),
                         atoms: atoms,
                         visible_windows: Vec::new(),
+                        window_outputs: HashMap::new(),
+                        borderless_windows: HashSet::new(),
+                        layout_floating_windows: HashSet::new(),
                         focused_window: None,
                         unmanaged_windows: Vec::new(),
+                        struts: HashMap::new(),
+                        honor_struts: true,
+                        ipc: match IpcSocket::bind() {
+                            Ok(socket) => Some(socket),
+                            Err(e) => {
+                                error!("could not create ipc socket: {}", e);
+                                None
+                            },
+                        },
+                        timers: TimerQueue::new(),
+                        zoomed_window: None,
+                        scratchpad: None,
+                        scratchpad_shown: false,
+                        unconfirmed_maps: HashSet::new(),
+                        pending_unmaps: HashMap::new(),
+                        published_desktops: HashMap::new(),
+                        idle: false,
+                        last_autostart: HashMap::new(),
+                        tag_stacks: None,
+                        drag: None,
+                        arrange_sequences: (0, 0),
+                        randr_base: con
+                            .get_extension_data(randr::id())
+                            .and_then(|data| if data.present() {
+                                Some(data.first_event())
+                            } else {
+                                None
+                            }),
                     })
                 }
                 Err(e) => Err(e),
@@ -142,6 +736,29 @@ impl<'a> Wm<'a> {
         }
     }
 
+    /// Determine the outputs to show the root window on.
+    ///
+    /// If RandR reports multiple outputs, each covers it's area of the root
+    /// window. Otherwise, a single output uses the screen parameters
+    /// requested by the user.
+    fn setup_outputs(con: &base::Connection, root: xproto::Window,
+                     config: &WmConfig, width: u16, height: u16)
+        -> OutputSet {
+        let mut outputs = query_outputs(con, root);
+        if outputs.len() > 1 {
+            OutputSet::new(outputs
+                .drain(..)
+                .map(|(name, area)| Output::new(name, area))
+                .collect())
+        } else {
+            let name = outputs
+                .pop()
+                .map_or_else(|| "default".to_owned(), |(name, _)| name);
+            let area = ScreenSize::new(&config.screen, width, height);
+            OutputSet::new(vec![Output::new(name, area)])
+        }
+    }
+
     /// Allocate colors needed for border drawing.
     fn setup_colors(con: &'a base::Connection,
                     colormap: xproto::Colormap,
@@ -169,21 +786,104 @@ impl<'a> Wm<'a> {
     /// Register window manager.
     ///
     /// Issues substructure redirects for the root window and registers for
-    /// all events we are interested in.
+    /// all events we are interested in, including screen configuration
+    /// changes if RandR is available. Afterwards, the supported EWMH hints
+    /// are advertised.
     pub fn register(&self) -> Result<(), WmError> {
         let values = xproto::EVENT_MASK_SUBSTRUCTURE_REDIRECT
             | xproto::EVENT_MASK_SUBSTRUCTURE_NOTIFY
             | xproto::EVENT_MASK_PROPERTY_CHANGE;
-        match xproto::change_window_attributes(
+        if xproto::change_window_attributes(
             self.con, self.root, &[(xproto::CW_EVENT_MASK, values)])
-            .request_check() {
-            Ok(()) => Ok(()),
-            Err(_) => Err(WmError::OtherWmRunning),
+            .request_check()
+            .is_err() {
+            return Err(WmError::OtherWmRunning);
+        }
+        if self.randr_base.is_some() &&
+           randr::select_input_checked(
+               self.con, self.root,
+               randr::NOTIFY_MASK_SCREEN_CHANGE as u16)
+               .request_check()
+               .is_err() {
+            error!("could not register for screen changes");
+        }
+        self.grab_drag_buttons();
+        self.advertise_ewmh();
+        Ok(())
+    }
+
+    /// Advertise EWMH compliance on the root window.
+    ///
+    /// The hints implemented are listed in `_NET_SUPPORTED`, and a child
+    /// window named like the window manager is referenced by
+    /// `_NET_SUPPORTING_WM_CHECK`, both on the root window and on itself.
+    fn advertise_ewmh(&self) {
+        let supported: Vec<_> = SUPPORTED_HINTS
+            .iter()
+            .map(|name| self.lookup_atom(name))
+            .collect();
+        let check = self.con.generate_id();
+        let check_atom = self.lookup_atom("_NET_SUPPORTING_WM_CHECK");
+        let cookies = vec![
+            xproto::change_property(
+                self.con, xproto::PROP_MODE_REPLACE as u8, self.root,
+                self.lookup_atom("_NET_SUPPORTED"), xproto::ATOM_ATOM, 32,
+                &supported),
+            xproto::create_window(
+                self.con, 0, check, self.root, -1, -1, 1, 1, 0,
+                xproto::WINDOW_CLASS_INPUT_ONLY as u16, 0, &[]),
+            xproto::change_property(
+                self.con, xproto::PROP_MODE_REPLACE as u8, check,
+                check_atom, xproto::ATOM_WINDOW, 32, &[check]),
+            xproto::change_property(
+                self.con, xproto::PROP_MODE_REPLACE as u8, check,
+                self.lookup_atom("_NET_WM_NAME"),
+                self.lookup_atom("UTF8_STRING"), 8,
+                "gabelstaplerwm".as_bytes()),
+            xproto::change_property(
+                self.con, xproto::PROP_MODE_REPLACE as u8, self.root,
+                check_atom, xproto::ATOM_WINDOW, 32, &[check]),
+        ];
+        for cookie in cookies {
+            if cookie.request_check().is_err() {
+                error!("could not advertise EWMH support");
+            }
+        }
+    }
+
+    /// Grab the mouse buttons used to drag floating clients on the root
+    /// window, if enabled.
+    fn grab_drag_buttons(&self) {
+        if self.config.drag_modifier == NO_MODIFIER {
+            return;
+        }
+        let mask = xproto::EVENT_MASK_BUTTON_PRESS |
+            xproto::EVENT_MASK_BUTTON_RELEASE |
+            xproto::EVENT_MASK_POINTER_MOTION;
+        for button in &[xproto::BUTTON_INDEX_1, xproto::BUTTON_INDEX_3] {
+            let cookie = xproto::grab_button(
+                self.con, false, self.root, mask as u16,
+                xproto::GRAB_MODE_ASYNC as u8, xproto::GRAB_MODE_ASYNC as u8,
+                base::NONE, base::NONE,
+                *button as u8, self.config.drag_modifier as u16
+            );
+            if cookie.request_check().is_err() {
+                error!("could not grab mouse button");
+            }
         }
     }
 
     /// Set up keybindings and necessary keygrabs.
+    ///
+    /// Problems with the bindings are logged, later bindings of a key
+    /// overwriting earlier ones.
     pub fn setup_bindings(&mut self, mut keys: Vec<(KeyPress, KeyCallback)>) {
+        if let Err(errors) = self.validate_bindings(&keys, false) {
+            for e in errors {
+                error!("{}", e);
+            }
+        }
+
         // don't grab anything for now
         xproto::ungrab_key(
             self.con, xproto::GRAB_ANY as u8,
@@ -196,26 +896,74 @@ impl<'a> Wm<'a> {
             .drain(..)
             .filter_map(|(key, callback)|
                 if self.bindings.insert(key, callback).is_some() {
-                    error!("overwriting bindings for a key!");
                     None
                 } else {
                     // register for the corresponding event
-                    Some(xproto::grab_key(
-                        self.con, true, self.root,
-                        key.mods as u16, key.code,
-                        xproto::GRAB_MODE_ASYNC as u8,
-                        xproto::GRAB_MODE_ASYNC as u8
-                    ))
+                    Some((key, self.grab_key(key)))
                 }
             )
             .collect();
 
         // check for errors
-        for cookie in cookies {
+        for (key, cookie) in cookies {
             if cookie.request_check().is_err() {
-                error!("could not grab key!");
+                error!("{}", BindingError::AlreadyGrabbed(key));
+            }
+        }
+    }
+
+    /// Grab a key on the root window.
+    fn grab_key(&self, key: KeyPress) -> base::VoidCookie<'a> {
+        xproto::grab_key(
+            self.con, true, self.root,
+            key.mods as u16, key.code,
+            xproto::GRAB_MODE_ASYNC as u8,
+            xproto::GRAB_MODE_ASYNC as u8
+        )
+    }
+
+    /// Check a keybinding configuration for problems without applying it.
+    ///
+    /// Reports keys bound more than once and keycodes the X server doesn't
+    /// support. If `check_grabs` is set, all keys are grabbed and released
+    /// again to find keys other clients have grabbed already. This releases
+    /// our own grabs as well, so it should only be used before setting up
+    /// the bindings.
+    pub fn validate_bindings(&self, keys: &[(KeyPress, KeyCallback)],
+                             check_grabs: bool)
+        -> Result<(), Vec<BindingError>> {
+        let setup = self.con.get_setup();
+        let keycodes = (setup.min_keycode(), setup.max_keycode());
+        let keys: Vec<KeyPress> = keys.iter().map(|&(key, _)| key).collect();
+        let mut errors = Vec::new();
+        for res in check_bindings(&keys, keycodes) {
+            match res {
+                Err(e) => errors.push(e),
+                Ok(key) if check_grabs => {
+                    if self.grab_key(key).request_check().is_err() {
+                        errors.push(BindingError::AlreadyGrabbed(key));
+                    } else {
+                        xproto::ungrab_key(self.con, key.code, self.root,
+                                           key.mods as u16);
+                    }
+                },
+                Ok(_) => (),
             }
         }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Set up a callback for key presses no keybinding or plugin matches.
+    ///
+    /// Note that the callback receives all state changes of the keyboard,
+    /// including the pressing of modifier keys.
+    #[allow(dead_code)]
+    pub fn setup_fallback(&mut self, fallback: FallbackCallback) {
+        self.fallback = Some(fallback);
     }
 
     /// Set up client matching.
@@ -223,225 +971,1532 @@ impl<'a> Wm<'a> {
         self.matching = Some(matching);
     }
 
-    /// Set up the tagset stack.
-    pub fn setup_tags(&mut self, stack: TagStack) {
-        self.tag_stack = stack;
+    /// Set up client rules.
+    pub fn setup_rules(&mut self, rules: Vec<ClientRule>) {
+        self.rules = rules;
     }
 
-    /// Add all present clients to the datastructures on startup.
-    pub fn setup_clients(&mut self) {
-        if let Ok(root) = xproto::query_tree(self.con, self.root).get_reply() {
-            for window in root.children() {
-                if let Some(client) = self.construct_client(*window) {
-                    self.add_client(client);
-                    self.visible_windows.push(*window);
-                }
-            }
+    /// Replace client rules and matching at runtime.
+    ///
+    /// If `retag` is set, the tags of all existing clients are recomputed
+    /// using the new rules and matching function, moving them accordingly.
+    /// Clients neither of them match keep their tags.
+    pub fn replace_matching(&mut self, rules: Vec<ClientRule>,
+                            matching: Matching, retag: bool) {
+        self.rules = rules;
+        self.matching = Some(matching);
+        if retag {
+            let rules = &self.rules;
+            let matching = &self.matching;
+            self.clients.retag(|props| {
+                ClientRule::tags_from(rules, props)
+                    .or_else(|| matching.as_ref().and_then(|f| f(props)))
+            });
             self.arrange_windows();
             self.reset_focus();
         }
     }
 
-    /// Check whether we currently create new clients as masters or slaves.
+    /// Set up the tagset stacks of all outputs.
     ///
-    /// This depends on the layout of the currently viewed tagset.
-    /// For instance, the `Monocle` layout only shows the master window,
-    /// rendering client creation as a slave useless and unergonomic.
-    fn new_window_as_master(&self) -> bool {
-        match self.tag_stack.current() {
-            Some(ref tagset) => tagset.layout.new_window_as_master(),
-            _ => false,
+    /// The closure is called with the index of each output, the leftmost
+    /// output having index 0. It is kept to set up outputs appearing later
+    /// on.
+    pub fn setup_tags<F>(&mut self, stacks: F)
+        where F: Fn(usize) -> TagStack + 'static {
+        for index in 0..self.outputs.len() {
+            if let Some(output) = self.outputs.get_mut(index) {
+                output.tag_stack = stacks(index);
+            }
         }
+        self.tag_stacks = Some(Box::new(stacks));
     }
 
-    /// Using the current layout, arrange all visible windows.
+    /// Query the outputs anew after the screen configuration changed.
     ///
-    /// This first determines the set of visible windows, and displays them
-    /// accordingly after hiding all windows. This semantic was chosen, because
-    /// redraws are only triggered when the set of visible windows is expected
-    /// to have changed, e.g. when a user-defined callback returned the
-    /// corresponding `WmCommand`.
-    fn arrange_windows(&mut self) {
-        // first, hide all visible windows ...
-        self.hide_windows(&self.visible_windows);
-        // ... and reset the vector of visible windows
-        self.visible_windows.clear();
-        // setup current client list
-        let (clients, layout) = match self.tag_stack.current() {
-            Some(tagset) => (
-                self.clients.get_order_or_insert(&tagset.tags),
-                &tagset.layout
-            ),
-            None => return, // nothing to do here - no current tagset
-        };
-        // get geometries ...
-        let geometries = layout.arrange(clients.1.len(), &self.screen);
-        // we set geometries in serial, because otherwise window redraws are
-        // rendered lazily, at least with xephyr. to avoid this condition,
-        // we accept some additional waiting time, which doesn't matter much
-        // - redraw times aren't subject to visible latency anyway. until this
-        // is fixed, the code below has to stay serial in nature.
-        for (client, geometry) in clients.1.iter().zip(geometries.iter()) {
-            // ... and apply them if a window is to be displayed
-            if let (Some(ref cl), &Some(ref geom))
-                = (client.upgrade(), geometry) {
-                self.visible_windows.push(cl.borrow().window);
-                let cookie = xproto::configure_window(
-                    self.con, cl.borrow().window,
-                    &[(xproto::CONFIG_WINDOW_X as u16, geom.x as u32),
-                      (xproto::CONFIG_WINDOW_Y as u16, geom.y as u32),
-                      (xproto::CONFIG_WINDOW_WIDTH as u16, geom.width as u32),
-                      (xproto::CONFIG_WINDOW_HEIGHT as u16, geom.height as u32)
-                    ]);
-                if cookie.request_check().is_err() {
-                    error!("could not set window geometry");
-                }
+    /// Outputs still present keep their tagset stacks. The stacks of
+    /// outputs that disappeared are handed to new outputs, the remaining new
+    /// outputs are set up like on startup. Clients only shown on outputs that
+    /// disappeared can be reached by viewing their tags on the others.
+    fn update_outputs(&mut self) {
+        let (width, height) =
+            match xproto::get_geometry(self.con, self.root).get_reply() {
+                Ok(geom) => (geom.width(), geom.height()),
+                Err(_) => self.root_size,
+            };
+        let outputs = Wm::setup_outputs(self.con, self.root, &self.config,
+                                        width, height);
+        let old = mem::replace(&mut self.outputs, outputs);
+        let current_name = old.current().name.clone();
+        self.root_size = (width, height);
+        let mut assigned = vec![false; self.outputs.len()];
+        let mut orphaned = Vec::new();
+        for output in old.into_outputs() {
+            match self.outputs.index_of(&output.name) {
+                Some(index) if !assigned[index] => {
+                    self.outputs.get_mut(index).unwrap().tag_stack =
+                        output.tag_stack;
+                    assigned[index] = true;
+                },
+                _ => orphaned.push(output.tag_stack),
+            }
+        }
+        orphaned.reverse();
+        for index in 0..self.outputs.len() {
+            if assigned[index] {
+                continue;
             }
+            let stack = orphaned.pop().unwrap_or_else(|| self
+                .tag_stacks
+                .as_ref()
+                .map_or_else(TagStack::new, |stacks| stacks(index)));
+            self.outputs.get_mut(index).unwrap().tag_stack = stack;
         }
+        if let Some(index) = self.outputs.index_of(&current_name) {
+            self.outputs.focus(index);
+        }
+        info!("outputs changed, {} present", self.outputs.len());
+        self.arrange_windows();
+        self.reset_focus();
     }
 
-    /// Hide some windows by moving them offscreen.
-    fn hide_windows(&self, windows: &[xproto::Window]) {
-        let safe_x = (self.screen.width * 2) as u32;
-        let cookies: Vec<_> = windows
-            .iter()
-            .map(|window| xproto::configure_window(
-                 self.con, *window,
-                 &[(xproto::CONFIG_WINDOW_X as u16, safe_x),
-                   (xproto::CONFIG_WINDOW_Y as u16, 0)]
-                )
-            )
-            .collect();
-        for cookie in cookies {
-            if cookie.request_check().is_err() {
-                error!("could not move window offscreen");
+    /// Add all present clients to the datastructures on startup.
+    pub fn setup_clients(&mut self) {
+        if let Ok(root) = xproto::query_tree(self.con, self.root).get_reply() {
+            for window in root.children() {
+                if let Some(mut client) = self.construct_client(*window) {
+                    // the initial state only applies to newly mapped windows
+                    client.set_minimized(false);
+                    self.add_client(client);
+                    self.visible_windows.push(*window);
+                }
             }
+            self.arrange_windows();
+            self.reset_focus();
         }
-
     }
 
-    /// Destroy a window.
+    /// Rebuild the client set from the window tree.
     ///
-    /// Send a client message and kill the client the hard and merciless way
-    /// if that fails, for instance if the client ignores such messages.
-    fn destroy_window(&self, window: xproto::Window) {
-        if self.send_event(window, "WM_DELETE_WINDOW") {
-            if xproto::kill_client(self.con, window).request_check().is_err() {
-                error!("could not kill client");
+    /// All cached client orderings and the state kept about unmanaged
+    /// windows are dropped, and the windows are arranged and focused anew.
+    /// Clients get their tags back from `_NET_WM_DESKTOP` where it is set,
+    /// and windows managed before keep their floating, sticky and minimized
+    /// state. All other windows are matched like newly mapped windows.
+    fn rebuild_clients(&mut self) {
+        info!("rebuilding client set");
+        let old = mem::replace(&mut self.clients, ClientSet::new());
+        self.zoomed_window = None;
+        self.scratchpad = None;
+        self.unmanaged_windows.clear();
+        self.struts.clear();
+        self.unconfirmed_maps.clear();
+        self.pending_unmaps.clear();
+        self.published_desktops.clear();
+        let children = match xproto::query_tree(self.con, self.root)
+            .get_reply() {
+            Ok(root) => root.children().to_vec(),
+            Err(_) => {
+                error!("could not query window tree");
+                Vec::new()
+            },
+        };
+        for window in children.iter() {
+            // unmapped windows are only kept if they were minimized clients
+            let old_client = old.get_client_by_window(*window);
+            if !self.is_viewable(*window) && old_client.is_none() {
+                continue;
+            }
+            if let Some(mut client) = self.construct_client(*window) {
+                client.set_minimized(false);
+                if let Some(tags) = self
+                    .get_desktop(*window)
+                    .and_then(|d| self.config.tags_of_desktop(d)) {
+                    client.set_tags(&tags);
+                }
+                if let Some(old_client) = old_client {
+                    let old_client = old_client.borrow();
+                    client.set_minimized(old_client.is_minimized());
+                    if old_client.is_floating() != client.is_floating() {
+                        client.toggle_floating();
+                    }
+                    if old_client.is_sticky() {
+                        client.toggle_sticky();
+                    }
+                }
+                self.add_client(client);
+                if !self.visible_windows.contains(window) {
+                    self.visible_windows.push(*window);
+                }
+            } else if self
+                .get_properties(*window)
+                .map_or(false, |props| self.is_unmanaged_type(&props)) {
+                self.add_unmanaged(*window);
             }
         }
+        self.arrange_windows();
+        self.reset_focus();
     }
 
-    /// Reset focus.
+    /// Check whether a window is currently mapped and viewable.
+    fn is_viewable(&self, window: xproto::Window) -> bool {
+        xproto::get_window_attributes(self.con, window)
+            .get_reply()
+            .map(|attrs| attrs.map_state() == xproto::MAP_STATE_VIEWABLE as u8)
+            .unwrap_or(false)
+    }
+
+    /// Publish the desktop number of each client's tags in it's
+    /// `_NET_WM_DESKTOP` property, where it changed.
     ///
-    /// The datastructures have been altered, we need to focus the appropriate
-    /// window as obtained from tehre. if an old window is given, uncolor it's
-    /// border.
-    fn reset_focus(&mut self) {
-        if let Some(new) = self
-            .tag_stack
-            .current()
-            .and_then(|t| self.clients.get_focused_window(&t.tags)) {
-            if self.new_window_as_master() {
-               self.clients.swap_master(self.tag_stack.current().unwrap());
-               self.arrange_windows();
-            }
-            if let Some(old_win) = self.focused_window {
-                self.set_border_color(old_win, self.border_colors.1);
-            }
-            if self.send_event(new, "WM_TAKE_FOCUS") {
-                info!("could not send focus message to window");
+    /// This lets the tags of clients be restored when rebuilding the client
+    /// set.
+    fn publish_desktops(&mut self) {
+        let atom = self.lookup_atom("_NET_WM_DESKTOP");
+        for window in self.clients.windows() {
+            let desktop = match self
+                .clients
+                .tags_of(window)
+                .and_then(|tags| self.config.desktop_of(&tags)) {
+                Some(desktop) => desktop,
+                None => continue,
+            };
+            if self.published_desktops.get(&window) == Some(&desktop) {
+                continue;
             }
-            let cookie =
-                xproto::set_input_focus(self.con,
-                                        xproto::INPUT_FOCUS_POINTER_ROOT as u8,
-                                        new,
-                                        xproto::TIME_CURRENT_TIME);
-            self.set_border_color(new, self.border_colors.0);
+            let cookie = xproto::change_property(
+                self.con, xproto::PROP_MODE_REPLACE as u8, window, atom,
+                xproto::ATOM_CARDINAL, 32, &[desktop]);
             if cookie.request_check().is_err() {
-                error!("could not focus window");
+                error!("could not set desktop of window");
             } else {
-                self.focused_window = Some(new);
+                self.published_desktops.insert(window, desktop);
             }
         }
     }
 
-    /// Color the borders of a window.
-    fn set_border_color(&self, window: xproto::Window, color: u32) {
-        let cookie = xproto::change_window_attributes(
-            self.con, window, &[(xproto::CW_BORDER_PIXEL, color)]);
-        if cookie.request_check().is_err() {
-            error!("could not set window border color");
+    /// Get the desktop number a window has in `_NET_WM_DESKTOP`, if any.
+    fn get_desktop(&self, window: xproto::Window) -> Option<u32> {
+        let cookie = xproto::get_property(
+            self.con, false, window, self.lookup_atom("_NET_WM_DESKTOP"),
+            xproto::ATOM_CARDINAL, 0, 1
+        );
+        match cookie.get_reply() {
+            Ok(reply) => {
+                let values: &[u32] = reply.value();
+                values.first().cloned()
+            },
+            Err(_) => None,
         }
     }
 
-    /// Wait for events, handle them. Repeat.
-    pub fn run(&mut self) -> Result<(), WmError> {
-        loop {
-            self.con.flush();
-            if let Err(_) = self.con.has_error() {
-                return Err(WmError::ConnectionInterrupted);
-            }
-            match self.con.wait_for_event() {
-                Some(ev) => self.handle(ev),
-                None => return Err(WmError::IOError),
-            }
+    /// Check whether we currently create new clients as masters or slaves.
+    ///
+    /// This depends on the layout of the currently viewed tagset, as in effect
+    /// for the number of tiled windows shown on it. For instance, the
+    /// `Monocle` layout only shows the master window, rendering client
+    /// creation as a slave useless and unergonomic.
+    fn new_window_as_master(&mut self) -> bool {
+        match self.outputs.current().tag_stack.current() {
+            Some(tagset) => {
+                let num_windows = self.clients
+                    .get_order_or_insert(&tagset.tags)
+                    .1
+                    .iter()
+                    .filter_map(|c| c.upgrade())
+                    .filter(|c| !c.borrow().is_floating())
+                    .count();
+                tagset.layout_for(num_windows).new_window_as_master()
+            },
+            _ => false,
         }
     }
 
-    /// Handle an event received from the X server.
-    fn handle(&mut self, event: base::GenericEvent) {
-        match event.response_type() {
-            xkb::STATE_NOTIFY =>
-                self.handle_state_notify(base::cast_event(&event)),
-            xproto::PROPERTY_NOTIFY =>
-                self.handle_property_notify(base::cast_event(&event)),
-            xproto::CLIENT_MESSAGE =>
-                self.handle_client_message(base::cast_event(&event)),
-            xproto::DESTROY_NOTIFY =>
-                self.handle_destroy_notify(base::cast_event(&event)),
-            xproto::CONFIGURE_REQUEST =>
-                self.handle_configure_request(base::cast_event(&event)),
-            xproto::MAP_REQUEST =>
-                self.handle_map_request(base::cast_event(&event)),
-            num => debug!("ignoring event: {}", num),
+    /// Get the windows of the tiled clients on the current tagset, in the
+    /// order they are passed to its layout.
+    fn tiled_windows(&mut self) -> Vec<xproto::Window> {
+        match self.outputs.current().tag_stack.current() {
+            Some(tagset) => self.clients.tiled_windows(&tagset.tags),
+            None => Vec::new(),
         }
     }
 
-    /// A key has been pressed, react accordingly.
+    /// Notify the layouts of all tagsets that a client is going to be
+    /// removed, if it is tiled there.
+    fn notify_client_removed(&mut self, window: xproto::Window) {
+        let tagsets = self
+            .outputs
+            .iter_mut()
+            .flat_map(|o| o.tag_stack.tagsets_mut());
+        self.clients.notify_removal(window, tagsets);
+    }
+
+    /// Using the current layouts, arrange all visible windows.
     ///
-    /// Look for a matching key binding upon event receival and call a callback
-    /// closure if necessary. Determine what to do next based on the
+    /// This first determines the set of visible windows and displays them,
+    /// unmapping the windows that aren't visible anymore afterwards. This
+    /// semantic was chosen, because redraws are only triggered when the set
+    /// of visible windows is expected to have changed, e.g. when a
+    /// user-defined callback returned the corresponding `WmCommand`.
+    fn arrange_windows(&mut self) {
+        let first = self.mark_sequence();
+        // reset the vector of visible windows ...
+        let previous = mem::replace(&mut self.visible_windows, Vec::new());
+        self.window_outputs.clear();
+        self.borderless_windows.clear();
+        self.layout_floating_windows.clear();
+        self.clients.clear_hidden();
+        // ... and fill it again
+        for index in 0..self.outputs.len() {
+            self.arrange_output(index);
+        }
+        self.arrange_scratchpad();
+        // hide and show windows, keeping the state of clients in line
+        let hidden: Vec<_> = previous
+            .iter()
+            .filter(|w| !self.visible_windows.contains(w) &&
+                    self.clients.get_client_by_window(**w).is_some())
+            .cloned()
+            .collect();
+        let shown: Vec<_> = self.visible_windows
+            .iter()
+            .filter(|w| !previous.contains(w))
+            .cloned()
+            .collect();
+        self.hide_windows(&hidden);
+        self.show_windows(&shown);
+        for window in hidden {
+            self.set_wm_state(window, ICONIC_STATE);
+        }
+        for window in shown {
+            self.set_wm_state(window, NORMAL_STATE);
+        }
+        // clients learn about their final geometries
+        self.send_configure_notifies(&self.visible_windows);
+        self.publish_desktops();
+        self.arrange_sequences = (first, self.mark_sequence());
+    }
+
+    /// Issue a request doing nothing and return its sequence number, to tell
+    /// apart events caused by the requests issued after it.
+    ///
+    /// Only needed if focus follows the mouse, returns 0 otherwise.
+    fn mark_sequence(&self) -> u16 {
+        if self.config.focus_follows_mouse {
+            xproto::no_operation(self.con).cookie.sequence as u16
+        } else {
+            0
+        }
+    }
+
+    /// Check whether an event has been caused by the last arrangement of
+    /// windows, given its sequence number.
+    fn caused_by_arrange(&self, sequence: u16) -> bool {
+        let (first, last) = self.arrange_sequences;
+        let offset = sequence.wrapping_sub(first);
+        offset > 0 && offset < last.wrapping_sub(first)
+    }
+
+    /// Arrange the windows visible on an output.
+    ///
+    /// Windows already shown on another output are skipped.
+    fn arrange_output(&mut self, index: usize) {
+        let (area, screen) = match self.outputs.get(index) {
+            Some(output) =>
+                (output.area.clone(), self.compute_struts(&output.area)),
+            None => return,
+        };
+        // setup current client list
+        let (clients, tagset) =
+            match self.outputs.get(index).and_then(|o| o.tag_stack.current()) {
+                Some(tagset) => (
+                    self.clients.get_order_or_insert(&tagset.tags),
+                    tagset
+                ),
+                None => return, // nothing to do here - no current tagset
+            };
+        // windows already shown elsewhere are skipped
+        let shown = &self.window_outputs;
+        let scratchpad = self.scratchpad;
+        let clients: Vec<_> = clients.1
+            .iter()
+            .filter_map(|c| c.upgrade())
+            .filter(|c| !shown.contains_key(&c.borrow().window))
+            .filter(|c| Some(c.borrow().window) != scratchpad)
+            .collect();
+        // get geometries ...
+        let arrangement = tagset
+            .arrange(clients, &screen, self.config.border_width as u16);
+        if arrangement.layout_floats {
+            for client in arrangement.floating.iter() {
+                self.layout_floating_windows.insert(client.borrow().window);
+            }
+        }
+        // a single tiled window doesn't need a border to stand out
+        if self.config.smart_borders && arrangement.num_shown() == 1 {
+            for &(ref client, ref geometry) in arrangement.tiled.iter() {
+                if geometry.is_some() {
+                    self.borderless_windows.insert(client.borrow().window);
+                }
+            }
+        }
+        // by default, we set geometries in serial, because otherwise window
+        // redraws are rendered lazily, at least with xephyr. to avoid this
+        // condition, we accept some additional waiting time, which doesn't
+        // matter much - redraw times aren't subject to visible latency
+        // anyway. servers not affected can batch the requests instead,
+        // saving a round trip per window.
+        let mut cookies = Vec::new();
+        for &(ref client, ref geometry) in arrangement.tiled.iter() {
+            // ... and apply them if a window is to be displayed
+            if let Some(ref geom) = *geometry {
+                let window = client.borrow().window;
+                self.visible_windows.push(window);
+                self.window_outputs.insert(window, index);
+                self.set_allowed_actions(window, false);
+                let size_hints = client.borrow().size_hints().clone();
+                let (geom, raise) = tiled_geometry(
+                    geom, &screen, self.config.border_width as u16,
+                    self.zoomed_window == Some(window),
+                    if self.config.respect_size_hints {
+                        Some(&size_hints)
+                    } else {
+                        None
+                    });
+                let cookie = self.configure_geometry(window, &geom, raise);
+                self.check_configure(cookie, &mut cookies);
+            } else {
+                self.clients.mark_hidden(client.borrow().window);
+            }
+        }
+        // floating clients are placed above the tiled ones
+        for client in arrangement.floating {
+            let geom = client
+                .borrow_mut()
+                .float_geometry(&screen, self.config.border_width as u16);
+            let window = client.borrow().window;
+            self.visible_windows.push(window);
+            self.window_outputs.insert(window, index);
+            self.set_allowed_actions(window, true);
+            let cookie = self.configure_geometry(window, &geom, true);
+            self.check_configure(cookie, &mut cookies);
+        }
+        // fullscreen clients cover the whole output, above everything else
+        for client in arrangement.fullscreen {
+            let window = client.borrow().window;
+            self.visible_windows.push(window);
+            self.window_outputs.insert(window, index);
+            self.set_allowed_actions(window, false);
+            let cookie = self.configure_fullscreen(window, &area);
+            self.check_configure(cookie, &mut cookies);
+        }
+        // batched requests are checked once all of them have been sent
+        for cookie in cookies {
+            if cookie.request_check().is_err() {
+                error!("could not set window geometry");
+            }
+        }
+    }
+
+    /// Show the scratchpad centered on the current output, if it is to be
+    /// shown.
+    ///
+    /// The scratchpad is floating above all other windows, keeping the size
+    /// of it's floating geometry.
+    fn arrange_scratchpad(&mut self) {
+        let client = match self.scratchpad {
+            Some(window) if self.scratchpad_shown =>
+                self.clients.get_client_by_window(window),
+            _ => None,
+        };
+        if let Some(client) = client {
+            let index = self.outputs.current_index();
+            let screen = self.compute_struts(&self.outputs.current().area);
+            let border_width = self.config.border_width as u16;
+            let float =
+                client.borrow_mut().float_geometry(&screen, border_width);
+            let geom = Geometry::centered(
+                &screen, float.width, float.height, border_width);
+            let window = client.borrow().window;
+            self.visible_windows.push(window);
+            self.window_outputs.insert(window, index);
+            self.set_allowed_actions(window, true);
+            self.set_geometry(window, &geom, true);
+        }
+    }
+
+    /// Make the focused client the scratchpad, or turn the scratchpad back
+    /// into a regular client if it is focused.
+    fn mark_scratchpad(&mut self) {
+        if self.focused_window.is_none() {
+            return;
+        }
+        if self.scratchpad == self.focused_window {
+            self.scratchpad = None;
+        } else {
+            self.scratchpad = self.focused_window;
+            self.scratchpad_shown = true;
+        }
+        self.arrange_windows();
+        self.reset_focus();
+    }
+
+    /// Show or hide the scratchpad, if any.
+    fn toggle_scratchpad(&mut self) {
+        if self.scratchpad.is_some() {
+            self.scratchpad_shown = !self.scratchpad_shown;
+            self.arrange_windows();
+            self.reset_focus();
+        }
+    }
+
+    /// View a tagset showing the most recently urgent client and focus the
+    /// client, clearing it's urgency flag.
+    ///
+    /// An output already showing the client is focused, otherwise the
+    /// current output switches to the tagset best matching the client's
+    /// tags.
+    fn focus_urgent(&mut self) {
+        let (tags, window) = match self.clients.find_urgent() {
+            Some(urgent) => urgent,
+            None => return,
+        };
+        let shown = (0..self.outputs.len()).find(|i| self
+            .outputs
+            .get(*i)
+            .and_then(|o| o.tag_stack.current())
+            .map_or(false, |t| self
+                .clients
+                .get_client_by_window(window)
+                .map_or(false, |c| c.borrow().match_tags(&t.tags))));
+        if let Some(index) = shown {
+            self.outputs.focus(index);
+        } else {
+            let tag_stack = &mut self.outputs.current_mut().tag_stack;
+            match tag_stack.index_for_tags(&tags) {
+                Some(index) => tag_stack.push(index),
+                None => {
+                    info!("no tagset shows the urgent client");
+                    return;
+                },
+            }
+        }
+        let current = self.outputs
+            .current()
+            .tag_stack
+            .current()
+            .map(|t| t.tags.clone());
+        if let Some(current) = current {
+            self.clients.focus_window(&current, window);
+        }
+        self.clients.clear_urgent(window);
+        self.arrange_windows();
+        self.reset_focus();
+    }
+
+    /// Make a window cover an area completely, without a border, and raise
+    /// it.
+    ///
+    /// Returns the cookie of the request, to be checked by the caller.
+    fn configure_fullscreen(&self, window: xproto::Window, area: &ScreenSize)
+        -> base::VoidCookie<'a> {
+        xproto::configure_window(
+            self.con, window,
+            &[(xproto::CONFIG_WINDOW_X as u16, area.offset_x as u32),
+              (xproto::CONFIG_WINDOW_Y as u16, area.offset_y as u32),
+              (xproto::CONFIG_WINDOW_WIDTH as u16, area.width as u32),
+              (xproto::CONFIG_WINDOW_HEIGHT as u16, area.height as u32),
+              (xproto::CONFIG_WINDOW_BORDER_WIDTH as u16, 0),
+              (xproto::CONFIG_WINDOW_STACK_MODE as u16,
+               xproto::STACK_MODE_ABOVE as u32)])
+    }
+
+    /// Move the focused client to the tagset shown on the next output.
+    ///
+    /// The client's tags are replaced by the tags of that tagset. Floating
+    /// clients keep their position relative to the output.
+    fn send_to_next_output(&mut self) {
+        let next = self.outputs.next_index();
+        if next == self.outputs.current_index() {
+            return;
+        }
+        let window = match self
+            .outputs
+            .current()
+            .tag_stack
+            .current()
+            .and_then(|t| self.clients.get_focused_window(&t.tags)) {
+            Some(window) => window,
+            None => return,
+        };
+        let (src, dest) = match (self.outputs.current(),
+                                 self.outputs.get(next)) {
+            (src, Some(dest)) => (src, dest),
+            _ => return,
+        };
+        let tags = match dest.tag_stack.current() {
+            Some(tagset) => tagset.tags.clone(),
+            None => return,
+        };
+        let src_screen = self.compute_struts(&src.area);
+        let dest_screen = self.compute_struts(&dest.area);
+        let border_width = self.config.border_width as u16;
+        self.clients.update_client(window, |mut cl| {
+            cl.move_to_output(&tags, &src_screen, &dest_screen, border_width);
+            WmCommand::Redraw
+        });
+        if self.config.follow_sent_client {
+            self.outputs.focus(next);
+            self.clients.focus_window(&tags, window);
+        }
+        self.arrange_windows();
+        self.reset_focus();
+    }
+
+    /// Zoom the focused tiled client, or restore the zoomed one.
+    fn toggle_zoom(&mut self) {
+        let tiled = self.focused_window
+            .and_then(|w| self.clients.get_client_by_window(w))
+            .map_or(false, |c| !c.borrow().is_floating());
+        self.zoomed_window =
+            toggled_zoom(self.zoomed_window, self.focused_window, tiled);
+        self.arrange_windows();
+    }
+
+    /// Compute the geometry the next mapped window would be assigned.
+    ///
+    /// Assumes the window gets shown on the current tagset and is tiled.
+    /// Returns `None` if the window would be hidden by the current layout.
+    pub fn next_window_geometry(&mut self) -> Option<Geometry> {
+        let screen = self.compute_struts(&self.outputs.current().area);
+        let tagset = match self.outputs.current().tag_stack.current() {
+            Some(tagset) => tagset,
+            None => return None,
+        };
+        let clients = self.clients
+            .get_order_or_insert(&tagset.tags)
+            .1
+            .iter()
+            .filter_map(|c| c.upgrade())
+            .collect();
+        tagset.next_geometry(clients, &screen,
+                             self.config.border_width as u16)
+    }
+
+    /// Advertise the actions allowed on a window in its
+    /// `_NET_WM_ALLOWED_ACTIONS` property.
+    fn set_allowed_actions(&self, window: xproto::Window, floating: bool) {
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, window,
+            self.lookup_atom("_NET_WM_ALLOWED_ACTIONS"), xproto::ATOM_ATOM,
+            32, &allowed_actions(floating)
+                .iter()
+                .map(|name| self.lookup_atom(name))
+                .collect::<Vec<_>>()
+        );
+        if cookie.request_check().is_err() {
+            error!("could not set allowed actions");
+        }
+    }
+
+    /// Apply a geometry to a window, optionally raising it.
+    fn set_geometry(&self, window: xproto::Window, geom: &Geometry,
+                    raise: bool) {
+        let cookie = self.configure_geometry(window, geom, raise);
+        if cookie.request_check().is_err() {
+            error!("could not set window geometry");
+        }
+    }
+
+    /// Check a request setting a window's geometry right away, or queue it
+    /// to be checked later if such requests are batched.
+    fn check_configure(&self, cookie: base::VoidCookie<'a>,
+                       queue: &mut Vec<base::VoidCookie<'a>>) {
+        if self.config.batch_configure {
+            queue.push(cookie);
+        } else if cookie.request_check().is_err() {
+            error!("could not set window geometry");
+        }
+    }
+
+    /// Send a request applying a geometry to a window, optionally raising
+    /// it.
+    ///
+    /// Geometries are computed for windows having the configured border
+    /// width, so windows without a border are enlarged accordingly. Returns
+    /// the cookie of the request, to be checked by the caller.
+    fn configure_geometry(&self, window: xproto::Window, geom: &Geometry,
+                          raise: bool) -> base::VoidCookie<'a> {
+        let border = self.border_width(window);
+        let extra = 2 * (self.config.border_width as u16 - border);
+        let mut values =
+            vec![(xproto::CONFIG_WINDOW_X as u16, geom.x as u32),
+                 (xproto::CONFIG_WINDOW_Y as u16, geom.y as u32),
+                 (xproto::CONFIG_WINDOW_WIDTH as u16,
+                  (geom.width + extra) as u32),
+                 (xproto::CONFIG_WINDOW_HEIGHT as u16,
+                  (geom.height + extra) as u32),
+                 (xproto::CONFIG_WINDOW_BORDER_WIDTH as u16, border as u32)];
+        if raise {
+            values.push((xproto::CONFIG_WINDOW_STACK_MODE as u16,
+                         xproto::STACK_MODE_ABOVE as u32));
+        }
+        xproto::configure_window(self.con, window, &values)
+    }
+
+    /// Check whether a window is shown floating, either because its client
+    /// is floating or because the layout leaves it floating.
+    fn is_shown_floating(&self, window: xproto::Window) -> bool {
+        self.scratchpad == Some(window) ||
+            self.layout_floating_windows.contains(&window) ||
+            self.clients
+                .get_client_by_window(window)
+                .map_or(false, |c| c.borrow().is_floating())
+    }
+
+    /// Resize the focused client by moving one of it's edges, if it is
+    /// floating.
+    fn resize_floating(&mut self, dir: Direction, percent: i8) {
+        let window = match self
+            .outputs
+            .current()
+            .tag_stack
+            .current()
+            .and_then(|t| self.clients.get_focused_window(&t.tags)) {
+            Some(window) => window,
+            None => return,
+        };
+        let screen = self.compute_struts(&self.outputs.current().area);
+        let floating = self.is_shown_floating(window);
+        let border_width = self.config.border_width as u16;
+        let resized = self.clients.update_client(window, |mut cl| {
+            if floating {
+                let geom = cl.float_geometry(&screen, border_width);
+                cl.set_float_geometry(
+                    geom.resize_rel(&screen, dir, percent, border_width));
+                WmCommand::Redraw
+            } else {
+                WmCommand::NoCommand
+            }
+        });
+        if let Some(WmCommand::Redraw) = resized {
+            self.arrange_windows();
+        }
+    }
+
+    /// Get the area available for tiling on the output a window is shown on,
+    /// or the current output if it isn't shown.
+    fn window_screen(&self, window: xproto::Window) -> ScreenSize {
+        let area = &self.window_outputs
+            .get(&window)
+            .and_then(|index| self.outputs.get(*index))
+            .unwrap_or(self.outputs.current())
+            .area;
+        self.compute_struts(area)
+    }
+
+    /// Compute the area available for tiling on an output.
+    ///
+    /// Takes the output's area and shrinks it to keep the space reserved by
+    /// dock windows free, unless struts are currently ignored.
+    fn compute_struts(&self, area: &ScreenSize) -> ScreenSize {
+        let struts: Vec<Strut> = self.struts.values().cloned().collect();
+        free_area(area, self.root_size, &struts, self.honor_struts)
+    }
+
+    /// Toggle whether the space reserved by dock windows is kept free.
+    fn toggle_struts(&mut self) {
+        self.honor_struts = !self.honor_struts;
+        self.arrange_windows();
+    }
+
+    /// Hide some windows by unmapping them.
+    ///
+    /// The resulting `UnmapNotify` events are counted as pending, so that
+    /// they aren't mistaken for clients withdrawing their windows.
+    fn hide_windows(&mut self, windows: &[xproto::Window]) {
+        let cookies: Vec<_> = windows
+            .iter()
+            .map(|window| xproto::unmap_window(self.con, *window))
+            .collect();
+        for (window, cookie) in windows.iter().zip(cookies) {
+            if cookie.request_check().is_err() {
+                error!("could not unmap window");
+            } else {
+                *self.pending_unmaps.entry(*window).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Show some windows hidden before by mapping them.
+    fn show_windows(&self, windows: &[xproto::Window]) {
+        let cookies: Vec<_> = windows
+            .iter()
+            .map(|window| xproto::map_window(self.con, *window))
+            .collect();
+        for cookie in cookies {
+            if cookie.request_check().is_err() {
+                error!("could not map window");
+            }
+        }
+    }
+
+    /// Destroy a window.
+    ///
+    /// If the client supports the `WM_DELETE_WINDOW` protocol, send it a
+    /// client message and kill it the hard and merciless way if it doesn't
+    /// close within the grace period. Clients not supporting the protocol,
+    /// or ones we can't send the message to, are killed right away.
+    fn destroy_window(&mut self, window: xproto::Window) {
+        let protocols = self.clients
+            .get_client_by_window(window)
+            .map_or(Vec::new(), |c| c.borrow().props().protocols.clone());
+        let delete_window = self.lookup_atom("WM_DELETE_WINDOW");
+        match CloseAction::for_protocols(&protocols, delete_window) {
+            CloseAction::Delete
+                if self.send_event(window, "WM_DELETE_WINDOW").is_ok() =>
+                self.timers.schedule(
+                    Instant::now(),
+                    Duration::from_secs(self.config.kill_grace),
+                    Timer::KillClient(window)),
+            _ => self.kill_client(window),
+        }
+    }
+
+    /// Kill the client owning a window.
+    fn kill_client(&self, window: xproto::Window) {
+        if xproto::kill_client(self.con, window).request_check().is_err() {
+            error!("could not kill client");
+        }
+    }
+
+    /// Reset focus.
+    ///
+    /// The datastructures have been altered, we need to focus the appropriate
+    /// window as obtained from tehre. if an old window is given, uncolor it's
+    /// border.
+    fn reset_focus(&mut self) {
+        // a shown scratchpad keeps the focus until it is hidden again
+        let scratchpad = if self.scratchpad_shown {
+            self.scratchpad
+        } else {
+            None
+        };
+        if let Some(new) = scratchpad.or_else(|| self
+            .outputs
+            .current()
+            .tag_stack
+            .current()
+            .and_then(|t| self.clients.get_focused_window(&t.tags))) {
+            // the focused client stays the same, even if its position
+            // changes. only rearrange if it does, so that we don't generate
+            // spurious pointer events moving the focus away again.
+            let surfaced = scratchpad.is_none() &&
+                match self.outputs.current().tag_stack.current() {
+                    Some(tagset) => self.clients.surface_focused(tagset),
+                    None => false,
+                };
+            if surfaced {
+                self.arrange_windows();
+            }
+            // a zoomed window is restored as soon as it loses focus
+            if self.zoomed_window.map_or(false, |w| w != new) {
+                self.zoomed_window = None;
+                self.arrange_windows();
+            }
+            // floating clients might be obscured by others
+            if self.is_shown_floating(new) {
+                self.raise_window(new);
+            }
+            if let Some(old_win) = self.focused_window {
+                self.set_border_color(old_win, self.border_colors.1);
+                if self.config.border_on_focus_only && old_win != new {
+                    let width = self.config.border_width_for(
+                        self.borderless_windows.contains(&old_win), false);
+                    self.set_border_width(old_win, width);
+                }
+            }
+            if self.config.border_on_focus_only {
+                let width = self.config.border_width_for(
+                    self.borderless_windows.contains(&new), true);
+                self.set_border_width(new, width);
+            }
+            // clients that don't want input, but take the focus themselves,
+            // are only asked to do so
+            let accepts_input = self.clients
+                .get_client_by_window(new)
+                .map_or(true, |c| c.borrow().props().accepts_input);
+            if self.supports_protocol(new, "WM_TAKE_FOCUS") &&
+               self.send_event(new, "WM_TAKE_FOCUS").is_err() {
+                info!("could not send focus message to window");
+            }
+            let focused = !accepts_input || xproto::set_input_focus(
+                self.con, xproto::INPUT_FOCUS_POINTER_ROOT as u8, new,
+                xproto::TIME_CURRENT_TIME).request_check().is_ok();
+            self.set_border_color(new, self.border_colors.0);
+            if focused && scratchpad.is_none() {
+                // remember the client to return to it once others are gone
+                let tags = self.outputs
+                    .current()
+                    .tag_stack
+                    .current()
+                    .map(|t| t.tags.clone());
+                if let Some(tags) = tags {
+                    self.clients.record_focus(&tags, new);
+                }
+            }
+            if !focused {
+                error!("could not focus window");
+            } else if self.focused_window != Some(new) {
+                self.focused_window = Some(new);
+                self.set_active_window(new);
+            }
+        } else if let Some(old_win) = self.focused_window {
+            // nothing left to focus
+            if self.clients.get_client_by_window(old_win).is_some() {
+                self.set_border_color(old_win, self.border_colors.1);
+            }
+            self.focused_window = None;
+            self.set_active_window(xproto::WINDOW_NONE);
+        }
+    }
+
+    /// Publish the focused window in the `_NET_ACTIVE_WINDOW` property of
+    /// the root window, `WINDOW_NONE` meaning no window is focused.
+    fn set_active_window(&self, window: xproto::Window) {
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, self.root,
+            self.lookup_atom("_NET_ACTIVE_WINDOW"), xproto::ATOM_WINDOW, 32,
+            &[window]
+        );
+        if cookie.request_check().is_err() {
+            error!("could not set active window");
+        }
+    }
+
+    /// Get the border width a window is supposed to have.
+    fn border_width(&self, window: xproto::Window) -> u16 {
+        self.config.border_width_for(self.borderless_windows.contains(&window),
+                                     self.focused_window == Some(window))
+    }
+
+    /// Set the border width of a window, keeping it's outer size.
+    fn set_border_width(&self, window: xproto::Window, width: u16) {
+        let geom = match xproto::get_geometry(self.con, window).get_reply() {
+            Ok(geom) => geom,
+            Err(_) => {
+                error!("could not get window geometry");
+                return;
+            },
+        };
+        if geom.border_width() == width {
+            return;
+        }
+        let outer_width = geom.width() + 2 * geom.border_width();
+        let outer_height = geom.height() + 2 * geom.border_width();
+        let cookie = xproto::configure_window(
+            self.con, window,
+            &[(xproto::CONFIG_WINDOW_WIDTH as u16,
+               outer_width.saturating_sub(2 * width) as u32),
+              (xproto::CONFIG_WINDOW_HEIGHT as u16,
+               outer_height.saturating_sub(2 * width) as u32),
+              (xproto::CONFIG_WINDOW_BORDER_WIDTH as u16, width as u32)]);
+        if cookie.request_check().is_err() {
+            error!("could not set window border width");
+        }
+    }
+
+    /// Color the borders of a window.
+    fn set_border_color(&self, window: xproto::Window, color: u32) {
+        let cookie = xproto::change_window_attributes(
+            self.con, window, &[(xproto::CW_BORDER_PIXEL, color)]);
+        if cookie.request_check().is_err() {
+            error!("could not set window border color");
+        }
+    }
+
+    /// Wait for events, handle them. Repeat.
+    ///
+    /// Besides events from the X server, requests on the IPC socket are
+    /// handled as well, so we wait on both file descriptors.
+    pub fn run(&mut self) -> Result<(), WmError> {
+        let x_fd = unsafe {
+            xcb_get_file_descriptor(self.con.get_raw_conn())
+        };
+        unsafe {
+            // don't pass the X connection on to spawned programs
+            libc::fcntl(x_fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            // let the kernel reap spawned programs, so no zombies remain.
+            // spawned programs get the default disposition back, see
+            // `detach`
+            libc::signal(libc::SIGCHLD, libc::SIG_IGN);
+        }
+        // start waiting for the idle timeout
+        self.note_activity();
+        loop {
+            self.con.flush();
+            if let Err(_) = self.con.has_error() {
+                return Err(WmError::ConnectionInterrupted);
+            }
+            // handle all timers that are due
+            for timer in self.timers.pop_due(Instant::now()) {
+                self.handle_timer(timer);
+            }
+            // handle all events already queued
+            if let Some(ev) = self.con.poll_for_event() {
+                self.handle(ev);
+                continue;
+            }
+            // wait until the next timer is due at most
+            let timeout = self.timers
+                .timeout(Instant::now())
+                .map_or(-1, |t| cmp::min(millis(t),
+                                         libc::c_int::max_value() as u64)
+                    as libc::c_int);
+            let mut fds = vec![
+                libc::pollfd { fd: x_fd, events: libc::POLLIN, revents: 0 }
+            ];
+            if let Some(ref ipc) = self.ipc {
+                fds.push(
+                    libc::pollfd { fd: ipc.fd(), events: libc::POLLIN,
+                                   revents: 0 });
+            }
+            let res = unsafe {
+                libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t,
+                           timeout)
+            };
+            if res < 0 {
+                if io::Error::last_os_error().kind() ==
+                   io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(WmError::IOError);
+            }
+            if fds.len() > 1 && fds[1].revents & libc::POLLIN != 0 {
+                self.handle_ipc();
+            }
+        }
+    }
+
+    /// Run the command configured for a tag that became empty, if any.
+    fn autostart(&mut self, tag: &Tag) {
+        if let Some(command) =
+                autostart_command(&self.config.autostart_on_empty,
+                                  &mut self.last_autostart,
+                                  Instant::now(), tag) {
+            self.spawn_shell(command);
+        }
+    }
+
+    /// Run a shell command in the background.
+    fn spawn_shell(&self, command: &str) {
+        let mut shell = Command::new("sh");
+        if detach(shell.arg("-c").arg(command)).spawn().is_err() {
+            error!("could not run command: {}", command);
+        }
+    }
+
+    /// Run a program with arguments in the background.
+    ///
+    /// The first element of `args` is the program to run. Children are
+    /// reaped automatically, as `SIGCHLD` is ignored.
+    fn spawn(&self, args: &[String]) {
+        if let Some((program, rest)) = args.split_first() {
+            let mut command = Command::new(program);
+            if detach(command.args(rest)).spawn().is_err() {
+                error!("could not run program: {}", program);
+            }
+        } else {
+            error!("no program to run given");
+        }
+    }
+
+    /// Restart the idle timeout after user input, ending the idle state.
+    fn note_activity(&mut self) {
+        if restart_idle(&mut self.timers, Instant::now(),
+                        self.config.idle_timeout) && self.idle {
+            self.idle = false;
+            if let Some(window) = self.focused_window {
+                self.set_border_color(window, self.border_colors.0);
+            }
+        }
+    }
+
+    /// Handle a timer that is due.
+    fn handle_timer(&mut self, timer: Timer) {
+        match timer {
+            Timer::FocusDwell(window) => self.focus_client(window),
+            Timer::KillClient(window) =>
+                if self.clients.get_client_by_window(window).is_some() {
+                    info!("client didn't close in time, killing it");
+                    self.kill_client(window);
+                },
+            Timer::MapCheck(window) =>
+                if self.unconfirmed_maps.remove(&window) &&
+                   self.clients.get_client_by_window(window).is_some() {
+                    info!("window didn't show up after mapping, dropping it");
+                    self.drop_client(window);
+                },
+            Timer::Idle => {
+                self.idle = true;
+                if let Some(window) = self.focused_window {
+                    self.set_border_color(window, self.border_colors.1);
+                }
+            },
+            Timer::MasterFactor(factor) => {
+                self.set_master_factor(factor);
+                self.arrange_windows();
+            },
+            Timer::UpdateOutputs => self.update_outputs(),
+        }
+    }
+
+    /// Handle all pending connections on the IPC socket.
+    fn handle_ipc(&mut self) {
+        loop {
+            let mut connection =
+                match self.ipc.as_ref().and_then(|i| i.accept()) {
+                    Some(connection) => connection,
+                    None => return,
+                };
+            let response = match connection
+                .read_line()
+                .ok_or("could not read request".to_owned())
+                .and_then(|line| IpcRequest::parse(&line)) {
+                Ok(request) => self.handle_ipc_request(request),
+                Err(e) => format!("error: {}\n", e),
+            };
+            connection.respond(&response);
+        }
+    }
+
+    /// Handle a request received over the IPC socket.
+    ///
+    /// Returns the response to send, see the `ipc` module for the format.
+    fn handle_ipc_request(&mut self, request: IpcRequest) -> String {
+        match request {
+            IpcRequest::QueryUrgent => {
+                let mut res = String::new();
+                for client in self.clients.urgent_clients() {
+                    res.push_str(&format_client(&client.borrow()));
+                }
+                res.push_str("ok\n");
+                res
+            },
+            IpcRequest::ClearUrgent(window) => {
+                if self.clients.clear_urgent(window) {
+                    "ok\n".to_owned()
+                } else {
+                    "error: no such client\n".to_owned()
+                }
+            },
+            IpcRequest::QueryNextGeometry =>
+                match self.next_window_geometry() {
+                    Some(g) => format!("{} {} {} {}\nok\n",
+                                       g.x, g.y, g.width, g.height),
+                    None => "ok\n".to_owned(),
+                },
+            IpcRequest::QueryTags => {
+                let mut res = String::new();
+                for index in 0..self.outputs.len() {
+                    let output = self.outputs.get(index).unwrap();
+                    res.push_str(&output.name);
+                    if let Some(tagset) = output.tag_stack.current() {
+                        for tag in self.config.display_order(&tagset.tags) {
+                            res.push_str(&format!(" {:?}", tag));
+                        }
+                    }
+                    res.push('\n');
+                }
+                res.push_str("ok\n");
+                res
+            },
+            IpcRequest::QueryScreens => {
+                let mut res = String::new();
+                for index in 0..self.outputs.len() {
+                    let output = self.outputs.get(index).unwrap();
+                    let area = self.compute_struts(&output.area);
+                    res.push_str(&format_screen(&output.name, &area));
+                }
+                res.push_str("ok\n");
+                res
+            },
+            IpcRequest::ReloadMatching(retag) => {
+                self.replace_matching(
+                    generate_rules(), generate_matching(), retag);
+                "ok\n".to_owned()
+            },
+            IpcRequest::FocusNext |
+            IpcRequest::FocusPrev |
+            IpcRequest::FocusDirection(_) => {
+                if let Some(tagset) =
+                        self.outputs.current().tag_stack.current() {
+                    let clients = &mut self.clients;
+                    match request {
+                        IpcRequest::FocusNext => clients.focus_next(tagset),
+                        IpcRequest::FocusPrev => clients.focus_prev(tagset),
+                        IpcRequest::FocusDirection(Direction::Left) =>
+                            clients.focus_left(tagset),
+                        IpcRequest::FocusDirection(Direction::Right) =>
+                            clients.focus_right(tagset),
+                        IpcRequest::FocusDirection(Direction::Top) =>
+                            clients.focus_top(tagset),
+                        IpcRequest::FocusDirection(Direction::Bottom) =>
+                            clients.focus_bottom(tagset),
+                        _ => false,
+                    };
+                }
+                self.reset_focus();
+                "ok\n".to_owned()
+            },
+            IpcRequest::ViewTagset(index) => {
+                {
+                    let stack = &mut self.outputs.current_mut().tag_stack;
+                    if stack.current_index() != Some(&index) {
+                        stack.push(index);
+                    }
+                    if stack.current_index() != Some(&index) {
+                        return "error: no such tagset\n".to_owned();
+                    }
+                }
+                self.arrange_windows();
+                self.reset_focus();
+                "ok\n".to_owned()
+            },
+            IpcRequest::SetLayout(name) => {
+                let layout = match layout_by_name(&name) {
+                    Some(layout) => layout,
+                    None => return format!("error: unknown layout: {}\n",
+                                           name),
+                };
+                let changed = match self
+                    .outputs
+                    .current_mut()
+                    .tag_stack
+                    .current_mut() {
+                    Some(tagset) => tagset.set_boxed_layout(layout),
+                    None => return "error: no tagset shown\n".to_owned(),
+                };
+                if changed {
+                    self.arrange_windows();
+                    self.reset_focus();
+                    "ok\n".to_owned()
+                } else {
+                    "error: layout is locked\n".to_owned()
+                }
+            },
+            IpcRequest::QueryTabs => {
+                let tiled = self.tiled_windows();
+                let screen =
+                    self.compute_struts(&self.outputs.current().area);
+                let tabs = match self.outputs.current().tag_stack.current() {
+                    Some(tagset) => tagset
+                        .layout_for(tiled.len())
+                        .tabs(tiled.len(), &screen),
+                    None => Vec::new(),
+                };
+                let mut res = String::new();
+                for (window, g) in tiled.iter().zip(tabs.iter()) {
+                    res.push_str(&format!(
+                        "{} {} {} {} {}\n", format_window(*window),
+                        g.x, g.y, g.width, g.height));
+                }
+                res.push_str("ok\n");
+                res
+            },
+            IpcRequest::QueryStatus =>
+                format!("{}\nok\n", self.status_string()),
+            IpcRequest::ListClients => {
+                let mut res = String::new();
+                for window in self.clients.windows() {
+                    let client = self.clients.get_client_by_window(window);
+                    if let Some(client) = client {
+                        res.push_str(&format_client(&client.borrow()));
+                    }
+                }
+                res.push_str("ok\n");
+                res
+            },
+        }
+    }
+
+    /// Handle an event received from the X server.
+    fn handle(&mut self, event: base::GenericEvent) {
+        match event.response_type() {
+            xkb::STATE_NOTIFY | xproto::BUTTON_PRESS |
+            xproto::ENTER_NOTIFY => self.note_activity(),
+            _ => (),
+        }
+        match event.response_type() {
+            xkb::STATE_NOTIFY =>
+                self.handle_state_notify(base::cast_event(&event)),
+            xproto::PROPERTY_NOTIFY =>
+                self.handle_property_notify(base::cast_event(&event)),
+            xproto::CLIENT_MESSAGE =>
+                self.handle_client_message(base::cast_event(&event)),
+            xproto::DESTROY_NOTIFY =>
+                self.handle_destroy_notify(base::cast_event(&event)),
+            xproto::CONFIGURE_REQUEST =>
+                self.handle_configure_request(base::cast_event(&event)),
+            xproto::MAP_REQUEST =>
+                self.handle_map_request(base::cast_event(&event)),
+            xproto::MAP_NOTIFY =>
+                self.handle_map_notify(base::cast_event(&event)),
+            xproto::UNMAP_NOTIFY =>
+                self.handle_unmap_notify(base::cast_event(&event)),
+            xproto::BUTTON_PRESS =>
+                self.handle_button_press(base::cast_event(&event)),
+            xproto::ENTER_NOTIFY =>
+                self.handle_enter_notify(base::cast_event(&event)),
+            xproto::LEAVE_NOTIFY =>
+                self.handle_leave_notify(base::cast_event(&event)),
+            xproto::MOTION_NOTIFY =>
+                self.handle_motion_notify(base::cast_event(&event)),
+            xproto::BUTTON_RELEASE =>
+                self.handle_button_release(base::cast_event(&event)),
+            num if self.randr_base ==
+                    Some(num.wrapping_sub(randr::SCREEN_CHANGE_NOTIFY)) => {
+                // wait for the configuration to settle
+                self.timers.cancel(|t| *t == Timer::UpdateOutputs);
+                self.timers.schedule(
+                    Instant::now(), Duration::from_millis(OUTPUT_UPDATE_DELAY),
+                    Timer::UpdateOutputs);
+            },
+            num => debug!("ignoring event: {}", num),
+        }
+    }
+
+    /// A key has been pressed, react accordingly.
+    ///
+    /// Look for a matching key binding upon event receival and call a callback
+    /// closure if necessary. Determine what to do next based on the
     /// return value received.
     fn handle_state_notify(&mut self, ev: &xkb::StateNotifyEvent) {
         let key = from_key(ev, self.mode);
         let mut command = WmCommand::NoCommand;
-        if let Some(func) = self.bindings.get(&key) {
-            command = func(&mut self.clients, &mut self.tag_stack);
-        } else if let Some(func) = self.plugins.get(&key) {
-            func(&self.con);
+        let old_factor = self.current_master_factor();
+        match handler_for(&key, &self.bindings, &self.plugins,
+                          self.fallback.as_ref()) {
+            Some(KeyHandler::Binding(func)) =>
+                command = func(&mut self.clients,
+                               &mut self.outputs.current_mut().tag_stack),
+            Some(KeyHandler::Plugin(func)) => func(&self.con),
+            Some(KeyHandler::Fallback(func)) =>
+                command = func(key, &mut self.clients,
+                               &mut self.outputs.current_mut().tag_stack),
+            None => (),
         }
         match command {
             WmCommand::Redraw => {
+                self.animate_master_factor(old_factor);
                 self.arrange_windows();
                 self.reset_focus();
             },
             WmCommand::Focus => self.reset_focus(),
             WmCommand::Kill(win) => self.destroy_window(win),
-            WmCommand::ModeSwitch(mode) => self.mode = mode,
+            WmCommand::ResizeFloating(dir, percent) =>
+                self.resize_floating(dir, percent),
+            WmCommand::ToggleStruts => self.toggle_struts(),
+            WmCommand::CycleTagset(offset) =>
+                if self.outputs.current_mut().tag_stack.cycle(offset) {
+                    self.arrange_windows();
+                    self.reset_focus();
+                },
+            WmCommand::FocusNextOutput => {
+                let next = self.outputs.next_index();
+                if self.outputs.focus(next) {
+                    self.reset_focus();
+                }
+            },
+            WmCommand::SendToNextOutput => self.send_to_next_output(),
+            WmCommand::ToggleZoom => self.toggle_zoom(),
+            WmCommand::MarkScratchpad => self.mark_scratchpad(),
+            WmCommand::ToggleScratchpad => self.toggle_scratchpad(),
+            WmCommand::FocusUrgent => self.focus_urgent(),
+            WmCommand::Rebuild => self.rebuild_clients(),
+            WmCommand::Spawn(args) => self.spawn(&args),
+            WmCommand::ModeSwitch(mode) => self.switch_mode(mode),
             WmCommand::Quit => exit(0),
             WmCommand::NoCommand => (),
         };
     }
 
-    // TODO: implement
-    fn handle_property_notify(&self, _: &xproto::PropertyNotifyEvent) {
-        ()
+    /// Get the master factor of the current tagset's layout, if any.
+    fn current_master_factor(&self) -> Option<u8> {
+        self.outputs
+            .current()
+            .tag_stack
+            .current()
+            .and_then(|t| t.current_layout().master_factor())
+    }
+
+    /// Set the master factor of the current tagset's layout.
+    fn set_master_factor(&mut self, factor: u8) {
+        if let Some(tagset) =
+                self.outputs.current_mut().tag_stack.current_mut() {
+            tagset
+                .current_layout_mut()
+                .edit_layout(LayoutMessage::MasterFactorAbs(factor));
+        }
+    }
+
+    /// Animate a change of the current layout's master factor, if enabled.
+    ///
+    /// The layout is set back to the previous factor, and moved towards the
+    /// new one over the configured number of frames. An animation still
+    /// running is replaced.
+    fn animate_master_factor(&mut self, old_factor: Option<u8>) {
+        let steps = self.config.animation_steps;
+        let (from, to) = match (old_factor, self.current_master_factor()) {
+            (Some(from), Some(to)) if steps > 0 && from != to => (from, to),
+            _ => return,
+        };
+        self.timers.cancel(|t| match *t {
+            Timer::MasterFactor(_) => true,
+            _ => false,
+        });
+        self.set_master_factor(from);
+        let now = Instant::now();
+        for step in 1..steps as u64 + 1 {
+            let factor = interpolate_factor(from, to, step as u8, steps);
+            self.timers.schedule(
+                now,
+                Duration::from_millis(self.config.animation_interval * step),
+                Timer::MasterFactor(factor));
+        }
+    }
+
+    /// A window's property has changed, react accordingly.
+    ///
+    /// Currently, the urgency hint and the title of managed windows are
+    /// tracked.
+    fn handle_property_notify(&mut self, ev: &xproto::PropertyNotifyEvent) {
+        if ev.atom() == xproto::ATOM_WM_NAME ||
+           ev.atom() == self.lookup_atom("_NET_WM_NAME") {
+            let window = ev.window();
+            if self.clients.get_client_by_window(window).is_some() {
+                if let Some(name) = self.get_name(window) {
+                    // the title doesn't influence the layout
+                    self.clients.update_client(window, |mut cl| {
+                        cl.set_name(name.clone());
+                        WmCommand::NoCommand
+                    });
+                }
+            }
+        } else if ev.atom() == xproto::ATOM_WM_NORMAL_HINTS {
+            let window = ev.window();
+            if self.clients.get_client_by_window(window).is_some() {
+                let size_hints = self.get_size_hints(window);
+                self.clients.update_client(window, |mut cl| {
+                    cl.set_size_hints(size_hints.clone());
+                    WmCommand::Redraw
+                });
+                if self.visible_windows.contains(&window) {
+                    self.arrange_windows();
+                }
+            }
+        } else if ev.atom() == xproto::ATOM_WM_HINTS {
+            let window = ev.window();
+            if self.clients.get_client_by_window(window).is_some() {
+                if self.get_urgency(window) {
+                    self.clients.mark_urgent(window);
+                } else {
+                    self.clients.clear_urgent(window);
+                }
+            }
+        }
+    }
+
+    /// A client has sent a message, react accordingly.
+    ///
+    /// Currently, only changes of `_NET_WM_STATE` are handled.
+    fn handle_client_message(&mut self, ev: &xproto::ClientMessageEvent) {
+        if ev.type_() == self.lookup_atom("_NET_WM_STATE") &&
+           ev.format() == 32 {
+            self.change_net_wm_state(ev.window(), ev.data().data32());
+        }
+    }
+
+    /// Apply a change of `_NET_WM_STATE` requested by a client.
+    ///
+    /// Fullscreen mode and vertical and horizontal maximization of floating
+    /// clients are supported. The first value of `data` is the action to
+    /// perform, the next two are the states to change.
+    fn change_net_wm_state(&mut self, window: xproto::Window, data: &[u32]) {
+        let (fullscreen, floating, (vert, horz)) =
+            match self.clients.get_client_by_window(window) {
+                Some(client) => {
+                    let client = client.borrow();
+                    (client.is_fullscreen(), client.is_floating(),
+                     client.maximized())
+                },
+                None => return,
+            };
+        let apply = |current: bool, atom: xproto::Atom|
+            if data[1] == atom || data[2] == atom {
+                match data[0] {
+                    0 => false, // _NET_WM_STATE_REMOVE
+                    1 => true, // _NET_WM_STATE_ADD
+                    2 => !current, // _NET_WM_STATE_TOGGLE
+                    _ => current,
+                }
+            } else {
+                current
+            };
+        let new_fullscreen =
+            apply(fullscreen, self.lookup_atom("_NET_WM_STATE_FULLSCREEN"));
+        // maximization only applies to floating clients
+        let (new_vert, new_horz) = if floating {
+            (apply(vert, self.lookup_atom("_NET_WM_STATE_MAXIMIZED_VERT")),
+             apply(horz, self.lookup_atom("_NET_WM_STATE_MAXIMIZED_HORZ")))
+        } else {
+            (vert, horz)
+        };
+        if new_fullscreen == fullscreen &&
+           (new_vert, new_horz) == (vert, horz) {
+            return;
+        }
+        let screen = self.window_screen(window);
+        let border_width = self.config.border_width as u16;
+        self.clients.update_client(window, |mut cl| {
+            cl.set_fullscreen(new_fullscreen);
+            if cl.maximized() != (new_vert, new_horz) {
+                cl.set_maximized(new_vert, new_horz, &screen, border_width);
+            }
+            WmCommand::Redraw
+        });
+        self.set_net_wm_state(window);
+        self.arrange_windows();
     }
 
-    // TODO: implement
-    fn handle_client_message(&self, _: &xproto::ClientMessageEvent) {
-        ()
+    /// Set the `_NET_WM_STATE` of a window to reflect the state of it's
+    /// client.
+    fn set_net_wm_state(&self, window: xproto::Window) {
+        let (fullscreen, (vert, horz)) =
+            match self.clients.get_client_by_window(window) {
+                Some(client) => {
+                    let client = client.borrow();
+                    (client.is_fullscreen(), client.maximized())
+                },
+                None => return,
+            };
+        let mut states = Vec::new();
+        if fullscreen {
+            states.push(self.lookup_atom("_NET_WM_STATE_FULLSCREEN"));
+        }
+        if vert {
+            states.push(self.lookup_atom("_NET_WM_STATE_MAXIMIZED_VERT"));
+        }
+        if horz {
+            states.push(self.lookup_atom("_NET_WM_STATE_MAXIMIZED_HORZ"));
+        }
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, window,
+            self.lookup_atom("_NET_WM_STATE"), xproto::ATOM_ATOM, 32, &states
+        );
+        if cookie.request_check().is_err() {
+            error!("could not set window state");
+        }
     }
 
     /// A window has been destroyed, react accordingly.
@@ -449,9 +2504,30 @@ impl<'a> Wm<'a> {
     /// If the window is managed (i.e. has a client), destroy it. Otherwise,
     /// remove it from the vector of unmanaged windows.
     fn handle_destroy_notify(&mut self, ev: &xproto::DestroyNotifyEvent) {
+        self.timers.cancel(|t| t.concerns(ev.window()));
+        self.unconfirmed_maps.remove(&ev.window());
+        self.pending_unmaps.remove(&ev.window());
+        self.published_desktops.remove(&ev.window());
+        if self.zoomed_window == Some(ev.window()) {
+            self.zoomed_window = None;
+        }
+        if self.scratchpad == Some(ev.window()) {
+            self.scratchpad = None;
+        }
+        if self.drag.as_ref().map_or(false, |d| d.window == ev.window()) {
+            self.drag = None;
+        }
+        let tags = self.clients.tags_of(ev.window()).unwrap_or_default();
+        self.notify_client_removed(ev.window());
         self.clients.remove(ev.window());
+        self.update_client_list();
         self.reset_focus();
         self.arrange_windows();
+        for tag in tags {
+            if self.clients.is_tag_empty(&tag) {
+                self.autostart(&tag);
+            }
+        }
         if let Some(index) = self
             .unmanaged_windows
             .iter()
@@ -459,40 +2535,210 @@ impl<'a> Wm<'a> {
             self.unmanaged_windows.swap_remove(index);
             info!("unregistered unmanaged window");
         }
+        if self.struts.remove(&ev.window()).is_some() {
+            self.arrange_windows();
+        }
+    }
+
+    /// A client wants to change its geometry, react accordingly.
+    ///
+    /// Windows we don't manage get their request granted as is. Floating
+    /// clients have their floating geometry adjusted, while tiled clients
+    /// are only told their current geometry, as it is up to the layout.
+    fn handle_configure_request(&mut self,
+                                ev: &xproto::ConfigureRequestEvent) {
+        let window = ev.window();
+        let floating = self.clients
+            .get_client_by_window(window)
+            .map(|_| self.is_shown_floating(window));
+        match floating {
+            None => self.grant_configure_request(ev),
+            Some(true) => {
+                let mask = ev.value_mask();
+                let screen = self.window_screen(window);
+                let border_width = self.config.border_width as u16;
+                self.clients.update_client(window, |mut cl| {
+                    let mut geom = cl.float_geometry(&screen, border_width);
+                    if mask & xproto::CONFIG_WINDOW_X as u16 != 0 {
+                        geom.x = cmp::max(0, ev.x()) as u16;
+                    }
+                    if mask & xproto::CONFIG_WINDOW_Y as u16 != 0 {
+                        geom.y = cmp::max(0, ev.y()) as u16;
+                    }
+                    if mask & xproto::CONFIG_WINDOW_WIDTH as u16 != 0 {
+                        geom.width = ev.width();
+                    }
+                    if mask & xproto::CONFIG_WINDOW_HEIGHT as u16 != 0 {
+                        geom.height = ev.height();
+                    }
+                    cl.set_float_geometry(geom);
+                    WmCommand::Redraw
+                });
+                self.arrange_windows();
+                self.send_configure_notify(window);
+            },
+            Some(false) => self.send_configure_notify(window),
+        }
+    }
+
+    /// Apply the changes requested in a `ConfigureRequestEvent` as is.
+    fn grant_configure_request(&self, ev: &xproto::ConfigureRequestEvent) {
+        let mask = ev.value_mask();
+        // the values need to be ordered like the bits of the mask
+        let requested = [
+            (xproto::CONFIG_WINDOW_X, ev.x() as i32 as u32),
+            (xproto::CONFIG_WINDOW_Y, ev.y() as i32 as u32),
+            (xproto::CONFIG_WINDOW_WIDTH, ev.width() as u32),
+            (xproto::CONFIG_WINDOW_HEIGHT, ev.height() as u32),
+            (xproto::CONFIG_WINDOW_BORDER_WIDTH, ev.border_width() as u32),
+            (xproto::CONFIG_WINDOW_SIBLING, ev.sibling()),
+            (xproto::CONFIG_WINDOW_STACK_MODE, ev.stack_mode() as u32),
+        ];
+        let values: Vec<(u16, u32)> = requested
+            .iter()
+            .filter(|&&(bit, _)| mask & bit as u16 != 0)
+            .map(|&(bit, value)| (bit as u16, value))
+            .collect();
+        let cookie = xproto::configure_window(self.con, ev.window(), &values);
+        if cookie.request_check().is_err() {
+            error!("could not configure window");
+        }
+    }
+
+    /// Tell a client about its current geometry using a synthetic
+    /// `ConfigureNotifyEvent`.
+    fn send_configure_notify(&self, window: xproto::Window) {
+        self.send_configure_notifies(&[window]);
+    }
+
+    /// Tell a set of clients about their current geometries using synthetic
+    /// `ConfigureNotifyEvent`s.
+    ///
+    /// The geometries are requested for all windows at once to avoid a
+    /// round trip per window.
+    fn send_configure_notifies(&self, windows: &[xproto::Window]) {
+        let cookies: Vec<_> = windows
+            .iter()
+            .map(|window| (*window, xproto::get_geometry(self.con, *window)))
+            .collect();
+        let mut sent = Vec::with_capacity(cookies.len());
+        for (window, cookie) in cookies {
+            let geom = match cookie.get_reply() {
+                Ok(geom) => geom,
+                Err(_) => {
+                    error!("could not get window geometry");
+                    continue;
+                },
+            };
+            let event = xproto::ConfigureNotifyEvent::new(
+                window, window, xproto::WINDOW_NONE, geom.x(), geom.y(),
+                geom.width(), geom.height(), geom.border_width(), false);
+            sent.push(xproto::send_event(
+                self.con, false, window, xproto::EVENT_MASK_STRUCTURE_NOTIFY,
+                &event));
+        }
+        for cookie in sent {
+            if cookie.request_check().is_err() {
+                error!("could not send configure notification");
+            }
+        }
     }
 
-    // TODO: implement
-    fn handle_configure_request(&self, _: &xproto::ConfigureRequestEvent) {
-        ()
-    }
-
     /// A client has sent a map request, react accordingly.
     ///
     /// Add the window to the necessary structures if it is not yet known and
     /// all prerequisitory conditions are met.
     fn handle_map_request(&mut self, ev: &xproto::MapRequestEvent) {
         let window = ev.window();
-        // no client corresponding to the window, add it
-        if self.clients.get_client_by_window(window).is_none() {
-            if let Some(client) = self.construct_client(window) {
-                // map window
-                let cookie = xproto::map_window(self.con, window);
+        let minimized = self.clients
+            .get_client_by_window(window)
+            .map(|c| c.borrow().is_minimized());
+        if minimized == Some(true) {
+            // a minimized client asks to be shown again
+            self.clients
+                .update_client(window, |mut c| {
+                    c.set_minimized(false);
+                    WmCommand::Redraw
+                });
+            let cookie = xproto::map_window(self.con, window);
+            self.set_wm_state(window, NORMAL_STATE);
+            self.visible_windows.push(window);
+            self.arrange_windows();
+            self.reset_focus();
+            if cookie.request_check().is_err() {
+                error!("could not map window");
+            }
+        } else if minimized.is_none() {
+            // no client corresponding to the window, add it to the tagset
+            // shown on the output under the pointer
+            let old_output = self.outputs.current_index();
+            self.focus_pointer_output();
+            let client = self.construct_client(window);
+            if client.is_none() {
+                self.outputs.focus(old_output);
+            }
+            if let Some(client) = client {
+                if client.is_minimized() {
+                    // the client asked to start iconified, don't show it
+                    self.set_wm_state(window, ICONIC_STATE);
+                    self.add_client(client);
+                    return;
+                }
+                let shown = self.outputs
+                    .current()
+                    .tag_stack
+                    .current()
+                    .and_then(|t| if client.match_tags(&t.tags) {
+                        Some(t.tags.clone())
+                    } else {
+                        None
+                    });
+                let old_focus = shown
+                    .as_ref()
+                    .and_then(|tags| self.clients.get_focused_window(tags));
+                let focus = self
+                    .config
+                    .focus_on_map
+                    .new_client_focus(&client, shown.is_some());
+                // map window, a window we can't map isn't managed at all
+                let con = self.con;
+                if !map_new_window(&mut self.outputs, old_output, || {
+                    xproto::map_window(con, window).request_check().is_ok()
+                }) {
+                    error!("could not map window");
+                    return;
+                }
                 // set border width
                 let cookie2 = xproto::configure_window(self.con, window,
                     &[(xproto::CONFIG_WINDOW_BORDER_WIDTH as u16,
                        self.config.border_width as u32)]);
+                if shown.is_some() {
+                    self.apply_rule_layout(client.props());
+                }
                 self.add_client(client);
+                match focus {
+                    NewClientFocus::Focus => (),
+                    NewClientFocus::KeepFocus => {
+                        if let (Some(tags), Some(old)) = (shown, old_focus) {
+                            self.clients.focus_window(&tags, old);
+                        }
+                    },
+                    NewClientFocus::MarkUrgent =>
+                        self.clients.mark_urgent(window),
+                }
+                // drop the client if the window doesn't show up
+                self.unconfirmed_maps.insert(window);
+                self.timers.schedule(Instant::now(),
+                                     Duration::from_millis(MAP_TIMEOUT),
+                                     Timer::MapCheck(window));
                 self.visible_windows.push(window);
                 self.arrange_windows();
                 self.reset_focus();
-                if cookie.request_check().is_err() {
-                    error!("could not map window");
-                }
                 if cookie2.request_check().is_err() {
                     error!("could not set border width");
                 }
             } else {
-                // it's a dock window - we don't care
+                // it's a dock, desktop or popup window - we don't care
                 let cookie = xproto::map_window(self.con, window);
                 self.add_unmanaged(window);
                 if cookie.request_check().is_err() {
@@ -502,10 +2748,276 @@ impl<'a> Wm<'a> {
         }
     }
 
+    /// A window has been mapped, react accordingly.
+    ///
+    /// Confirms that a window mapped on a client's request actually shows
+    /// up.
+    fn handle_map_notify(&mut self, ev: &xproto::MapNotifyEvent) {
+        if self.unconfirmed_maps.remove(&ev.window()) {
+            self.timers.cancel(|t| *t == Timer::MapCheck(ev.window()));
+        }
+    }
+
+    /// A window has been unmapped, react accordingly.
+    ///
+    /// Unmaps we requested to hide windows are expected, otherwise the
+    /// client withdrew its window and is dropped.
+    fn handle_unmap_notify(&mut self, ev: &xproto::UnmapNotifyEvent) {
+        let window = ev.window();
+        if let Some(count) = self.pending_unmaps.remove(&window) {
+            if count > 1 {
+                self.pending_unmaps.insert(window, count - 1);
+            }
+            return;
+        }
+        if self.clients.get_client_by_window(window).is_some() {
+            info!("client withdrew window");
+            self.drop_client(window);
+        }
+    }
+
+    /// Remove the client of a window that isn't shown and forget about it.
+    fn drop_client(&mut self, window: xproto::Window) {
+        if self.zoomed_window == Some(window) {
+            self.zoomed_window = None;
+        }
+        if self.scratchpad == Some(window) {
+            self.scratchpad = None;
+        }
+        self.notify_client_removed(window);
+        self.clients.remove(window);
+        self.update_client_list();
+        self.clear_wm_state(window);
+        self.visible_windows.retain(|w| *w != window);
+        self.arrange_windows();
+        self.reset_focus();
+    }
+
+    /// A mouse button has been pressed on a client, react accordingly.
+    ///
+    /// Focus the client the window belongs to, which raises it if it is
+    /// floating. Afterwards, the click is replayed, so that the client
+    /// receives it.
+    fn handle_button_press(&mut self, ev: &xproto::ButtonPressEvent) {
+        let window = ev.event();
+        match ClickAction::for_click(window, self.root, self.focused_window) {
+            ClickAction::Drag => {
+                self.start_drag(ev);
+                return;
+            },
+            ClickAction::FocusAndReplay => self.focus_client(window),
+            ClickAction::Replay => (),
+        }
+        let cookie = xproto::allow_events(
+            self.con, xproto::ALLOW_REPLAY_POINTER as u8, ev.time());
+        if cookie.request_check().is_err() {
+            error!("could not replay button press");
+        }
+    }
+
+    /// Start dragging the floating client under the pointer, moving it with
+    /// the first and resizing it with the third mouse button.
+    fn start_drag(&mut self, ev: &xproto::ButtonPressEvent) {
+        let window = ev.child();
+        let screen = self.window_screen(window);
+        let geometry = match self.clients.get_client_by_window(window) {
+            Some(ref client) if self.is_shown_floating(window) =>
+                client
+                    .borrow_mut()
+                    .float_geometry(&screen, self.config.border_width as u16),
+            _ => return,
+        };
+        if self.focused_window != Some(window) {
+            self.focus_client(window);
+        }
+        self.drag = Some(Drag {
+            window: window,
+            resize: ev.detail() == xproto::BUTTON_INDEX_3 as u8,
+            origin: (ev.root_x(), ev.root_y()),
+            geometry: geometry,
+        });
+    }
+
+    /// The pointer moved while a mouse button was held, react accordingly.
+    ///
+    /// Moves or resizes the dragged client by the distance the pointer has
+    /// travelled since the drag started.
+    fn handle_motion_notify(&mut self, ev: &xproto::MotionNotifyEvent) {
+        let (window, geometry) = match self.drag {
+            Some(ref drag) => {
+                let dx = ev.root_x() as i32 - drag.origin.0 as i32;
+                let dy = ev.root_y() as i32 - drag.origin.1 as i32;
+                (drag.window, drag.geometry.dragged(dx, dy, drag.resize))
+            },
+            None => return,
+        };
+        self.clients.update_client(window, |mut cl| {
+            cl.set_float_geometry(geometry.clone());
+            WmCommand::NoCommand
+        });
+        self.set_geometry(window, &geometry, true);
+    }
+
+    /// A mouse button has been released, finishing any drag.
+    fn handle_button_release(&mut self, _: &xproto::ButtonReleaseEvent) {
+        if self.drag.take().is_some() {
+            self.arrange_windows();
+        }
+    }
+
+    /// The pointer entered a window, react accordingly.
+    ///
+    /// If focus follows the mouse, focus the client the window belongs to,
+    /// once the pointer has rested on it long enough. Windows moved under
+    /// the pointer while arranging them don't get focused.
+    fn handle_enter_notify(&mut self, ev: &xproto::EnterNotifyEvent) {
+        if !self.config.focus_follows_mouse ||
+           ev.mode() != xproto::NOTIFY_MODE_NORMAL as u8 ||
+           ev.detail() == xproto::NOTIFY_DETAIL_INFERIOR as u8 {
+            return;
+        }
+        if self.caused_by_arrange(unsafe { (*ev.ptr).sequence }) {
+            return;
+        }
+        let window = ev.event();
+        if dwell_on(&mut self.timers, Instant::now(), window,
+                    self.focused_window, self.config.focus_dwell) {
+            self.focus_client(window);
+        }
+    }
+
+    /// The pointer left a window, react accordingly.
+    ///
+    /// Cancel focusing the window if the pointer didn't rest on it long
+    /// enough.
+    fn handle_leave_notify(&mut self, ev: &xproto::LeaveNotifyEvent) {
+        let window = ev.event();
+        self.timers.cancel(|t| *t == Timer::FocusDwell(window));
+    }
+
+    /// Focus the client corresponding to a window on the tagset it is shown
+    /// on.
+    ///
+    /// If the window is shown on another output, that output becomes the
+    /// current one.
+    fn focus_client(&mut self, window: xproto::Window) {
+        let output = self.window_outputs.get(&window).cloned();
+        if let Some(index) = output {
+            self.outputs.focus(index);
+        }
+        let focused = match self.outputs.current().tag_stack.current() {
+            Some(tagset) => self.clients.focus_window(&tagset.tags, window),
+            None => false,
+        };
+        if focused {
+            self.reset_focus();
+        }
+    }
+
+    /// Make the output under the pointer the current one, returning whether
+    /// changes have been made.
+    fn focus_pointer_output(&mut self) -> bool {
+        if self.outputs.len() < 2 {
+            return false;
+        }
+        match xproto::query_pointer(self.con, self.root).get_reply() {
+            Ok(reply) => {
+                let index =
+                    self.outputs.index_at(reply.root_x(), reply.root_y());
+                index.map_or(false, |i| self.outputs.focus(i))
+            },
+            Err(_) => {
+                error!("could not query pointer position");
+                false
+            },
+        }
+    }
+
+    /// Get the outputs managed.
+    #[allow(dead_code)]
+    pub fn outputs(&self) -> &OutputSet {
+        &self.outputs
+    }
+
+    /// Render a single line describing the current state, to be shown in a
+    /// status bar.
+    ///
+    /// Consists of the labels of the current tagset's tags, the name of the
+    /// layout in effect, the keyboard mode and the focused client's title,
+    /// like `web/chat [vstack] (normal) | title`.
+    pub fn status_string(&self) -> String {
+        let label = self
+            .outputs
+            .current()
+            .tag_stack
+            .current()
+            .map_or_else(String::new, |tagset| format!(
+                "{} [{}]", tagset.label(),
+                tagset.current_layout().name()));
+        let title = self
+            .focused_window
+            .and_then(|window| self.clients.get_client_by_window(window))
+            .map_or_else(String::new,
+                         |client| client.borrow().name().to_owned());
+        format!("{} ({}) | {}", label, self.mode.name(), title)
+    }
+
+    /// Switch the keyboard mode, publishing its name in the `WM_NAME`
+    /// property of the root window for external programs to show.
+    fn switch_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, self.root,
+            xproto::ATOM_WM_NAME, xproto::ATOM_STRING, 8,
+            mode.name().as_bytes()
+        );
+        if cookie.request_check().is_err() {
+            error!("could not set root window name");
+        }
+    }
+
+    /// Grab the first mouse button on a window to implement click-to-focus.
+    fn grab_buttons(&self, window: xproto::Window) {
+        let cookie = xproto::grab_button(
+            self.con, false, window,
+            xproto::EVENT_MASK_BUTTON_PRESS as u16,
+            xproto::GRAB_MODE_SYNC as u8, xproto::GRAB_MODE_ASYNC as u8,
+            base::NONE, base::NONE,
+            xproto::BUTTON_INDEX_1 as u8, xproto::MOD_MASK_ANY as u16
+        );
+        if cookie.request_check().is_err() {
+            error!("could not grab mouse button");
+        }
+    }
+
+    /// Raise a window above all others.
+    fn raise_window(&self, window: xproto::Window) {
+        let cookie = xproto::configure_window(
+            self.con, window,
+            &[(xproto::CONFIG_WINDOW_STACK_MODE as u16,
+               xproto::STACK_MODE_ABOVE as u32)]);
+        if cookie.request_check().is_err() {
+            error!("could not raise window");
+        }
+    }
+
+    /// Lower a window below all others.
+    fn lower_window(&self, window: xproto::Window) {
+        let cookie = xproto::configure_window(
+            self.con, window,
+            &[(xproto::CONFIG_WINDOW_STACK_MODE as u16,
+               xproto::STACK_MODE_BELOW as u32)]);
+        if cookie.request_check().is_err() {
+            error!("could not lower window");
+        }
+    }
+
     /// Construct a client for a window, or don't if we don't want to manage it.
     ///
     /// If the window has a type different from `_NET_WM_WINDOW_TYPE_DOCK`,
-    /// generate a client structure for it and return it, otherwise don't.
+    /// `_NET_WM_WINDOW_TYPE_DESKTOP`, `_NET_WM_WINDOW_TYPE_NOTIFICATION` and
+    /// `_NET_WM_WINDOW_TYPE_TOOLTIP`, generate a client structure for it and
+    /// return it, otherwise don't.
     fn construct_client(&self, window: xproto::Window) -> Option<Client> {
         let props = match self.get_properties(window) {
             Some(props) => props,
@@ -514,54 +3026,240 @@ impl<'a> Wm<'a> {
                 return None;
             }
         };
-        if props.window_type != self.lookup_atom("_NET_WM_WINDOW_TYPE_DOCK") {
+        if !self.is_unmanaged_type(&props) {
             // compute tags of the new client
-            let tags = if let Some(res) = self.matching
-                .as_ref()
-                .and_then(|f| f(&props)) {
+            let tags = if let Some(res) = self.matching_tags(&props) {
                 res
-            } else if let Some(tagset) = self.tag_stack.current() {
+            } else if let Some(tagset) =
+                    self.outputs.current().tag_stack.current() {
                 tagset.tags.clone()
             } else {
                 vec![Tag::default()]
             };
-            Some(Client::new(window, tags, props))
+            let floating = self.is_floating_type(&props) || self
+                .matching_rule(&props)
+                .map_or(false, |r| r.floating);
+            let mut client = Client::new(window, tags, props);
+            client.set_urgent(self.get_urgency(window));
+            client.set_size_hints(self.get_size_hints(window));
+            // center the window when floating, keeping the size it asked
+            // for
+            let screen = self.compute_struts(&self.outputs.current().area);
+            if let Ok(geom) =
+                    xproto::get_geometry(self.con, window).get_reply() {
+                client.set_float_geometry(Geometry::centered(
+                    &screen, geom.width(), geom.height(),
+                    self.config.border_width as u16));
+            }
+            if floating {
+                client.toggle_floating();
+            }
+            Some(client)
         } else {
             None
         }
     }
 
+    /// Get the first client rule applying to a client with the given
+    /// properties, if any.
+    fn matching_rule(&self, props: &ClientProps) -> Option<&ClientRule> {
+        self.rules.iter().find(|r| r.matches(props))
+    }
+
+    /// Compute the tags of a client with the given properties, using the
+    /// first rule specifying tags and the matching function as a fallback.
+    fn matching_tags(&self, props: &ClientProps) -> Option<Vec<Tag>> {
+        ClientRule::tags_from(&self.rules, props)
+            .or_else(|| self.matching.as_ref().and_then(|f| f(props)))
+    }
+
+    /// Switch the current tagset to the layout a client rule specifies for
+    /// a client with the given properties, if any.
+    fn apply_rule_layout(&mut self, props: &ClientProps) {
+        let layout = self
+            .matching_rule(props)
+            .and_then(|r| r.layout)
+            .and_then(|name| match layout_by_name(name) {
+                Some(layout) => Some(layout),
+                None => {
+                    error!("unknown layout in client rule: {}", name);
+                    None
+                },
+            });
+        if let (Some(layout), Some(tagset)) =
+                (layout, self.outputs.current_mut().tag_stack.current_mut()) {
+            tagset.set_boxed_layout(layout);
+        }
+    }
+
+    /// Check whether window properties mark a window we don't manage.
+    fn is_unmanaged_type(&self, props: &ClientProps) -> bool {
+        ["_NET_WM_WINDOW_TYPE_DOCK", "_NET_WM_WINDOW_TYPE_DESKTOP",
+         "_NET_WM_WINDOW_TYPE_NOTIFICATION", "_NET_WM_WINDOW_TYPE_TOOLTIP"]
+            .iter()
+            .any(|name| props.window_type == self.lookup_atom(name))
+    }
+
+    /// Check whether window properties mark a popup we don't manage, which
+    /// is to be shown above all other windows where it placed itself.
+    fn is_popup_type(&self, props: &ClientProps) -> bool {
+        ["_NET_WM_WINDOW_TYPE_NOTIFICATION", "_NET_WM_WINDOW_TYPE_TOOLTIP"]
+            .iter()
+            .any(|name| props.window_type == self.lookup_atom(name))
+    }
+
+    /// Check whether window properties mark a window to be floating from the
+    /// start, like dialogs.
+    fn is_floating_type(&self, props: &ClientProps) -> bool {
+        ["_NET_WM_WINDOW_TYPE_DIALOG", "_NET_WM_WINDOW_TYPE_UTILITY",
+         "_NET_WM_WINDOW_TYPE_SPLASH"]
+            .iter()
+            .any(|name| props.window_type == self.lookup_atom(name))
+    }
+
     /// Add a client constructed from the parameters to the client store.
     ///
     /// Swaps new client with the master on the current layout if the
     /// currenlty used layout dictates it.
     fn add_client(&mut self, client: Client) {
-        self.clients.add(client);
-        if let Some(tagset) = self.tag_stack.current() {
-            if self.new_window_as_master() {
-                self.clients.swap_master(&tagset);
-            }
+        self.register_client_events(client.window);
+        if self.config.click_to_focus {
+            self.grab_buttons(client.window);
+        }
+        if !client.is_minimized() {
+            self.set_wm_state(client.window, NORMAL_STATE);
+        }
+        let policy =
+            if !client.is_minimized() && self.new_window_as_master() {
+                InsertPolicy::AsMaster
+            } else if self.config.new_window_after_focused {
+                InsertPolicy::AfterFocused
+            } else {
+                InsertPolicy::AsSlave
+            };
+        // layouts keeping track of windows learn where the new one went
+        let tagsets = self
+            .outputs
+            .iter_mut()
+            .flat_map(|o| o.tag_stack.tagsets_mut());
+        self.clients.add_to_tagsets(client, policy, tagsets);
+        self.update_client_list();
+    }
+
+    /// Publish the windows of all clients in the `_NET_CLIENT_LIST` property
+    /// of the root window.
+    fn update_client_list(&self) {
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, self.root,
+            self.lookup_atom("_NET_CLIENT_LIST"), xproto::ATOM_WINDOW, 32,
+            &self.clients.windows()
+        );
+        if cookie.request_check().is_err() {
+            error!("could not set client list");
+        }
+    }
+
+    /// Set the ICCCM `WM_STATE` of a window.
+    fn set_wm_state(&self, window: xproto::Window, state: u32) {
+        let atom = self.lookup_atom("WM_STATE");
+        // the state is followed by the icon window, which we don't use
+        let cookie = xproto::change_property(
+            self.con, xproto::PROP_MODE_REPLACE as u8, window,
+            atom, atom, 32, &[state, xproto::WINDOW_NONE]);
+        if cookie.request_check().is_err() {
+            error!("could not set window state");
+        }
+    }
+
+    /// Remove the ICCCM `WM_STATE` of a window we don't manage anymore,
+    /// putting it into the withdrawn state.
+    fn clear_wm_state(&self, window: xproto::Window) {
+        let cookie = xproto::delete_property(
+            self.con, window, self.lookup_atom("WM_STATE"));
+        if cookie.request_check().is_err() {
+            error!("could not clear window state");
+        }
+    }
+
+    /// Register for the events we are interested in on a client's window.
+    fn register_client_events(&self, window: xproto::Window) {
+        let mut mask = xproto::EVENT_MASK_PROPERTY_CHANGE;
+        if self.config.focus_follows_mouse {
+            mask |= xproto::EVENT_MASK_ENTER_WINDOW
+                | xproto::EVENT_MASK_LEAVE_WINDOW;
+        }
+        let cookie = xproto::change_window_attributes(
+            self.con, window, &[(xproto::CW_EVENT_MASK, mask)]);
+        if cookie.request_check().is_err() {
+            error!("could not register for client events");
         }
     }
 
     /// Add a window to the list of unmanaged windows.
+    ///
+    /// If the window reserves space at the screen edges, the tiled windows
+    /// are rearranged to keep it free. Desktop windows are kept below all
+    /// other windows, while notifications and tooltips are raised above
+    /// them.
     fn add_unmanaged(&mut self, window: xproto::Window) {
         self.unmanaged_windows.push(window);
         info!("registered unmanaged window");
+        let desktop = self.lookup_atom("_NET_WM_WINDOW_TYPE_DESKTOP");
+        if let Some(props) = self.get_properties(window) {
+            if props.window_type == desktop {
+                self.lower_window(window);
+            } else if self.is_popup_type(&props) {
+                self.raise_window(window);
+            }
+        }
+        if let Some(strut) = self.get_strut(window) {
+            self.struts.insert(window, strut);
+            self.arrange_windows();
+        }
+    }
+
+    /// Get the space a window reserves at the screen edges, if any.
+    ///
+    /// `_NET_WM_STRUT_PARTIAL` takes precedence, the older `_NET_WM_STRUT`
+    /// is used if it isn't set.
+    fn get_strut(&self, window: xproto::Window) -> Option<Strut> {
+        self.get_strut_property(window, "_NET_WM_STRUT_PARTIAL", 12)
+            .or_else(|| self.get_strut_property(window, "_NET_WM_STRUT", 4))
+    }
+
+    /// Get the space reserved at the screen edges from a property holding
+    /// `len` cardinals, the first four being the edges' widths.
+    fn get_strut_property(&self, window: xproto::Window, name: &str,
+                          len: u32) -> Option<Strut> {
+        let cookie = xproto::get_property(
+            self.con, false, window, self.lookup_atom(name),
+            xproto::ATOM_CARDINAL, 0, len
+        );
+        match cookie.get_reply() {
+            Ok(reply) => {
+                let values: &[u32] = reply.value();
+                if values.len() >= 4 {
+                    Some((values[0] as u16, values[1] as u16,
+                          values[2] as u16, values[3] as u16))
+                } else {
+                    None
+                }
+            },
+            Err(_) => None,
+        }
     }
 
     /// Register and get back atoms, return an error on failure.
     fn get_atoms(con: &base::Connection, names: &[&'a str])
-        -> Result<Vec<(xproto::Atom, &'a str)>, WmError> {
+        -> Result<AtomList<'a>, WmError> {
         let mut cookies = Vec::with_capacity(names.len());
-        let mut res: Vec<(xproto::Atom, &'a str)> =
-            Vec::with_capacity(names.len());
+        let mut res = HashMap::with_capacity(names.len());
         for name in names {
             cookies.push((xproto::intern_atom(con, false, name), name));
         }
         for (cookie, name) in cookies {
             match cookie.get_reply() {
-                Ok(r) => res.push((r.atom(), name)),
+                Ok(r) => { res.insert(*name, r.atom()); },
                 Err(_) => {
                     return Err(WmError::CouldNotRegisterAtom(name.to_string()))
                 }
@@ -572,12 +3270,7 @@ impl<'a> Wm<'a> {
 
     /// Get an atom by name.
     fn lookup_atom(&self, name: &str) -> xproto::Atom {
-        self.atoms[
-            self.atoms
-                .iter()
-                .position(|&(_, n)| n == name)
-                .expect("unregistered atom used!")
-        ].0
+        *self.atoms.get(name).expect("unregistered atom used!")
     }
 
     /// Get a window's properties (like window type and such), if possible.
@@ -601,10 +3294,57 @@ impl<'a> Wm<'a> {
             xproto::ATOM_WM_CLASS, xproto::ATOM_STRING,
             0, 0xffffffff
         );
+        // request supported protocols
+        let cookie4 = xproto::get_property(
+            self.con, false, window,
+            self.lookup_atom("WM_PROTOCOLS"), xproto::ATOM_ATOM,
+            0, 0xffffffff
+        );
+        // request the initial state and input model
+        let cookie5 = xproto::get_property(
+            self.con, false, window,
+            xproto::ATOM_WM_HINTS, xproto::ATOM_WM_HINTS, 0, 9
+        );
+        // request the owning process
+        let cookie6 = xproto::get_property(
+            self.con, false, window,
+            self.lookup_atom("_NET_WM_PID"), xproto::ATOM_CARDINAL, 0, 1
+        );
+        // request the time of the last user activity
+        let cookie7 = xproto::get_property(
+            self.con, false, window,
+            self.lookup_atom("_NET_WM_USER_TIME"), xproto::ATOM_CARDINAL,
+            0, 1
+        );
         // check for replies
         if let (Ok(r1), Ok(r2), Ok(r3)) = (cookie1.get_reply(),
                                            cookie2.get_reply(),
                                            cookie3.get_reply()) {
+            // a missing protocol list means no protocols are supported
+            let protocols: Vec<xproto::Atom> = cookie4
+                .get_reply()
+                .map(|r| r.value().to_vec())
+                .unwrap_or_default();
+            let hints: Vec<u32> = cookie5
+                .get_reply()
+                .map(|r| r.value().to_vec())
+                .unwrap_or_default();
+            let (start_iconic, accepts_input) = parse_wm_hints(&hints);
+            // not all clients set their pid
+            let pid = cookie6
+                .get_reply()
+                .ok()
+                .and_then(|r| {
+                    let values: &[u32] = r.value();
+                    values.first().cloned()
+                });
+            let user_time = cookie7
+                .get_reply()
+                .ok()
+                .and_then(|r| {
+                    let values: &[u32] = r.value();
+                    values.first().cloned()
+                });
             unsafe {
                 // we need to get exactly one atom for the type
                 let type_atoms: &[xproto::Atom] = r1.value();
@@ -638,6 +3378,11 @@ impl<'a> Wm<'a> {
                     window_type: type_atoms[0].clone(),
                     name: name.into_owned(),
                     class: class,
+                    protocols: protocols,
+                    start_iconic: start_iconic,
+                    accepts_input: accepts_input,
+                    pid: pid,
+                    user_time: user_time,
                 })
             }
         } else {
@@ -645,10 +3390,101 @@ impl<'a> Wm<'a> {
         }
     }
 
+    /// Check whether a window has the urgency hint set in it's `WM_HINTS`.
+    fn get_urgency(&self, window: xproto::Window) -> bool {
+        let cookie = xproto::get_property(
+            self.con, false, window,
+            xproto::ATOM_WM_HINTS, xproto::ATOM_WM_HINTS, 0, 9
+        );
+        match cookie.get_reply() {
+            Ok(reply) => {
+                let values: &[u32] = reply.value();
+                // the urgency hint is the ninth bit of the flags
+                values.first().map_or(false, |flags| flags & (1 << 8) != 0)
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Get the size constraints of a window from `WM_NORMAL_HINTS`.
+    ///
+    /// Windows without the property aren't constrained at all.
+    fn get_size_hints(&self, window: xproto::Window) -> SizeHints {
+        let cookie = xproto::get_property(
+            self.con, false, window,
+            xproto::ATOM_WM_NORMAL_HINTS, xproto::ATOM_WM_SIZE_HINTS, 0, 18
+        );
+        let values: Vec<u32> = match cookie.get_reply() {
+            Ok(reply) => reply.value().to_vec(),
+            Err(_) => return SizeHints::default(),
+        };
+        if values.len() < 18 {
+            return SizeHints::default();
+        }
+        // the flags determine which of the values are set
+        let flag = |bit: u32| values[0] & (1 << bit) != 0;
+        let pair = |i: usize| (values[i] as u16, values[i + 1] as u16);
+        let base_size = if flag(8) {
+            pair(15)
+        } else if flag(4) {
+            // the minimal size is used as base size if there is none
+            pair(5)
+        } else {
+            (0, 0)
+        };
+        SizeHints {
+            min_size: if flag(4) { Some(pair(5)) } else { None },
+            max_size: if flag(5) { Some(pair(7)) } else { None },
+            resize_inc: if flag(6) { Some(pair(9)) } else { None },
+            base_size: base_size,
+            min_aspect:
+                if flag(7) { Some((values[11], values[12])) } else { None },
+            max_aspect:
+                if flag(7) { Some((values[13], values[14])) } else { None },
+        }
+    }
+
+    /// Get a window's title, preferring `_NET_WM_NAME` over `WM_NAME`.
+    fn get_name(&self, window: xproto::Window) -> Option<String> {
+        let cookie1 = xproto::get_property(
+            self.con, false, window,
+            self.lookup_atom("_NET_WM_NAME"), self.lookup_atom("UTF8_STRING"),
+            0, 0xffffffff
+        );
+        let cookie2 = xproto::get_property(
+            self.con, false, window,
+            xproto::ATOM_WM_NAME, xproto::ATOM_STRING, 0, 0xffffffff
+        );
+        cookie1
+            .get_reply()
+            .ok()
+            .and_then(|r| if r.value_len() > 0 { Some(r) } else { None })
+            .or_else(|| cookie2.get_reply().ok())
+            .map(|r| {
+                let value: &[u8] = r.value();
+                String::from_utf8_lossy(value).into_owned()
+            })
+    }
+
+    /// Check whether the client corresponding to a window supports a
+    /// protocol.
+    fn supports_protocol(&self, window: xproto::Window, atom: &'static str)
+        -> bool {
+        let atom = self.lookup_atom(atom);
+        self.clients
+            .get_client_by_window(window)
+            .map_or(false, |c| c.borrow().props().protocols.contains(&atom))
+    }
+
     /// Send an atomic event to a client specified by a window.
-    fn send_event(&self, window: xproto::Window, atom: &'static str) -> bool {
-        let data = [self.lookup_atom(atom), 0, 0, 0, 0].as_ptr()
-            as *const xcb_client_message_data_t;
+    ///
+    /// Callers are expected to check whether the client supports the
+    /// protocol in question using `supports_protocol`.
+    fn send_event(&self, window: xproto::Window, atom: &'static str)
+        -> Result<(), base::GenericError> {
+        // the array has to outlive the pointer to it
+        let values = [self.lookup_atom(atom), 0, 0, 0, 0];
+        let data = values.as_ptr() as *const xcb_client_message_data_t;
         let event = unsafe {
             xproto::ClientMessageEvent::new(
                 32, window, self.lookup_atom("WM_PROTOCOLS"), *data)
@@ -656,6 +3492,338 @@ impl<'a> Wm<'a> {
         xproto::send_event(self.con, false, window,
                            xproto::EVENT_MASK_NO_EVENT, &event)
             .request_check()
-            .is_err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wm::config::generate_config;
+    use wm::timer::MockClock;
+
+    fn props() -> ClientProps {
+        ClientProps {
+            window_type: 0,
+            name: String::new(),
+            class: Vec::new(),
+            protocols: Vec::new(),
+            start_iconic: false,
+            accepts_input: true,
+            pid: None,
+            user_time: None,
+        }
+    }
+
+    #[test]
+    fn detached_children_get_own_session() {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("cat /proc/$$/stat");
+        let output = detach(&mut command).output().unwrap();
+        let stat = String::from_utf8(output.stdout).unwrap();
+        let pid = stat.split_whitespace().next().unwrap();
+        // the fields after the program name are state, parent, process
+        // group and session
+        let fields: Vec<&str> = stat
+            .rsplit(')')
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        assert_eq!(fields[3], pid);
+    }
+
+    #[test]
+    fn close_clients_supporting_delete_politely() {
+        assert_eq!(CloseAction::for_protocols(&[3, 7], 7),
+                   CloseAction::Delete);
+        assert_eq!(CloseAction::for_protocols(&[3], 7), CloseAction::Kill);
+        assert_eq!(CloseAction::for_protocols(&[], 7), CloseAction::Kill);
+    }
+
+    #[test]
+    fn kill_after_grace_period() {
+        let start = Instant::now();
+        let mut timers = TimerQueue::new();
+        timers.schedule(start, Duration::from_secs(5), Timer::KillClient(1));
+        timers.schedule(start, Duration::from_secs(5), Timer::KillClient(2));
+        assert!(timers.pop_due(start + Duration::from_secs(4)).is_empty());
+        // a client closing in time doesn't get killed
+        timers.cancel(|t| t.concerns(2));
+        assert_eq!(timers.pop_due(start + Duration::from_secs(5)),
+                   vec![Timer::KillClient(1)]);
+    }
+
+    #[test]
+    fn kill_clients_without_delete_protocol() {
+        let (take_focus, delete_window) = (5, 7);
+        let mut props = props();
+        props.protocols.push(take_focus);
+        assert_eq!(CloseAction::for_protocols(&props.protocols,
+                                              delete_window),
+                   CloseAction::Kill);
+        props.protocols.push(delete_window);
+        assert_eq!(CloseAction::for_protocols(&props.protocols,
+                                              delete_window),
+                   CloseAction::Delete);
+    }
+
+    #[test]
+    fn restore_tags_from_desktops() {
+        let mut config = generate_config();
+        config.tag_order = vec![Tag::Web, Tag::Chat, Tag::Media];
+        assert_eq!(config.desktop_of(&[Tag::Web]), Some(0));
+        // the first tag in display order counts
+        assert_eq!(config.desktop_of(&[Tag::Media, Tag::Chat]), Some(1));
+        assert_eq!(config.desktop_of(&[Tag::Logs]), None);
+        assert_eq!(config.desktop_of(&[]), None);
+        assert_eq!(config.tags_of_desktop(2), Some(vec![Tag::Media]));
+        assert_eq!(config.tags_of_desktop(3), None);
+        assert_eq!(config.tags_of_desktop(0xffffffff), None);
+        for tag in config.tag_order.iter() {
+            let desktop = config.desktop_of(&[tag.clone()]).unwrap();
+            assert_eq!(config.tags_of_desktop(desktop),
+                       Some(vec![tag.clone()]));
+        }
+    }
+
+    #[test]
+    fn sort_tags_by_display_order() {
+        let mut config = generate_config();
+        config.tag_order = vec![Tag::Chat, Tag::Web, Tag::Media];
+        assert_eq!(config.display_order(&[Tag::Media, Tag::Chat, Tag::Web]),
+                   vec![Tag::Chat, Tag::Web, Tag::Media]);
+        // tags not listed keep their order and come last
+        assert_eq!(config.display_order(&[Tag::Mon, Tag::Web, Tag::Logs]),
+                   vec![Tag::Web, Tag::Mon, Tag::Logs]);
+        assert!(config.display_order(&[]).is_empty());
+    }
+
+    fn area(offset_x: u16, offset_y: u16, width: u16, height: u16)
+        -> ScreenSize {
+        ScreenSize {
+            offset_x: offset_x,
+            offset_y: offset_y,
+            width: width,
+            height: height,
+        }
+    }
+
+    #[test]
+    fn ignored_struts_leave_full_screen() {
+        let screen = area(0, 0, 800, 600);
+        let struts = [(0, 0, 20, 0), (10, 0, 0, 30)];
+        assert_eq!(free_area(&screen, (800, 600), &struts, true),
+                   area(10, 20, 790, 550));
+        assert_eq!(free_area(&screen, (800, 600), &struts, false), screen);
+        assert_eq!(free_area(&screen, (800, 600), &[], true), screen);
+    }
+
+    #[test]
+    fn report_screen_area_left_by_struts() {
+        // a panel at the top of the right output of two
+        let screen = area(800, 0, 800, 600);
+        let struts = [(0, 0, 24, 0)];
+        let free = free_area(&screen, (1600, 600), &struts, true);
+        assert_eq!(format_screen("DP-1", &free), "DP-1 800 24 800 576\n");
+    }
+
+    #[test]
+    fn focus_clicked_clients_and_replay() {
+        assert_eq!(ClickAction::for_click(1, 1, Some(2)), ClickAction::Drag);
+        assert_eq!(ClickAction::for_click(2, 1, Some(3)),
+                   ClickAction::FocusAndReplay);
+        assert_eq!(ClickAction::for_click(2, 1, None),
+                   ClickAction::FocusAndReplay);
+        assert_eq!(ClickAction::for_click(2, 1, Some(2)),
+                   ClickAction::Replay);
+    }
+
+    #[test]
+    fn mark_new_clients_off_tag_urgent() {
+        let client = Client::new(1, vec![Tag::Web], props());
+        let policy = FocusOnMap::SameTagOnly;
+        assert_eq!(policy.new_client_focus(&client, true),
+                   NewClientFocus::Focus);
+        assert_eq!(policy.new_client_focus(&client, false),
+                   NewClientFocus::MarkUrgent);
+        // windows mapped without user activity don't steal the focus
+        let mut props = props();
+        props.user_time = Some(0);
+        let quiet = Client::new(2, vec![Tag::Web], props);
+        assert_eq!(policy.new_client_focus(&quiet, true),
+                   NewClientFocus::KeepFocus);
+        assert_eq!(policy.new_client_focus(&quiet, false),
+                   NewClientFocus::MarkUrgent);
+        // other policies never mark clients urgent
+        assert_eq!(FocusOnMap::Never.new_client_focus(&client, true),
+                   NewClientFocus::KeepFocus);
+        assert_eq!(FocusOnMap::Never.new_client_focus(&client, false),
+                   NewClientFocus::Focus);
+        assert_eq!(FocusOnMap::Always.new_client_focus(&quiet, true),
+                   NewClientFocus::Focus);
+    }
+
+    #[test]
+    fn focus_after_dwelling() {
+        let mut clock = MockClock::new(Duration::from_millis(50));
+        let mut timers = TimerQueue::new();
+        assert!(!dwell_on(&mut timers, clock.now(), 1, None, 150));
+        clock.advance();
+        clock.advance();
+        assert!(timers.pop_due(clock.now()).is_empty());
+        // entering another window cancels focusing the first one
+        assert!(!dwell_on(&mut timers, clock.now(), 2, None, 150));
+        clock.advance();
+        clock.advance();
+        assert!(timers.pop_due(clock.now()).is_empty());
+        clock.advance();
+        assert_eq!(timers.pop_due(clock.now()), vec![Timer::FocusDwell(2)]);
+        // leaving the window cancels focusing it
+        assert!(!dwell_on(&mut timers, clock.now(), 3, Some(2), 150));
+        timers.cancel(|t| *t == Timer::FocusDwell(3));
+        for _ in 0..5 {
+            clock.advance();
+        }
+        assert!(timers.pop_due(clock.now()).is_empty());
+        // the focused window isn't focused again
+        assert!(!dwell_on(&mut timers, clock.now(), 2, Some(2), 150));
+        assert_eq!(timers.timeout(clock.now()), None);
+        assert!(dwell_on(&mut timers, clock.now(), 3, Some(2), 0));
+        assert_eq!(timers.timeout(clock.now()), None);
+    }
+
+    #[test]
+    fn idle_until_input() {
+        let mut clock = MockClock::new(Duration::from_secs(1));
+        let mut timers = TimerQueue::new();
+        let mut idle = false;
+        assert!(restart_idle(&mut timers, clock.now(), 3));
+        clock.advance();
+        clock.advance();
+        assert!(timers.pop_due(clock.now()).is_empty());
+        // input restarts the timeout
+        assert!(restart_idle(&mut timers, clock.now(), 3));
+        clock.advance();
+        clock.advance();
+        assert!(timers.pop_due(clock.now()).is_empty());
+        clock.advance();
+        for timer in timers.pop_due(clock.now()) {
+            assert_eq!(timer, Timer::Idle);
+            idle = true;
+        }
+        assert!(idle);
+        // staying idle doesn't fire the timer again
+        clock.advance();
+        assert!(timers.pop_due(clock.now()).is_empty());
+        // input ends the idle state
+        if restart_idle(&mut timers, clock.now(), 3) {
+            idle = false;
+        }
+        assert!(!idle);
+        assert_eq!(timers.timeout(clock.now()),
+                   Some(Duration::from_secs(3)));
+        // without a timeout, no timer is scheduled
+        let mut timers = TimerQueue::new();
+        assert!(!restart_idle(&mut timers, clock.now(), 0));
+        assert_eq!(timers.timeout(clock.now()), None);
+    }
+
+    #[test]
+    fn autostart_once_on_emptied_tag() {
+        let mut clock = MockClock::new(Duration::from_secs(1));
+        let commands = vec![(Tag::Web, String::from("termite"))];
+        let mut last_run = HashMap::new();
+        let mut clients = ClientSet::new();
+        clients.add(Client::new(1, vec![Tag::Web, Tag::Work2], props()),
+                    InsertPolicy::AsSlave);
+        clients.remove(1);
+        let mut spawned = Vec::new();
+        for tag in &[Tag::Web, Tag::Work2] {
+            if clients.is_tag_empty(tag) {
+                spawned.extend(autostart_command(
+                    &commands, &mut last_run, clock.now(), tag));
+            }
+        }
+        assert_eq!(spawned, vec!["termite"]);
+        // the tag emptied again before the interval passed
+        clock.advance();
+        assert_eq!(autostart_command(
+            &commands, &mut last_run, clock.now(), &Tag::Web), None);
+        for _ in 0..AUTOSTART_INTERVAL {
+            clock.advance();
+        }
+        assert_eq!(autostart_command(
+            &commands, &mut last_run, clock.now(), &Tag::Web),
+            Some("termite"));
+    }
+
+    #[test]
+    fn failed_map_restores_output() {
+        let mut outputs = OutputSet::new(vec![
+            Output::new(String::from("DP-1"), area(0, 0, 800, 600)),
+            Output::new(String::from("DP-2"), area(800, 0, 800, 600)),
+        ]);
+        // the pointer is on the second output
+        outputs.focus(1);
+        assert!(!map_new_window(&mut outputs, 0, || false));
+        assert_eq!(outputs.current_index(), 0);
+        outputs.focus(1);
+        assert!(map_new_window(&mut outputs, 0, || true));
+        assert_eq!(outputs.current_index(), 1);
+    }
+
+    #[test]
+    fn zoom_and_restore() {
+        let screen = area(0, 0, 100, 60);
+        let geometry = Geometry { x: 50, y: 0, width: 48, height: 58 };
+        let zoomed = toggled_zoom(None, Some(2), true);
+        assert_eq!(zoomed, Some(2));
+        assert_eq!(tiled_geometry(&geometry, &screen, 1, true, None),
+                   (Geometry { x: 9, y: 5, width: 80, height: 48 }, true));
+        // toggling again restores the geometry assigned by the layout
+        assert_eq!(toggled_zoom(zoomed, Some(2), true), None);
+        assert_eq!(tiled_geometry(&geometry, &screen, 1, false, None),
+                   (geometry.clone(), false));
+        // floating clients aren't zoomed
+        assert_eq!(toggled_zoom(None, Some(3), false), None);
+        assert_eq!(toggled_zoom(None, None, false), None);
+    }
+
+    #[test]
+    fn border_only_on_focused_client() {
+        let mut config = generate_config();
+        config.border_width = 2;
+        config.border_on_focus_only = true;
+        assert_eq!(config.border_width_for(false, true), 2);
+        assert_eq!(config.border_width_for(false, false), 0);
+        // windows shown alone have no border, even when focused
+        assert_eq!(config.border_width_for(true, true), 0);
+        config.border_on_focus_only = false;
+        assert_eq!(config.border_width_for(false, false), 2);
+        assert_eq!(config.border_width_for(true, false), 0);
+    }
+
+    #[test]
+    fn floating_clients_can_be_moved_and_resized() {
+        assert_eq!(allowed_actions(false), vec!["_NET_WM_ACTION_CLOSE"]);
+        assert_eq!(allowed_actions(true),
+                   vec!["_NET_WM_ACTION_CLOSE", "_NET_WM_ACTION_MOVE",
+                        "_NET_WM_ACTION_RESIZE"]);
+        // every action advertised is interned
+        for name in allowed_actions(true) {
+            assert!(ATOM_VEC.contains(&name));
+        }
+    }
+
+    #[test]
+    fn iconic_start_read_from_wm_hints() {
+        // no hints at all
+        assert_eq!(parse_wm_hints(&[]), (false, true));
+        // initial state set to iconic, input hint set to false
+        assert_eq!(parse_wm_hints(&[3, 0, ICONIC_STATE]), (true, false));
+        // initial state ignored as its flag is unset
+        assert_eq!(parse_wm_hints(&[1, 1, ICONIC_STATE]), (false, true));
+        // normal initial state
+        assert_eq!(parse_wm_hints(&[2, 0, 1]), (false, true));
     }
 }